@@ -5,22 +5,44 @@ document.addEventListener('DOMContentLoaded', () => {
     const previewModal = document.getElementById('preview-modal');
     const previewContent = document.getElementById('preview-content');
     const closeModal = document.getElementById('close-modal');
+    let currentPath = '/';
+    let trashEnabled = false;
+    // Big folders arrive in pages; "Load more" follows next_cursor
+    const PAGE_SIZE = 500;
+    const sortSelect = document.getElementById('sort-select');
+    let currentSort = { sort: 'name', order: 'asc' };
+    let nextCursor = null;
+    let renderEntry = null;
+    const searchInput = document.getElementById('search-input');
+    let searchAbort = null;
+    // Live updates: the server pushes changes in the open folder (/api/events, SSE)
+    let liveEvents = null;
+    let livePath = null;
+    let liveTimer = null;
+    // Paths ticked for "Download Selected"; kept while navigating between folders
+    const selected = new Set();
 
     // Initial State Check (if injected by server)
     if (window.INITIAL_DATA) {
         renderDirectory(window.INITIAL_DATA);
     }
 
+    function listUrl(path, cursor) {
+        const params = new URLSearchParams({ format: 'json', limit: PAGE_SIZE, ...currentSort });
+        if (cursor) params.set('cursor', cursor);
+        return `/list${encodePath(path)}?${params}`;
+    }
+
     // Navigation Handler
     async function navigate(path) {
         try {
-            const url = `/list${path}?format=json`;
+            const url = listUrl(path);
             const response = await fetch(url);
             if (!response.ok) throw new Error('Network response was not ok');
             const data = await response.json();
 
             // Update History
-            history.pushState({ path }, '', `/list${path}`);
+            history.pushState({ path }, '', `/list${encodePath(path)}`);
 
             renderDirectory(data);
         } catch (error) {
@@ -29,13 +51,209 @@ document.addEventListener('DOMContentLoaded', () => {
         }
     }
 
+    // Re-render the current directory after a change (no history entry)
+    async function refresh() {
+        const response = await fetch(listUrl(currentPath));
+        if (response.ok) renderDirectory(await response.json());
+    }
+
+    sortSelect.addEventListener('change', () => {
+        const [sort, order] = sortSelect.value.split(':');
+        currentSort = { sort, order };
+        refresh();
+    });
+
+    // Recursive name search from the current folder (/api/search, streamed NDJSON)
+    searchInput.addEventListener('keydown', (event) => {
+        if (event.key === 'Enter') {
+            event.preventDefault();
+            runSearch(searchInput.value.trim());
+        }
+    });
+    // Fired by the clear button of type="search" inputs
+    searchInput.addEventListener('search', () => {
+        if (!searchInput.value) runSearch('');
+    });
+
+    function cancelSearch() {
+        if (searchAbort) searchAbort.abort();
+        searchAbort = null;
+    }
+
+    async function runSearch(query) {
+        cancelSearch();
+        if (!query) {
+            refresh();
+            return;
+        }
+        const controller = new AbortController();
+        searchAbort = controller;
+        if (document.getElementById('content-search').checked) {
+            await searchContents(query, controller);
+            return;
+        }
+
+        // Wildcards turn the query into a glob ("*.jpg", "report-202?.pdf")
+        const params = new URLSearchParams({ path: currentPath });
+        params.set(/[*?[]/.test(query) ? 'glob' : 'q', query);
+        const count = document.getElementById('listing-count');
+        count.textContent = 'Searching...';
+        listContainer.innerHTML = '';
+        let found = 0;
+
+        try {
+            const response = await fetch(`/api/search?${params}`, { signal: controller.signal });
+            if (!response.ok) throw new Error(await response.text());
+            await readNdjson(response, (event) => {
+                if (event.event === 'match') {
+                    found++;
+                    listContainer.appendChild(searchResult(event));
+                    count.textContent = `Searching... ${found} found`;
+                } else if (event.event === 'done') {
+                    count.textContent = event.truncated
+                        ? `First ${event.matches} results (refine the search for more)`
+                        : `${event.matches} result${event.matches === 1 ? '' : 's'}`;
+                } else if (event.event === 'error') {
+                    count.textContent = `Search stopped: ${event.message}`;
+                }
+            });
+        } catch (err) {
+            if (err.name === 'AbortError') return;
+            console.error(err);
+            count.textContent = `Search failed: ${err.message}`;
+        }
+        if (searchAbort === controller) searchAbort = null;
+    }
+
+    // Full-text search in the server's content index (/api/search/content)
+    async function searchContents(query, controller) {
+        const count = document.getElementById('listing-count');
+        count.textContent = 'Searching file contents...';
+        listContainer.innerHTML = '';
+        try {
+            const response = await fetch(`/api/search/content?q=${encodeURIComponent(query)}`, { signal: controller.signal });
+            if (!response.ok) throw new Error(await response.text());
+            const data = await response.json();
+            data.results.forEach(hit => {
+                const li = searchResult({ ...hit, is_dir: false });
+                // The snippet comes HTML-escaped from the server, with matches in <b>
+                if (hit.snippet) {
+                    const snippet = document.createElement('blockquote');
+                    snippet.style.cssText = 'margin: 0.25em 0 0; padding: 0.25em 0.75em; font-size: 0.85em;';
+                    snippet.innerHTML = hit.snippet;
+                    li.querySelector('.grid > div').appendChild(snippet);
+                }
+                listContainer.appendChild(li);
+            });
+            const indexing = data.indexing ? ' (indexing in progress, results may be incomplete)' : '';
+            count.textContent = `${data.results.length} result${data.results.length === 1 ? '' : 's'} in ${data.documents} indexed files${indexing}`;
+        } catch (err) {
+            if (err.name === 'AbortError') return;
+            console.error(err);
+            count.textContent = `Search failed: ${err.message}`;
+        }
+        if (searchAbort === controller) searchAbort = null;
+    }
+
+    function searchResult(match) {
+        const li = document.createElement('li');
+        li.className = 'entry';
+        const parent = getParentPath(match.path);
+        const details = match.is_dir ? parent : `${parent} · ${formatSize(match.size)}`;
+        li.innerHTML = `
+            <div class="grid">
+                <div>
+                    <a href="#">${match.is_dir ? '📁' : '📄'} ${escapeHtml(match.name)}</a>
+                    <small class="muted">${escapeHtml(details)}</small>
+                </div>
+                <div class="actions">
+                    ${match.is_dir ? '' : `<a href="/download${encodePath(match.path)}" role="button" class="outline contrast" style="font-size: 0.7em; padding: 2px 8px;">⬇</a>`}
+                </div>
+            </div>
+        `;
+        li.querySelector('a').addEventListener('click', (event) => {
+            event.preventDefault();
+            if (match.is_dir) {
+                navigate(match.path);
+            } else {
+                openPreview(match.name, match.path, match.size);
+            }
+        });
+        return li;
+    }
+
+    // One EventSource per folder; reopened when navigating elsewhere
+    function watchFolder(enabled) {
+        if (enabled && liveEvents && livePath === currentPath) return;
+        if (liveEvents) liveEvents.close();
+        liveEvents = null;
+        livePath = null;
+        if (!enabled) return;
+        livePath = currentPath;
+        liveEvents = new EventSource(`/api/events?${new URLSearchParams({ path: currentPath })}`);
+        liveEvents.addEventListener('change', folderChanged);
+        liveEvents.addEventListener('resync', folderChanged);
+    }
+
+    // Bursts (copying many files) are coalesced into a single reload
+    function folderChanged() {
+        clearTimeout(liveTimer);
+        liveTimer = setTimeout(() => {
+            // Don't wipe search results or the extra pages the user has loaded
+            const loaded = listContainer.querySelectorAll('li.entry').length;
+            if (searchAbort || searchInput.value || loaded > PAGE_SIZE) {
+                showStale();
+                return;
+            }
+            refresh();
+        }, 300);
+    }
+
+    function showStale() {
+        const count = document.getElementById('listing-count');
+        if (count.querySelector('.stale')) return;
+        count.insertAdjacentHTML('beforeend', ' · <a href="#" class="stale">Folder changed, refresh</a>');
+        count.querySelector('.stale').addEventListener('click', (event) => {
+            event.preventDefault();
+            refresh();
+        });
+    }
+
+    // Append the next page of a big folder
+    async function loadMore() {
+        if (!nextCursor) return;
+        const response = await fetch(listUrl(currentPath, nextCursor));
+        if (!response.ok) {
+            alert(`Error (${response.status}): ${await response.text()}`);
+            return;
+        }
+        const data = await response.json();
+        listContainer.querySelector('.load-more')?.remove();
+        data.entries.forEach(entry => listContainer.appendChild(renderEntry(entry)));
+        showPaging(data);
+    }
+
+    function showPaging(data) {
+        nextCursor = data.next_cursor || null;
+        const shown = listContainer.querySelectorAll('li.entry').length;
+        const count = document.getElementById('listing-count');
+        count.textContent = data.total > shown ? `${shown} of ${data.total} items` : `${data.total} items`;
+        if (nextCursor) {
+            const li = document.createElement('li');
+            li.className = 'load-more';
+            li.innerHTML = `<a href="#" role="button" class="outline" onclick="event.preventDefault()">Load more (${data.total - shown} left)</a>`;
+            li.querySelector('a').addEventListener('click', loadMore);
+            listContainer.appendChild(li);
+        }
+    }
+
     // Back Button Handler
     window.addEventListener('popstate', (event) => {
         if (event.state && event.state.path) {
             // Re-fetch to ensure freshness, or could cache.
             // Simplified: just reload for now or re-fetch.
             // Let's re-fetch.
-            fetch(`/list${event.state.path}?format=json`)
+            fetch(listUrl(event.state.path))
                 .then(r => r.json())
                 .then(renderDirectory)
                 .catch(e => window.location.reload());
@@ -44,8 +262,70 @@ document.addEventListener('DOMContentLoaded', () => {
 
     // Render Function
     function renderDirectory(data) {
+        currentPath = data.current_path || '/';
+        cancelSearch();
+        searchInput.value = '';
+        if (data.sort) {
+            currentSort = { sort: data.sort, order: data.order };
+            sortSelect.value = `${data.sort}:${data.order}`;
+        }
+
+        // Logged-in users (cookie session) get a logout link
+        const logoutLink = document.getElementById('logout-link');
+        if (logoutLink) {
+            logoutLink.style.display = data.user ? '' : 'none';
+        }
+
+        // Hide the actions the server has disabled (--read-only, --no-clipboard, --no-zip)
+        const features = data.features || { upload: true, clipboard: true, zip: true };
+        const toggle = (id, visible) => {
+            const el = document.getElementById(id);
+            if (el) el.style.display = visible ? '' : 'none';
+        };
+        // Without permission lists (older servers) assume everything is allowed
+        const permissions = data.permissions || ['read', 'upload', 'delete', 'clipboard', 'share'];
+        const can = (perm) => permissions.includes(perm);
+        toggle('upload-link', features.upload && can('upload'));
+        toggle('upload-folder-link', features.upload && can('upload'));
+        toggle('extract-link', features.upload && can('upload'));
+        toggle('mkdir-link', features.upload && can('upload'));
+        toggle('notepad-link', features.clipboard);
+        toggle('trash-link', features.trash && can('delete'));
+        toggle('content-search-option', !!features.content_search);
+        trashEnabled = !!features.trash;
+        watchFolder(!!features.live_updates);
+        updateSelection();
+
+        // File management actions (the /api/fs routes only exist when uploads are on).
+        // Built as elements with listeners: file names never end up inside inline JS
+        const manage = (rawPath) => {
+            const actions = document.createDocumentFragment();
+            if (!features.upload) return actions;
+            const button = (action, label, title) => {
+                const link = document.createElement('a');
+                link.href = '#';
+                link.setAttribute('role', 'button');
+                link.className = 'outline secondary';
+                link.title = title;
+                link.style.cssText = 'font-size: 0.7em; padding: 2px 8px;';
+                link.textContent = label;
+                link.addEventListener('click', (event) => {
+                    event.preventDefault();
+                    fileAction(action, rawPath);
+                });
+                actions.appendChild(link);
+            };
+            if (can('delete')) {
+                button('rename', '✎', 'Rename');
+                button('move', '⇄', 'Move');
+            }
+            if (can('upload')) button('copy', '⧉', 'Copy');
+            if (can('delete')) button('delete', '🗑', 'Delete');
+            return actions;
+        };
+
         // Update Breadcrumbs
-        breadcrumbsContainer.innerHTML = buildBreadcrumbs(data.current_path);
+        breadcrumbsContainer.replaceChildren(buildBreadcrumbs(data.current_path));
 
         // Update List
         listContainer.innerHTML = '';
@@ -53,64 +333,132 @@ document.addEventListener('DOMContentLoaded', () => {
         // Parent Directory Link (if not root)
         if (data.current_path !== '/' && data.current_path !== '') {
             const li = document.createElement('li');
-            li.innerHTML = `<a href="#" onclick="event.preventDefault(); navigate('${getParentPath(data.current_path)}')" class="secondary">⬅ ..</a>`;
+            li.appendChild(navLink('⬅ ..', getParentPath(data.current_path))).className = 'secondary';
             listContainer.appendChild(li);
         }
 
-        data.entries.forEach(entry => {
+        // Checkbox for multi-file downloads (only when archives are allowed)
+        const selectBox = () => features.zip
+            ? '<input type="checkbox" class="select-entry" aria-label="Select" style="margin-right: 0.5em;">'
+            : '';
+
+        // Size, date and item count under each name
+        const details = (entry) => {
+            const parts = [];
+            if (entry.is_dir) {
+                if (entry.children != null) parts.push(`${entry.children} item${entry.children === 1 ? '' : 's'}`);
+            } else {
+                parts.push(formatSize(entry.size));
+            }
+            if (entry.modified) parts.push(new Date(entry.modified * 1000).toLocaleString());
+            return parts.join(' · ');
+        };
+
+        renderEntry = (entry) => {
             const li = document.createElement('li');
-            const icon = entry.is_dir ? '📁' : '📄';
+            li.className = 'entry';
+            const icon = (entry.is_dir ? '📁' : '📄') + (entry.is_symlink ? '↪' : '');
             const name = entry.name;
             // Proper path joining
             const rawPath = data.current_path.endsWith('/') ? `${data.current_path}${name}` : `${data.current_path}/${name}`;
 
-            if (entry.is_dir) {
-                li.innerHTML = `
-                    <div class="grid">
-                        <div>
-                            <a href="#" onclick="event.preventDefault(); navigate('${rawPath}')">
-                                ${icon} ${name}
-                            </a>
-                        </div>
-                        <div class="actions">
-                             <a href="/download${rawPath}" role="button" class="outline contrast" style="font-size: 0.7em; padding: 2px 8px;">ZIP</a>
-                        </div>
+            const downloadUrl = `/download${encodePath(rawPath)}`;
+            const download = entry.is_dir
+                ? (features.zip ? `<a href="${downloadUrl}" role="button" class="outline contrast" style="font-size: 0.7em; padding: 2px 8px;">ZIP</a>` : '')
+                : `<a href="${downloadUrl}" role="button" class="outline contrast" style="font-size: 0.7em; padding: 2px 8px;">⬇</a>`;
+            li.innerHTML = `
+                <div class="grid">
+                    <div>
+                        ${selectBox()}
+                        <a href="#" class="entry-link" title="${escapeHtml(entry.mime || '')}">
+                            ${icon} ${escapeHtml(name)}
+                        </a>
+                        <small class="muted">${details(entry)}</small>
                     </div>
-                `;
-            } else {
-                li.innerHTML = `
-                     <div class="grid">
-                        <div>
-                            <a href="#" onclick="event.preventDefault(); openPreview('${name}', '${rawPath}', ${entry.size})">
-                                ${icon} ${name}
-                            </a>
-                            <small class="muted">(${formatSize(entry.size)})</small>
-                        </div>
-                         <div class="actions">
-                             <a href="/download${rawPath}" role="button" class="outline contrast" style="font-size: 0.7em; padding: 2px 8px;">⬇</a>
-                        </div>
+                    <div class="actions">
+                        ${download}
                     </div>
-                `;
+                </div>
+            `;
+            li.querySelector('.entry-link').addEventListener('click', (event) => {
+                event.preventDefault();
+                if (entry.is_dir) {
+                    navigate(rawPath);
+                } else {
+                    openPreview(name, rawPath, entry.size);
+                }
+            });
+            li.querySelector('.actions').appendChild(manage(rawPath));
+            const checkbox = li.querySelector('.select-entry');
+            if (checkbox) {
+                checkbox.checked = selected.has(rawPath);
+                checkbox.addEventListener('change', () => {
+                    if (checkbox.checked) selected.add(rawPath); else selected.delete(rawPath);
+                    updateSelection();
+                });
             }
-            listContainer.appendChild(li);
-        });
+            return li;
+        };
+
+        data.entries.forEach(entry => listContainer.appendChild(renderEntry(entry)));
+        showPaging(data);
+    }
+
+    function updateSelection() {
+        const link = document.getElementById('download-selected-link');
+        if (!link) return;
+        link.style.display = selected.size ? '' : 'none';
+        document.getElementById('selected-count').textContent = selected.size;
     }
 
+    // One archive with everything ticked (POST /api/download). A real form
+    // submission lets the browser stream the download to disk.
+    window.downloadSelected = function () {
+        if (!selected.size) return;
+        const form = document.createElement('form');
+        form.method = 'POST';
+        form.action = '/api/download';
+        const input = document.createElement('input');
+        input.type = 'hidden';
+        input.name = 'paths';
+        input.value = JSON.stringify([...selected]);
+        form.appendChild(input);
+        document.body.appendChild(form);
+        form.submit();
+        form.remove();
+
+        selected.clear();
+        listContainer.querySelectorAll('.select-entry').forEach(box => { box.checked = false; });
+        updateSelection();
+    };
+
     function buildBreadcrumbs(path) {
-        if (!path || path === '/') return '<ul><li>/</li></ul>';
+        const list = document.createElement('ul');
+        const crumb = (child) => list.appendChild(document.createElement('li')).appendChild(child);
+        if (!path || path === '/') {
+            crumb(document.createTextNode('/'));
+            return list;
+        }
+        crumb(navLink('Home', '/'));
         const parts = path.split('/').filter(p => p);
-        let html = '<ul><li><a href="#" onclick="event.preventDefault(); navigate(\'/\')">Home</a></li>';
         let current = '';
         parts.forEach((part, index) => {
             current += '/' + part;
-            if (index === parts.length - 1) {
-                html += `<li>${part}</li>`;
-            } else {
-                html += `<li><a href="#" onclick="event.preventDefault(); navigate('${current}')">${part}</a></li>`;
-            }
+            crumb(index === parts.length - 1 ? document.createTextNode(part) : navLink(part, current));
+        });
+        return list;
+    }
+
+    // Link that opens a folder inside the app
+    function navLink(label, path) {
+        const link = document.createElement('a');
+        link.href = '#';
+        link.textContent = label;
+        link.addEventListener('click', (event) => {
+            event.preventDefault();
+            navigate(path);
         });
-        html += '</ul>';
-        return html;
+        return link;
     }
 
     function getParentPath(path) {
@@ -120,16 +468,141 @@ document.addEventListener('DOMContentLoaded', () => {
         return '/' + parts.join('/');
     }
 
+    // File Management (/api/fs)
+    window.fileAction = async function (action, path) {
+        const name = path ? path.split('/').pop() : '';
+        const dir = path ? getParentPath(path) : currentPath;
+        const join = (base, child) => (base.endsWith('/') ? base : base + '/') + child;
+        let request;
+
+        if (action === 'mkdir') {
+            const folder = prompt('New folder name:');
+            if (!folder) return;
+            request = fetch('/api/fs/mkdir', jsonPost({ path: join(currentPath, folder) }));
+        } else if (action === 'rename') {
+            const newName = prompt('New name:', name);
+            if (!newName || newName === name) return;
+            request = fetch('/api/fs/move', jsonPost({ from: path, to: join(dir, newName) }));
+        } else if (action === 'move' || action === 'copy') {
+            const suggestion = action === 'copy' ? join(dir, `${name} (copy)`) : path;
+            const target = prompt(`${action === 'copy' ? 'Copy' : 'Move'} to (full path):`, suggestion);
+            if (!target || target === path) return;
+            request = fetch(`/api/fs/${action}`, jsonPost({ from: path, to: target }));
+        } else if (action === 'delete') {
+            const warning = trashEnabled ? 'It will be moved to the trash.' : 'This cannot be undone.';
+            if (!confirm(`Delete ${name}? ${warning}`)) return;
+            request = fetch(`/api/fs/delete${encodePath(path)}`, { method: 'DELETE' });
+        } else {
+            return;
+        }
+
+        try {
+            const response = await request;
+            if (!response.ok) {
+                alert(`Error (${response.status}): ${await response.text()}`);
+            }
+        } catch (err) {
+            console.error(err);
+            alert('Request failed: connection error');
+        }
+        refresh();
+    };
+
+    // Trash (/api/trash)
+    const trashModal = document.getElementById('trash-modal');
+    const trashList = document.getElementById('trash-list');
+
+    window.openTrash = async function () {
+        trashList.innerHTML = '<li aria-busy="true">Loading...</li>';
+        trashModal.showModal();
+        try {
+            const response = await fetch('/api/trash');
+            if (!response.ok) throw new Error(`HTTP ${response.status}`);
+            renderTrash(await response.json());
+        } catch (err) {
+            console.error(err);
+            trashList.innerHTML = '<li>Failed to load the trash.</li>';
+        }
+    };
+
+    function renderTrash(items) {
+        if (!items.length) {
+            trashList.innerHTML = '<li class="muted">The trash is empty.</li>';
+            return;
+        }
+        trashList.innerHTML = '';
+        items.forEach(item => {
+            const li = document.createElement('li');
+            const when = new Date(item.deleted_at * 1000).toLocaleString();
+            const by = item.deleted_by ? ` by ${escapeHtml(item.deleted_by)}` : '';
+            li.innerHTML = `
+                <div class="grid">
+                    <div>
+                        ${item.is_dir ? '📁' : '📄'} ${escapeHtml(item.original_path)}
+                        <br><small class="muted">${item.is_dir ? '' : formatSize(item.size) + ' · '}${when}${by}</small>
+                    </div>
+                    <div class="actions">
+                        <a href="#" role="button" class="outline" style="font-size: 0.7em; padding: 2px 8px;" data-action="restore">Restore</a>
+                        <a href="#" role="button" class="outline secondary" style="font-size: 0.7em; padding: 2px 8px;" data-action="purge">Delete</a>
+                    </div>
+                </div>
+            `;
+            li.querySelectorAll('[data-action]').forEach(link => link.addEventListener('click', (event) => {
+                event.preventDefault();
+                trashAction(link.dataset.action, item.id);
+            }));
+            trashList.appendChild(li);
+        });
+    }
+
+    window.trashAction = async function (action, id) {
+        let response;
+        if (action === 'restore') {
+            response = await fetch(`/api/trash/${id}/restore`, { method: 'POST' });
+            if (response.status === 409) {
+                // The original path is taken again: ask for another one
+                const target = prompt('The original location is taken. Restore to (full path):');
+                if (!target) return;
+                response = await fetch(`/api/trash/${id}/restore`, jsonPost({ to: target }));
+            }
+        } else {
+            if (!confirm('Delete permanently?')) return;
+            response = await fetch(`/api/trash/${id}`, { method: 'DELETE' });
+        }
+        if (!response.ok) alert(`Error (${response.status}): ${await response.text()}`);
+        openTrash();
+        refresh();
+    };
+
+    window.emptyTrash = async function () {
+        if (!confirm('Permanently delete everything in the trash?')) return;
+        const response = await fetch('/api/trash', { method: 'DELETE' });
+        if (!response.ok) alert(`Error (${response.status}): ${await response.text()}`);
+        openTrash();
+    };
+
+    function jsonPost(body) {
+        return {
+            method: 'POST',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify(body),
+        };
+    }
+
+    function encodePath(path) {
+        return path.split('/').map(encodeURIComponent).join('/');
+    }
+
     // Preview Logic
     window.openPreview = function (name, path, size) {
         previewContent.innerHTML = '<p aria-busy="true">Loading...</p>';
         previewModal.showModal();
 
         const ext = name.split('.').pop().toLowerCase();
-        const downloadUrl = `/download${path}`;
+        const downloadUrl = `/download${encodePath(path)}`;
 
         if (['jpg', 'jpeg', 'png', 'gif', 'webp', 'svg'].includes(ext)) {
-            previewContent.innerHTML = `<img src="${downloadUrl}" alt="${name}" style="max-height: 80vh; max-width: 100%;">`;
+            previewContent.innerHTML = `<img src="${downloadUrl}" alt="${escapeHtml(name)}" style="max-height: 80vh; max-width: 100%;">`;
         } else if (['mp4', 'webm', 'ogg'].includes(ext)) {
             previewContent.innerHTML = `<video src="${downloadUrl}" controls style="max-height: 80vh; max-width: 100%;"></video>`;
         } else if (['mp3', 'wav'].includes(ext)) {
@@ -149,7 +622,7 @@ document.addEventListener('DOMContentLoaded', () => {
             previewContent.innerHTML = `
                 <article>
                     <header>Preview not available</header>
-                    <p>File type .${ext} is not supported for preview.</p>
+                    <p>File type .${escapeHtml(ext)} is not supported for preview.</p>
                     <a href="${downloadUrl}" role="button">Download File</a>
                 </article>
             `;
@@ -169,10 +642,14 @@ document.addEventListener('DOMContentLoaded', () => {
         }
     });
 
+    // Safe in text and in quoted attributes
     function escapeHtml(text) {
-        const div = document.createElement('div');
-        div.textContent = text;
-        return div.innerHTML;
+        return String(text)
+            .replace(/&/g, '&amp;')
+            .replace(/</g, '&lt;')
+            .replace(/>/g, '&gt;')
+            .replace(/"/g, '&quot;')
+            .replace(/'/g, '&#39;');
     }
 
     function formatSize(bytes) {
@@ -1,15 +1,28 @@
 let notepadModal;
 let notepadTextarea;
 let notepadStatus;
+let notepadHistory;
+
+// Shared clipboard over a WebSocket (/api/clipboard/ws): the server sends the
+// whole history on connect and then every change; nothing is polled.
+let socket = null;
+let reconnectTimer;
+let entries = [];          // newest first, as the server keeps them
+let currentId = null;      // entry shown in the textarea (null = a new one)
+let currentRevision = 0;
 
 let isTyping = false;
 let typingTimeout;
-let pollInterval;
+// One save in flight at a time: the next one needs the revision it returns
+let pendingRequest = null;
+let dirty = false;
+let requestCounter = 0;
 
 function initNotepad() {
     notepadModal = document.getElementById('notepad-modal');
     notepadTextarea = document.getElementById('notepad-content');
     notepadStatus = document.getElementById('notepad-status');
+    notepadHistory = document.getElementById('notepad-history');
 
     if (!notepadModal || !notepadTextarea) return;
 
@@ -24,11 +37,8 @@ function initNotepad() {
         }, 500);
     });
 
-    // Detect open/close to manage polling
-    // Since we use showModal(), we can also listen to 'close' event
-    notepadModal.addEventListener('close', () => {
-        stopPolling();
-    });
+    // Only connected while the notepad is open
+    notepadModal.addEventListener('close', disconnect);
 }
 
 function toggleNotepad() {
@@ -36,62 +46,185 @@ function toggleNotepad() {
         notepadModal.close();
     } else {
         notepadModal.showModal();
-        startPolling();
+        connect();
     }
 }
 
-async function fetchClipboard() {
-    if (isTyping) return; // Don't overwrite if user is typing
+function connect() {
+    if (socket) return;
+    const protocol = location.protocol === 'https:' ? 'wss' : 'ws';
+    socket = new WebSocket(`${protocol}://${location.host}/api/clipboard/ws`);
+    socket.addEventListener('open', () => notepadStatus.innerText = '(Synced)');
+    socket.addEventListener('message', (event) => handleMessage(JSON.parse(event.data)));
+    socket.addEventListener('close', () => {
+        socket = null;
+        pendingRequest = null;
+        if (!notepadModal.hasAttribute('open')) return;
+        notepadStatus.innerText = 'Offline, reconnecting...';
+        reconnectTimer = setTimeout(connect, 2000);
+    });
+}
+
+function disconnect() {
+    clearTimeout(reconnectTimer);
+    if (socket) socket.close();
+    socket = null;
+}
 
-    try {
-        const response = await fetch('/api/clipboard');
-        if (response.ok) {
-            const text = await response.text();
-
-            // Only update if changed
-            if (notepadTextarea.value !== text) {
-                // Save cursor position if focused
-                const isFocused = document.activeElement === notepadTextarea;
-                const start = notepadTextarea.selectionStart;
-                const end = notepadTextarea.selectionEnd;
-
-                notepadTextarea.value = text;
-
-                if (isFocused) {
-                    notepadTextarea.setSelectionRange(start, end);
-                }
+function handleMessage(message) {
+    switch (message.type) {
+        case 'snapshot':
+            entries = message.entries;
+            // Follow the newest entry unless we're editing one that still exists
+            if (!isTyping && !entries.some(e => e.id === currentId)) {
+                showEntry(entries[0] || null);
             }
+            break;
+        case 'upsert': {
+            const isNew = !entries.some(e => e.id === message.entry.id);
+            entries = [message.entry, ...entries.filter(e => e.id !== message.entry.id)];
+            // Someone else's new snippet becomes the clipboard, as before
+            const follow = message.entry.id === currentId || (isNew && pendingRequest === null && !dirty);
+            if (follow && !isTyping) showEntry(message.entry);
+            break;
         }
-    } catch (e) {
-        console.error("Error fetching clipboard:", e);
+        case 'delete':
+            entries = entries.filter(e => e.id !== message.id);
+            if (message.id === currentId && !isTyping) showEntry(entries[0] || null);
+            break;
+        case 'saved':
+            if (message.request !== pendingRequest) break;
+            pendingRequest = null;
+            // The server may have stored the text as a new entry (conflicting edit)
+            currentId = message.entry.id;
+            currentRevision = message.entry.revision;
+            if (dirty) {
+                saveClipboard();
+            } else {
+                notepadStatus.innerText = 'Saved';
+            }
+            break;
+        case 'error':
+            pendingRequest = null;
+            notepadStatus.innerText = message.message;
+            break;
     }
+    renderHistory();
 }
 
-async function saveClipboard() {
+function showEntry(entry) {
+    currentId = entry ? entry.id : null;
+    currentRevision = entry ? entry.revision : 0;
+    const text = entry ? entry.text : '';
+    if (notepadTextarea.value === text) return;
+
+    // Save cursor position if focused
+    const isFocused = document.activeElement === notepadTextarea;
+    const start = notepadTextarea.selectionStart;
+    const end = notepadTextarea.selectionEnd;
+    notepadTextarea.value = text;
+    if (isFocused) {
+        notepadTextarea.setSelectionRange(start, end);
+    }
+}
+
+function saveClipboard() {
+    if (!socket || socket.readyState !== WebSocket.OPEN) {
+        notepadStatus.innerText = 'Offline, not saved';
+        return;
+    }
+    if (pendingRequest !== null) {
+        dirty = true;
+        return;
+    }
+    dirty = false;
     const text = notepadTextarea.value;
+    pendingRequest = ++requestCounter;
     notepadStatus.innerText = 'Saving...';
-    try {
-        await fetch('/api/clipboard', {
-            method: 'POST',
-            body: text
+    const message = currentId === null
+        ? { op: 'push', text, request: pendingRequest }
+        : { op: 'edit', id: currentId, revision: currentRevision, text, request: pendingRequest };
+    socket.send(JSON.stringify(message));
+}
+
+// Start a new snippet; the current one stays in the history
+function newSnippet() {
+    currentId = null;
+    currentRevision = 0;
+    notepadTextarea.value = '';
+    notepadTextarea.focus();
+}
+
+function renderHistory() {
+    if (!notepadHistory) return;
+    notepadHistory.innerHTML = '';
+    // Pinned snippets first, then the newest
+    const sorted = [...entries.filter(e => e.pinned), ...entries.filter(e => !e.pinned)];
+    for (const entry of sorted) {
+        const li = document.createElement('li');
+        if (entry.id === currentId) li.className = 'current';
+
+        const text = document.createElement('a');
+        text.href = '#';
+        text.title = 'Open in the notepad';
+        text.textContent = (entry.pinned ? '📌 ' : '') + (entry.text.slice(0, 80).replace(/\s+/g, ' ') || '(empty)');
+        text.addEventListener('click', (event) => {
+            event.preventDefault();
+            showEntry(entry);
+            renderHistory();
         });
-        notepadStatus.innerText = 'Saved';
-    } catch (e) {
-        notepadStatus.innerText = 'Error saving';
-        console.error("Error saving clipboard:", e);
+
+        const meta = document.createElement('small');
+        meta.className = 'muted';
+        meta.textContent = ` ${entry.author || 'guest'}, ${formatTime(entry.updated)} `;
+
+        const actions = document.createElement('span');
+        actions.append(
+            historyButton('Copy', () => copyText(entry.text)),
+            historyButton(entry.pinned ? 'Unpin' : 'Pin', () => send({ op: 'pin', id: entry.id, pinned: !entry.pinned })),
+            historyButton('Delete', () => send({ op: 'delete', id: entry.id })),
+        );
+
+        li.append(text, meta, actions);
+        notepadHistory.appendChild(li);
     }
 }
 
-function startPolling() {
-    fetchClipboard();
-    pollInterval = setInterval(fetchClipboard, 2000);
+function historyButton(label, onClick) {
+    const link = document.createElement('a');
+    link.href = '#';
+    link.role = 'button';
+    link.className = 'outline secondary';
+    link.style.padding = '0.1rem 0.4rem';
+    link.style.marginLeft = '0.25rem';
+    link.style.fontSize = '0.8em';
+    link.textContent = label;
+    link.addEventListener('click', (event) => {
+        event.preventDefault();
+        onClick();
+    });
+    return link;
+}
+
+function send(message) {
+    if (socket && socket.readyState === WebSocket.OPEN) socket.send(JSON.stringify(message));
 }
 
-function stopPolling() {
-    clearInterval(pollInterval);
+function formatTime(seconds) {
+    const date = new Date(seconds * 1000);
+    return date.toDateString() === new Date().toDateString() ? date.toLocaleTimeString() : date.toLocaleString();
 }
 
 // Copy/Paste Utils
+async function copyText(text) {
+    try {
+        await navigator.clipboard.writeText(text);
+        notepadStatus.innerText = 'Copied!';
+    } catch (err) {
+        console.error('Failed to copy!', err);
+    }
+}
+
 async function copyToClipboard() {
     try {
         await navigator.clipboard.writeText(notepadTextarea.value);
@@ -108,10 +241,7 @@ async function copyToClipboard() {
 async function pasteFromClipboard() {
     try {
         const text = await navigator.clipboard.readText();
-        // Insert at cursor position or replace?
-        // Usually paste appends or inserts.
-        // For simplicity, let's just append or replace if empty?
-        // Ideally insert at cursor.
+        // Insert at cursor position
         const start = notepadTextarea.selectionStart;
         const end = notepadTextarea.selectionEnd;
         const val = notepadTextarea.value;
@@ -131,6 +261,7 @@ async function pasteFromClipboard() {
 
 // Global expose
 window.toggleNotepad = toggleNotepad;
+window.newSnippet = newSnippet;
 window.copyToClipboard = copyToClipboard;
 window.pasteFromClipboard = pasteFromClipboard;
 
@@ -0,0 +1,166 @@
+// Subidas compartidas por la UI moderna y la legacy.
+// Los archivos pequeños van por multipart a /upload; los grandes usan el
+// protocolo tus (/api/tus) para poder reanudarse si la conexión se corta.
+// `onConflict` (rename, overwrite, skip, fail) cambia la política del servidor;
+// ambas vías devuelven { path, status } con dónde acabó el archivo.
+
+const TUS_THRESHOLD = 8 * 1024 * 1024;
+const TUS_CHUNK_SIZE = 4 * 1024 * 1024;
+const TUS_MAX_RETRIES = 5;
+
+// Nombre con el que se sube: con subcarpetas si viene de una carpeta
+// (<input webkitdirectory> o arrastrada); el servidor crea las que falten.
+function uploadName(file) {
+    return file.relativePath || file.webkitRelativePath || file.name;
+}
+
+// Archivos de un drop, entrando en las carpetas arrastradas
+async function filesFromDataTransfer(dataTransfer) {
+    const entries = Array.from(dataTransfer.items || [])
+        .map(item => item.webkitGetAsEntry && item.webkitGetAsEntry())
+        .filter(Boolean);
+    if (!entries.length) return Array.from(dataTransfer.files);
+
+    const files = [];
+    async function walk(entry, prefix) {
+        if (entry.isFile) {
+            const file = await new Promise((resolve, reject) => entry.file(resolve, reject));
+            file.relativePath = prefix + file.name;
+            files.push(file);
+        } else if (entry.isDirectory) {
+            const reader = entry.createReader();
+            // readEntries devuelve los hijos por tandas hasta una vacía
+            let batch;
+            do {
+                batch = await new Promise((resolve, reject) => reader.readEntries(resolve, reject));
+                for (const child of batch) await walk(child, `${prefix}${entry.name}/`);
+            } while (batch.length);
+        }
+    }
+    for (const entry of entries) await walk(entry, '');
+    return files;
+}
+
+async function uploadFile(file, dirPath, onProgress, onConflict) {
+    const target = dirPath || '/';
+    if (file.size > TUS_THRESHOLD) {
+        return uploadResumable(file, target, onProgress, onConflict);
+    }
+
+    const formData = new FormData();
+    formData.append('file', file, uploadName(file));
+    let url = `/upload?path=${encodeURIComponent(target)}`;
+    if (onConflict) url += `&on_conflict=${onConflict}`;
+    const response = await fetch(url, {
+        method: 'POST',
+        body: formData
+    });
+    if (!response.ok) throw new Error(`Upload failed (${response.status})`);
+    if (onProgress) onProgress(file.size, file.size);
+    const result = await response.json();
+    return result.files[0];
+}
+
+// Upload-Stored-Path viene en base64, como los valores de Upload-Metadata
+function tusResult(response) {
+    const status = response.headers.get('Upload-Status');
+    const stored = response.headers.get('Upload-Stored-Path');
+    if (!status || !stored) return null;
+    return { path: decodeURIComponent(escape(atob(stored))), status };
+}
+
+function tusEncode(value) {
+    return btoa(unescape(encodeURIComponent(value)));
+}
+
+async function tusOffset(location) {
+    const response = await fetch(location, { method: 'HEAD', headers: { 'Tus-Resumable': '1.0.0' } });
+    if (!response.ok) return null;
+    return parseInt(response.headers.get('Upload-Offset'), 10);
+}
+
+async function uploadResumable(file, dirPath, onProgress, onConflict) {
+    // Recordamos la URL de la subida para continuarla tras recargar la página
+    const key = `tus:${dirPath}:${uploadName(file)}:${file.size}:${file.lastModified}`;
+    let location = localStorage.getItem(key);
+    let offset = location ? await tusOffset(location) : null;
+    let result = null;
+
+    if (offset === null) {
+        let metadata = `filename ${tusEncode(uploadName(file))},path ${tusEncode(dirPath)}`;
+        if (onConflict) metadata += `,on_conflict ${tusEncode(onConflict)}`;
+        const response = await fetch('/api/tus', {
+            method: 'POST',
+            headers: {
+                'Tus-Resumable': '1.0.0',
+                'Upload-Length': String(file.size),
+                'Upload-Metadata': metadata
+            }
+        });
+        if (!response.ok) throw new Error(`Upload failed (${response.status})`);
+        location = response.headers.get('Location');
+        localStorage.setItem(key, location);
+        offset = 0;
+        result = tusResult(response);
+    }
+
+    let retries = 0;
+    while (offset < file.size) {
+        try {
+            const response = await fetch(location, {
+                method: 'PATCH',
+                headers: {
+                    'Tus-Resumable': '1.0.0',
+                    'Upload-Offset': String(offset),
+                    'Content-Type': 'application/offset+octet-stream'
+                },
+                body: file.slice(offset, offset + TUS_CHUNK_SIZE)
+            });
+            if (!response.ok) throw new Error(`Upload failed (${response.status})`);
+            offset = parseInt(response.headers.get('Upload-Offset'), 10);
+            result = tusResult(response) || result;
+            retries = 0;
+            if (onProgress) onProgress(offset, file.size);
+        } catch (err) {
+            if (++retries > TUS_MAX_RETRIES) throw err;
+            console.warn(`Retrying upload of ${file.name}:`, err);
+            await new Promise(resolve => setTimeout(resolve, 1000 * retries));
+            const current = await tusOffset(location).catch(() => null);
+            if (current === null) throw err;
+            offset = current;
+        }
+    }
+
+    localStorage.removeItem(key);
+    return result;
+}
+
+// Sube un .zip / .tar / .tar.gz y lo extrae en `dirPath`. El servidor responde
+// con una línea JSON por evento (entry, skipped, done, error) a medida que
+// avanza; `onEvent` recibe cada una.
+async function uploadArchive(file, dirPath, onEvent, onConflict) {
+    const formData = new FormData();
+    formData.append('file', file, file.name);
+    let url = `/upload?path=${encodeURIComponent(dirPath || '/')}&extract=true`;
+    if (onConflict) url += `&on_conflict=${onConflict}`;
+    const response = await fetch(url, { method: 'POST', body: formData });
+    if (!response.ok) throw new Error(`Upload failed (${response.status})`);
+    await readNdjson(response, onEvent);
+}
+
+// Lee una respuesta NDJSON y pasa cada línea ya parseada a `onEvent`.
+async function readNdjson(response, onEvent) {
+    const reader = response.body.getReader();
+    const decoder = new TextDecoder();
+    let buffer = '';
+    for (;;) {
+        const { done, value } = await reader.read();
+        buffer += decoder.decode(value || new Uint8Array(), { stream: !done });
+        const lines = buffer.split('\n');
+        buffer = lines.pop();
+        for (const line of lines) {
+            if (line.trim()) onEvent(JSON.parse(line));
+        }
+        if (done) break;
+    }
+}
@@ -1,48 +1,283 @@
 use axum::{
+    async_trait,
     body::Body,
-    extract::Request,
-    http::{header, StatusCode},
+    extract::{ConnectInfo, FromRequestParts, Request},
+    http::{header, request::Parts, HeaderMap, Method, StatusCode},
     middleware::Next,
-    response::Response,
+    response::{IntoResponse, Redirect, Response},
 };
 use base64::{engine::general_purpose, Engine as _};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    error::AppError,
+    sessions::{self, SessionStore},
+    throttle::FailureThrottle,
+    users::{self, Permission, User, UserTable},
+};
 
 #[derive(Clone)]
 pub struct AuthConfig {
-    pub username: String,
-    pub password: String,
+    pub users: Arc<UserTable>,
+    /// Permisos de las peticiones sin credenciales
+    pub guest: Vec<Permission>,
+    pub sessions: Arc<SessionStore>,
+    /// Credenciales Basic ya verificadas, para no pasar Argon2 en cada petición
+    pub verified: Arc<CredentialCache>,
+    /// Contraseñas fallidas por IP (Basic y formulario de login)
+    pub failures: Arc<FailureThrottle>,
+}
+
+/// Tiempo que vale una verificación Basic antes de repetir Argon2
+const CREDENTIAL_TTL: Duration = Duration::from_secs(5 * 60);
+const CREDENTIAL_CACHE_CAPACITY: usize = 1024;
+
+/// Pares usuario/contraseña que ya pasaron Argon2 hace poco. Sólo guarda un
+/// hash con sal aleatoria del par, nunca la contraseña, y sólo los aciertos:
+/// cada intento fallido sigue costando una verificación completa.
+pub struct CredentialCache {
+    salt: [u8; 32],
+    entries: Mutex<HashMap<[u8; 32], (String, Instant)>>,
+}
+
+impl Default for CredentialCache {
+    fn default() -> Self {
+        Self { salt: rand::random(), entries: Mutex::default() }
+    }
+}
+
+impl CredentialCache {
+    fn key(&self, username: &str, password: &str) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update(self.salt);
+        hasher.update((username.len() as u64).to_le_bytes());
+        hasher.update(username.as_bytes());
+        hasher.update(password.as_bytes());
+        hasher.finalize().into()
+    }
+
+    fn get(&self, username: &str, password: &str) -> bool {
+        let key = self.key(username, password);
+        let entries = self.entries.lock().expect("Lock poisoned");
+        entries.get(&key).is_some_and(|(cached, at)| cached == username && at.elapsed() < CREDENTIAL_TTL)
+    }
+
+    fn insert(&self, username: &str, password: &str) {
+        let key = self.key(username, password);
+        let mut entries = self.entries.lock().expect("Lock poisoned");
+        if entries.len() >= CREDENTIAL_CACHE_CAPACITY {
+            entries.retain(|_, (_, at)| at.elapsed() < CREDENTIAL_TTL);
+            if entries.len() >= CREDENTIAL_CACHE_CAPACITY {
+                entries.clear();
+            }
+        }
+        entries.insert(key, (username.to_string(), Instant::now()));
+    }
+}
+
+impl AuthConfig {
+    /// Comprueba usuario y contraseña con Argon2 fuera de los hilos del runtime.
+    /// `client` (ver [`client_key`]) acumula los fallos; si ya lleva demasiados,
+    /// ni se comprueba. `Ok(None)` si las credenciales no son correctas.
+    pub async fn verify(&self, client: &str, username: &str, password: &str) -> Result<Option<User>, AppError> {
+        if self.failures.check(client).is_err() {
+            return Err(AppError::TooManyRequests);
+        }
+        let (users, name, pass) = (self.users.clone(), username.to_string(), password.to_string());
+        let user = users::verify_blocking(move || users.authenticate(&name, &pass).cloned()).await?;
+        match user {
+            Some(_) => self.failures.success(client),
+            None => self.failures.failure(client),
+        }
+        Ok(user)
+    }
+
+    /// Verifica credenciales Basic, con Argon2 sólo si no se vieron hace poco.
+    /// El usuario se vuelve a buscar en la tabla: sus permisos son los actuales.
+    async fn authenticate_basic(&self, client: &str, username: &str, password: &str) -> Result<Option<User>, AppError> {
+        if self.verified.get(username, password) {
+            return Ok(self.users.get(username).cloned());
+        }
+        let user = self.verify(client, username, password).await?;
+        if user.is_some() {
+            self.verified.insert(username, password);
+        }
+        Ok(user)
+    }
+}
+
+/// Clave de quien hace la petición (su IP) para frenar los intentos fallidos.
+pub fn client_key(addr: Option<SocketAddr>) -> String {
+    addr.map(|addr| addr.ip().to_string()).unwrap_or_default()
+}
+
+/// Rutas accesibles sin credenciales: el formulario de login, sus estilos y
+/// los enlaces compartidos (que llevan su propio token).
+fn is_public(path: &str) -> bool {
+    path == "/login" || path == "/logout" || path.starts_with("/assets/") || path.starts_with("/s/")
+}
+
+/// Quién hace la petición. Lo inserta `auth_middleware` o, sin autenticación
+/// configurada, la capa de [`CurrentUser::open_access`]. Si falta (una ruta
+/// montada fuera de esas capas) el extractor no concede ningún permiso.
+#[derive(Clone, Debug)]
+pub struct CurrentUser {
+    /// `None` para invitados
+    pub username: Option<String>,
+    pub permissions: Vec<Permission>,
+}
+
+impl CurrentUser {
+    /// Servidor sin usuarios: cualquiera puede hacerlo todo.
+    pub fn open_access() -> Self {
+        CurrentUser { username: None, permissions: Permission::ALL.to_vec() }
+    }
+
+    /// Un invitado sin el permiso recibe 401 (para que inicie sesión);
+    /// un usuario autenticado, 403.
+    pub fn require(&self, permission: Permission) -> Result<(), AppError> {
+        if self.permissions.contains(&permission) {
+            Ok(())
+        } else if self.username.is_none() {
+            Err(AppError::Unauthorized)
+        } else {
+            Err(AppError::PermissionDenied)
+        }
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for CurrentUser {
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts.extensions.get::<CurrentUser>().cloned().unwrap_or(CurrentUser {
+            username: None,
+            permissions: Vec::new(),
+        }))
+    }
 }
 
 pub async fn auth_middleware(
     config: AuthConfig,
-    req: Request<Body>,
+    mut req: Request<Body>,
     next: Next,
 ) -> Result<Response, StatusCode> {
+    // 1. Cookie de sesión (navegador) 2. Basic (curl, scripts) 3. Invitado
+    let session_user = sessions::cookie_value(req.headers())
+        .and_then(|cookie| config.sessions.get(cookie))
+        .and_then(|username| config.users.get(&username));
+
+    // Las rutas públicas no piden credenciales, pero el usuario queda explícito
+    if is_public(req.uri().path()) {
+        let current = match session_user {
+            Some(user) => CurrentUser::from(user),
+            None => CurrentUser { username: None, permissions: config.guest.clone() },
+        };
+        req.extensions_mut().insert(current);
+        return Ok(next.run(req).await);
+    }
+
+    let target = req.uri().path_and_query().map(|p| p.to_string()).unwrap_or_else(|| "/".to_string());
+    let client = Client::of(req.method(), req.headers());
+
     let auth_header = req
         .headers()
         .get(header::AUTHORIZATION)
         .and_then(|header| header.to_str().ok());
 
-    if let Some(auth_header) = auth_header {
-        if let Some(credentials) = auth_header.strip_prefix("Basic ") {
-            if let Ok(decoded) = general_purpose::STANDARD.decode(credentials) {
-                if let Ok(decoded_str) = String::from_utf8(decoded) {
-                    if let Some((username, password)) = decoded_str.split_once(':') {
-                        if username == config.username && password == config.password {
-                            return Ok(next.run(req).await);
-                        }
+    let current = match (session_user, auth_header) {
+        (Some(user), _) => CurrentUser::from(user),
+        (None, Some(auth_header)) => {
+            let user = match basic_credentials(auth_header) {
+                Some((username, password)) => {
+                    let addr = req.extensions().get::<ConnectInfo<SocketAddr>>().map(|info| info.0);
+                    match config.authenticate_basic(&client_key(addr), &username, &password).await {
+                        Ok(user) => user,
+                        Err(e) => return Ok(e.into_response()),
                     }
                 }
+                None => None,
+            };
+            match user {
+                Some(user) => CurrentUser::from(&user),
+                None => return Ok(client.login_required(&target)),
+            }
+        }
+        // Sin credenciales: invitado, salvo que no pueda hacer nada
+        (None, None) if config.guest.is_empty() => return Ok(client.login_required(&target)),
+        (None, None) => CurrentUser { username: None, permissions: config.guest.clone() },
+    };
+
+    req.extensions_mut().insert(current);
+    let response = next.run(req).await;
+
+    // Un handler pidió credenciales (p. ej. un invitado intentando subir)
+    if response.status() == StatusCode::UNAUTHORIZED {
+        return Ok(client.login_required(&target));
+    }
+    Ok(response)
+}
+
+impl From<&User> for CurrentUser {
+    fn from(user: &User) -> Self {
+        CurrentUser { username: Some(user.username.clone()), permissions: user.permissions.clone() }
+    }
+}
+
+/// Cómo pedir credenciales según quién hace la petición.
+enum Client {
+    /// El navegador navega a una página: se le lleva al formulario de login
+    Navigation,
+    /// `fetch()` desde la web: un 401 sin desafío, para no abrir el diálogo nativo
+    Script,
+    /// curl y compañía: 401 con desafío Basic
+    Other,
+}
+
+impl Client {
+    fn of(method: &Method, headers: &HeaderMap) -> Self {
+        let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+        match header("Sec-Fetch-Mode") {
+            Some("navigate") if method == Method::GET => Client::Navigation,
+            Some(_) => Client::Script,
+            // Navegadores antiguos sin Sec-Fetch-*
+            None if method == Method::GET && header("Accept").is_some_and(|a| a.contains("text/html")) => Client::Navigation,
+            None => Client::Other,
+        }
+    }
+
+    fn login_required(&self, target: &str) -> Response {
+        match self {
+            Client::Navigation => {
+                let query = serde_urlencoded::to_string([("next", target)]).unwrap_or_default();
+                Redirect::to(&format!("/login?{}", query)).into_response()
             }
+            Client::Script => StatusCode::UNAUTHORIZED.into_response(),
+            Client::Other => challenge(),
         }
     }
+}
 
-    // Si falla la autenticación, pedimos credenciales
-    let response = Response::builder()
+fn basic_credentials(auth_header: &str) -> Option<(String, String)> {
+    let credentials = auth_header.strip_prefix("Basic ")?;
+    let decoded = general_purpose::STANDARD.decode(credentials).ok()?;
+    let decoded_str = String::from_utf8(decoded).ok()?;
+    let (username, password) = decoded_str.split_once(':')?;
+    Some((username.to_string(), password.to_string()))
+}
+
+/// Si falla la autenticación, pedimos credenciales
+pub fn challenge() -> Response {
+    Response::builder()
         .status(StatusCode::UNAUTHORIZED)
         .header(header::WWW_AUTHENTICATE, "Basic realm=\"Restricted Area\"")
         .body(Body::empty())
-        .unwrap();
-
-    Ok(response)
+        .unwrap()
 }
@@ -0,0 +1,166 @@
+// Portapapeles compartido: historial acotado de fragmentos de texto.
+//
+// La entrada más reciente es "el portapapeles" (lo que devuelve
+// `GET /api/clipboard`); las anteriores se conservan hasta `MAX_HISTORY`, y las
+// fijadas no caducan. Cada cambio se reparte por un canal broadcast a los
+// clientes conectados por WebSocket (`/api/clipboard/ws`).
+//
+// Al editar una entrada se indica la `revision` sobre la que se escribió. Si
+// otro la ha cambiado entretanto, es de otro autor o está fijada, el texto se
+// guarda como entrada nueva en vez de pisar la anterior.
+
+use crate::{error::AppError, utils::listing::unix_seconds};
+use serde::Serialize;
+use std::{collections::VecDeque, sync::Mutex, time::SystemTime};
+use tokio::sync::broadcast;
+
+/// Entradas sin fijar que se conservan
+pub const MAX_HISTORY: usize = 50;
+pub const MAX_PINNED: usize = 50;
+/// Bytes de texto por entrada
+pub const MAX_TEXT: usize = 1024 * 1024;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ClipEntry {
+    pub id: u64,
+    pub text: String,
+    /// `None` para invitados
+    pub author: Option<String>,
+    pub created: u64, // segundos UNIX
+    pub updated: u64,
+    /// Sube con cada edición; las ediciones sobre una revisión vieja se bifurcan
+    pub revision: u64,
+    pub pinned: bool,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClipEvent {
+    /// Entrada nueva o modificada (pasa a ser la primera del historial)
+    Upsert { entry: ClipEntry },
+    Delete { id: u64 },
+}
+
+#[derive(Default)]
+struct History {
+    /// De la más reciente a la más antigua
+    entries: VecDeque<ClipEntry>,
+    next_id: u64,
+}
+
+pub struct Clipboard {
+    history: Mutex<History>,
+    events: broadcast::Sender<ClipEvent>,
+}
+
+impl Default for Clipboard {
+    fn default() -> Self {
+        Self { history: Mutex::default(), events: broadcast::channel(256).0 }
+    }
+}
+
+fn now_secs() -> u64 {
+    unix_seconds(SystemTime::now()).unwrap_or(0)
+}
+
+impl Clipboard {
+    pub fn subscribe(&self) -> broadcast::Receiver<ClipEvent> {
+        self.events.subscribe()
+    }
+
+    pub fn entries(&self) -> Vec<ClipEntry> {
+        self.history.lock().expect("Lock poisoned").entries.iter().cloned().collect()
+    }
+
+    /// Texto de la entrada más reciente (vacío si no hay ninguna).
+    pub fn current(&self) -> String {
+        let history = self.history.lock().expect("Lock poisoned");
+        history.entries.front().map(|entry| entry.text.clone()).unwrap_or_default()
+    }
+
+    /// Añade una entrada nueva al principio del historial.
+    pub fn push(&self, text: String, author: Option<String>) -> Result<ClipEntry, AppError> {
+        check_size(&text)?;
+        let mut history = self.history.lock().expect("Lock poisoned");
+        Ok(self.insert(&mut history, text, author))
+    }
+
+    /// Cambia el texto de `id`, o lo guarda aparte si no se puede editar sin
+    /// perder nada (ver el comentario del módulo).
+    pub fn edit(&self, id: u64, revision: u64, text: String, author: Option<String>) -> Result<ClipEntry, AppError> {
+        check_size(&text)?;
+        let mut history = self.history.lock().expect("Lock poisoned");
+        let Some(index) = history.entries.iter().position(|entry| entry.id == id) else {
+            // La borraron mientras se escribía
+            return Ok(self.insert(&mut history, text, author));
+        };
+        let entry = &history.entries[index];
+        if entry.revision != revision || entry.author != author || entry.pinned {
+            return Ok(self.insert(&mut history, text, author));
+        }
+        if entry.text == text {
+            return Ok(entry.clone());
+        }
+
+        let mut entry = history.entries.remove(index).expect("índice válido");
+        entry.text = text;
+        entry.updated = now_secs();
+        entry.revision += 1;
+        history.entries.push_front(entry.clone());
+        let _ = self.events.send(ClipEvent::Upsert { entry: entry.clone() });
+        Ok(entry)
+    }
+
+    pub fn pin(&self, id: u64, pinned: bool) -> Result<ClipEntry, AppError> {
+        let mut history = self.history.lock().expect("Lock poisoned");
+        if pinned && history.entries.iter().filter(|entry| entry.pinned && entry.id != id).count() >= MAX_PINNED {
+            return Err(AppError::BadRequest("Demasiadas entradas fijadas"));
+        }
+        let entry = history.entries.iter_mut().find(|entry| entry.id == id).ok_or(AppError::NotFound)?;
+        if entry.pinned != pinned {
+            entry.pinned = pinned;
+            let _ = self.events.send(ClipEvent::Upsert { entry: entry.clone() });
+        }
+        let entry = entry.clone();
+        // Al soltarla puede sobrar una entrada
+        self.trim(&mut history);
+        Ok(entry)
+    }
+
+    pub fn delete(&self, id: u64) -> Result<(), AppError> {
+        let mut history = self.history.lock().expect("Lock poisoned");
+        let index = history.entries.iter().position(|entry| entry.id == id).ok_or(AppError::NotFound)?;
+        history.entries.remove(index);
+        let _ = self.events.send(ClipEvent::Delete { id });
+        Ok(())
+    }
+
+    fn insert(&self, history: &mut History, text: String, author: Option<String>) -> ClipEntry {
+        history.next_id += 1;
+        let now = now_secs();
+        let entry = ClipEntry { id: history.next_id, text, author, created: now, updated: now, revision: 1, pinned: false };
+        history.entries.push_front(entry.clone());
+        let _ = self.events.send(ClipEvent::Upsert { entry: entry.clone() });
+        self.trim(history);
+        entry
+    }
+
+    /// Descarta las entradas sin fijar más antiguas por encima de `MAX_HISTORY`.
+    fn trim(&self, history: &mut History) {
+        while history.entries.iter().filter(|entry| !entry.pinned).count() > MAX_HISTORY {
+            let Some(index) = history.entries.iter().rposition(|entry| !entry.pinned) else {
+                break;
+            };
+            if let Some(entry) = history.entries.remove(index) {
+                let _ = self.events.send(ClipEvent::Delete { id: entry.id });
+            }
+        }
+    }
+}
+
+fn check_size(text: &str) -> Result<(), AppError> {
+    if text.len() > MAX_TEXT {
+        return Err(AppError::PayloadTooLarge);
+    }
+    Ok(())
+}
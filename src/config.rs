@@ -0,0 +1,204 @@
+// Archivo de configuración TOML con perfiles con nombre.
+//
+// Ejemplo (`~/.config/local-share/config.toml` o `--config equipo.toml`):
+//
+//     default_profile = "casa"
+//
+//     [profiles.casa]
+//     path = "/home/yo/Compartido"
+//     port = 8080
+//     max_upload_size = 500
+//     tls = true
+//     users_file = "usuarios.toml"
+//     guest = ["read"]
+//
+//     [profiles.casa.features]
+//     mdns = false
+//
+// Las rutas relativas se interpretan respecto a la carpeta del propio archivo,
+// de modo que un equipo puede versionar la configuración junto al proyecto.
+
+use crate::users::{Permission, User};
+use crate::utils::{conflict::ConflictPolicy, paths::SymlinkPolicy};
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_PROFILE: &str = "default";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ConfigFile {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_profile: Option<String>,
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+/// Todo lo necesario para arrancar un servidor.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    pub path: PathBuf,
+    pub port: u16,
+    /// Tamaño máximo de subida en MB
+    pub max_upload_size: u64,
+    pub tls: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cert: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key: Option<PathBuf>,
+    /// Usuarios definidos en el propio perfil (se suman a los de `users_file`)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub users: Vec<User>,
+    /// Tabla de usuarios (por defecto `users.toml` junto a `config.toml`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub users_file: Option<PathBuf>,
+    /// Permisos de quien no inicia sesión cuando hay usuarios configurados
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub guest: Vec<Permission>,
+    /// Duración de las sesiones del formulario de login, en horas
+    pub session_hours: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub theme: Option<PathBuf>,
+    pub symlinks: SymlinkPolicy,
+    /// Qué hacer si una subida se llama igual que un archivo existente
+    pub on_conflict: ConflictPolicy,
+    /// Modo buzón: sólo subir, sin listar ni descargar
+    pub drop_box: bool,
+    /// En modo buzón, una subcarpeta por cada nombre de remitente
+    pub drop_box_subfolders: bool,
+    /// Días que se guarda lo borrado en la papelera (0 = hasta vaciarla a mano)
+    pub trash_days: u64,
+    /// Máximo de entradas al extraer un archivo comprimido subido
+    pub extract_max_entries: u64,
+    /// Máximo descomprimido por archivo comprimido, en MB
+    pub extract_max_size: u64,
+    pub features: Features,
+}
+
+/// Funcionalidades opcionales que se pueden desactivar por perfil.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Features {
+    /// Anunciar el servidor por mDNS (local-share.local)
+    pub mdns: bool,
+    /// Imprimir el código QR de conexión en la terminal
+    pub qr: bool,
+    /// Admitir subidas (`/upload` y `/api/tus`); `false` = sólo lectura
+    pub upload: bool,
+    /// Portapapeles compartido (`/api/clipboard`)
+    pub clipboard: bool,
+    /// Descargar carpetas enteras como ZIP
+    pub zip: bool,
+    /// Mover lo borrado a la papelera en vez de eliminarlo
+    pub trash: bool,
+    /// Indexar el contenido de los archivos para `/api/search/content`
+    /// (sólo si se compiló con la feature `content-search`)
+    pub content_search: bool,
+    /// Avisar a los navegadores de los cambios en la carpeta (`/api/events`)
+    pub live_updates: bool,
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from("."),
+            port: 3000,
+            max_upload_size: 10,
+            tls: false,
+            cert: None,
+            key: None,
+            users: Vec::new(),
+            users_file: None,
+            guest: Vec::new(),
+            session_hours: 12,
+            theme: None,
+            symlinks: SymlinkPolicy::default(),
+            on_conflict: ConflictPolicy::default(),
+            drop_box: false,
+            drop_box_subfolders: false,
+            trash_days: 30,
+            extract_max_entries: 10_000,
+            extract_max_size: 4096,
+            features: Features::default(),
+        }
+    }
+}
+
+impl Default for Features {
+    fn default() -> Self {
+        Self { mdns: true, qr: true, upload: true, clipboard: true, zip: true, trash: true, content_search: false, live_updates: true }
+    }
+}
+
+/// `~/.config/local-share/config.toml` (o el equivalente de cada sistema).
+pub fn default_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("local-share").join("config.toml"))
+}
+
+impl ConfigFile {
+    /// Carga `path`, o la ubicación por defecto si no se indica. Un archivo
+    /// explícito debe existir; el de por defecto es opcional.
+    pub fn load(path: Option<&Path>) -> anyhow::Result<Self> {
+        let (path, required) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => match default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Self::default()),
+            },
+        };
+
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if !required && e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e).with_context(|| format!("No se pudo leer {:?}", path)),
+        };
+
+        let mut config: Self = toml::from_str(&text).with_context(|| format!("Configuración inválida en {:?}", path))?;
+
+        let base_dir = path.parent().unwrap_or(Path::new("."));
+        for profile in config.profiles.values_mut() {
+            profile.make_paths_absolute(base_dir);
+        }
+
+        tracing::debug!("Configuración cargada desde {:?}", path);
+        Ok(config)
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let text = toml::to_string_pretty(self)?;
+        std::fs::write(path, text).with_context(|| format!("No se pudo escribir {:?}", path))?;
+        Ok(())
+    }
+
+    /// Devuelve el perfil pedido, el `default_profile` o uno vacío si no hay ninguno.
+    pub fn profile(&self, name: Option<&str>) -> anyhow::Result<Profile> {
+        let name = name.or(self.default_profile.as_deref());
+        match name {
+            Some(name) => self
+                .profiles
+                .get(name)
+                .cloned()
+                .with_context(|| format!("El perfil '{}' no existe", name)),
+            None => Ok(self.profiles.get(DEFAULT_PROFILE).cloned().unwrap_or_default()),
+        }
+    }
+}
+
+impl Profile {
+    fn make_paths_absolute(&mut self, base_dir: &Path) {
+        let absolute = |p: &mut PathBuf| {
+            if p.is_relative() {
+                *p = base_dir.join(&*p);
+            }
+        };
+        absolute(&mut self.path);
+        for p in [&mut self.cert, &mut self.key, &mut self.users_file, &mut self.theme].into_iter().flatten() {
+            absolute(p);
+        }
+    }
+}
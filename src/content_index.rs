@@ -0,0 +1,398 @@
+// Índice de texto completo de la carpeta compartida (feature de cargo
+// `content-search`, activado con `--content-search`).
+//
+// El índice (tantivy) vive fuera de la carpeta compartida, en
+// `~/.local/share/local-share/index/<hash de la carpeta>/`. Un indexador en
+// segundo plano recorre el árbol con las reglas del listado en cuanto el
+// watcher (`crate::watcher`) avisa de un cambio, y además cada pocos minutos
+// por si se pierde alguno o el watcher no está activo. Sólo vuelve a leer los
+// archivos cuya fecha o tamaño ha cambiado; lo que ya no existe se borra del
+// índice. `manifest.json` guarda qué versión de cada
+// archivo está indexada, así un reinicio no obliga a reindexarlo todo.
+
+use crate::{
+    utils::{listing::unix_seconds, paths::PathResolver, walk::TreeWalker},
+    watcher::FsEvent,
+};
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tantivy::{
+    collector::TopDocs,
+    directory::MmapDirectory,
+    doc,
+    query::QueryParser,
+    schema::{Field, Schema, Value, INDEXED, STORED, STRING, TEXT},
+    snippet::SnippetGenerator,
+    Index, IndexReader, IndexWriter, TantivyDocument, Term,
+};
+
+/// Recorrido de respaldo, aunque el watcher no haya avisado de nada
+const SYNC_INTERVAL: Duration = Duration::from_secs(5 * 60);
+/// Calma que se espera tras un cambio antes de recorrer el árbol: copiar
+/// muchos archivos de golpe provoca un único recorrido.
+const SETTLE_DELAY: Duration = Duration::from_secs(2);
+/// Con cambios continuos (un log que no para de crecer) se recorre igualmente
+const MAX_SETTLE: Duration = Duration::from_secs(30);
+/// Cada cuántos documentos nuevos se confirma el índice durante un recorrido
+const COMMIT_EVERY: usize = 1000;
+const WRITER_MEMORY: usize = 50 * 1024 * 1024;
+const MAX_TEXT_FILE: u64 = 20 * 1024 * 1024;
+const MAX_PDF_FILE: u64 = 100 * 1024 * 1024;
+/// Texto guardado por documento (para los fragmentos)
+const MAX_INDEXED_CHARS: usize = 1_000_000;
+const SNIPPET_CHARS: usize = 240;
+
+/// Extensiones que se leen como texto.
+const TEXT_EXTENSIONS: &[&str] = &[
+    "txt", "md", "markdown", "rst", "org", "tex", "csv", "tsv", "log", "json", "yaml", "yml", "toml", "ini", "cfg",
+    "conf", "xml", "html", "htm", "css", "scss", "js", "mjs", "ts", "tsx", "jsx", "rs", "py", "rb", "go", "java",
+    "kt", "swift", "c", "h", "cpp", "hpp", "cc", "cs", "php", "sh", "bash", "zsh", "ps1", "sql", "lua", "pl", "r",
+    "vue", "svelte", "dart", "scala", "ex", "exs", "erl", "hs", "ml", "clj", "el", "vim", "gradle", "properties",
+];
+
+#[derive(Clone, Copy)]
+enum Kind {
+    Text,
+    Pdf,
+}
+
+impl Kind {
+    fn detect(name: &str, size: u64) -> Option<Self> {
+        let extension = Path::new(name).extension()?.to_str()?.to_ascii_lowercase();
+        if extension == "pdf" {
+            return (size <= MAX_PDF_FILE).then_some(Self::Pdf);
+        }
+        (TEXT_EXTENSIONS.contains(&extension.as_str()) && size <= MAX_TEXT_FILE).then_some(Self::Text)
+    }
+}
+
+/// Versión indexada de un archivo.
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct Version {
+    modified: u64,
+    size: u64,
+}
+
+struct Fields {
+    /// Ruta relativa a la carpeta compartida (`/docs/a.md`); identifica el documento
+    path: Field,
+    name: Field,
+    body: Field,
+    size: Field,
+    modified: Field,
+}
+
+#[derive(Serialize)]
+pub struct ContentHit {
+    pub path: String,
+    pub name: String,
+    pub score: f32,
+    pub size: u64,
+    pub modified: u64,
+    /// Fragmento en HTML (escapado) con las coincidencias en `<b>`
+    pub snippet: String,
+    /// El mismo fragmento en texto plano...
+    pub fragment: String,
+    /// ...y las posiciones (en bytes) de las coincidencias dentro de él
+    pub highlights: Vec<(usize, usize)>,
+}
+
+#[derive(Default)]
+struct SyncStats {
+    indexed: usize,
+    removed: usize,
+}
+
+pub struct ContentIndex {
+    dir: PathBuf,
+    /// Carpeta compartida (ya canonicalizada)
+    root: PathBuf,
+    resolver: PathResolver,
+    index: Index,
+    reader: IndexReader,
+    writer: Mutex<IndexWriter>,
+    fields: Fields,
+    manifest: Mutex<HashMap<String, Version>>,
+    /// Hay un recorrido en marcha (la primera indexación puede tardar)
+    indexing: AtomicBool,
+}
+
+/// `~/.local/share/local-share/index/<hash>`: un índice por carpeta compartida.
+pub fn default_dir(base_path: &Path) -> Option<PathBuf> {
+    let hash = Sha256::digest(base_path.to_string_lossy().as_bytes());
+    let id: String = hash.iter().take(8).map(|b| format!("{:02x}", b)).collect();
+    dirs::data_dir().map(|dir| dir.join("local-share").join("index").join(id))
+}
+
+fn schema() -> (Schema, Fields) {
+    let mut builder = Schema::builder();
+    let fields = Fields {
+        path: builder.add_text_field("path", STRING | STORED),
+        name: builder.add_text_field("name", TEXT | STORED),
+        body: builder.add_text_field("body", TEXT | STORED),
+        size: builder.add_u64_field("size", STORED),
+        modified: builder.add_u64_field("modified", INDEXED | STORED),
+    };
+    (builder.build(), fields)
+}
+
+impl ContentIndex {
+    pub fn open(dir: PathBuf, root: PathBuf, resolver: PathResolver) -> anyhow::Result<Self> {
+        let (schema, fields) = schema();
+        let index = match Self::open_index(&dir, schema.clone()) {
+            Ok(index) => index,
+            Err(e) => {
+                // Índice de otra versión o corrupto: se rehace desde cero
+                tracing::warn!("Índice de contenido inservible en {:?} ({}), se reconstruye", dir, e);
+                std::fs::remove_dir_all(&dir).ok();
+                Self::open_index(&dir, schema)?
+            }
+        };
+        let reader = index.reader()?;
+        let mut writer = index.writer_with_num_threads(1, WRITER_MEMORY)?;
+
+        let manifest = match std::fs::read(dir.join("manifest.json")) {
+            Ok(data) => serde_json::from_slice(&data).unwrap_or_default(),
+            Err(_) => HashMap::new(),
+        };
+        if manifest.is_empty() {
+            // Sin manifiesto no se sabe qué hay dentro: mejor empezar limpio
+            writer.delete_all_documents()?;
+            writer.commit()?;
+        }
+
+        Ok(Self {
+            dir,
+            root,
+            resolver,
+            index,
+            reader,
+            writer: Mutex::new(writer),
+            fields,
+            manifest: Mutex::new(manifest),
+            indexing: AtomicBool::new(false),
+        })
+    }
+
+    fn open_index(dir: &Path, schema: Schema) -> anyhow::Result<Index> {
+        std::fs::create_dir_all(dir).with_context(|| format!("No se pudo crear {:?}", dir))?;
+        Ok(Index::open_or_create(MmapDirectory::open(dir)?, schema)?)
+    }
+
+    pub fn documents(&self) -> u64 {
+        self.reader.searcher().num_docs()
+    }
+
+    pub fn is_indexing(&self) -> bool {
+        self.indexing.load(Ordering::Relaxed)
+    }
+
+    /// Pone el índice al día con la carpeta compartida.
+    async fn sync(self: &Arc<Self>) -> anyhow::Result<SyncStats> {
+        let mut walker = TreeWalker::new(&self.root, "", &self.resolver).await?.skip_hidden();
+        let mut stats = SyncStats::default();
+        let mut seen = HashSet::new();
+        let mut pending = 0;
+
+        while let Some(entry) = walker.next().await? {
+            if !entry.metadata.is_file() {
+                continue;
+            }
+            let Some(kind) = Kind::detect(&entry.name, entry.metadata.len()) else {
+                continue;
+            };
+            let version = Version {
+                modified: entry.metadata.modified().ok().and_then(unix_seconds).unwrap_or(0),
+                size: entry.metadata.len(),
+            };
+            seen.insert(entry.name.clone());
+            if self.manifest.lock().expect("Lock poisoned").get(&entry.name) == Some(&version) {
+                continue;
+            }
+
+            // Si no se puede leer se indexa igual (sólo el nombre) para no
+            // reintentarlo en cada pasada mientras no cambie
+            let body = extract_text(kind, entry.path.clone()).await.unwrap_or_else(|e| {
+                tracing::debug!("Sin texto para el índice en {:?}: {}", entry.path, e);
+                String::new()
+            });
+            let name = entry.name.rsplit('/').next().unwrap_or_default().to_string();
+            {
+                let writer = self.writer.lock().expect("Lock poisoned");
+                writer.delete_term(Term::from_field_text(self.fields.path, &entry.name));
+                writer.add_document(doc!(
+                    self.fields.path => entry.name.clone(),
+                    self.fields.name => name,
+                    self.fields.body => body,
+                    self.fields.size => version.size,
+                    self.fields.modified => version.modified,
+                ))?;
+            }
+            self.manifest.lock().expect("Lock poisoned").insert(entry.name, version);
+            stats.indexed += 1;
+            pending += 1;
+            if pending >= COMMIT_EVERY {
+                self.commit().await?;
+                pending = 0;
+            }
+        }
+
+        let gone: Vec<String> = {
+            let mut manifest = self.manifest.lock().expect("Lock poisoned");
+            let gone = manifest.keys().filter(|path| !seen.contains(*path)).cloned().collect::<Vec<_>>();
+            for path in &gone {
+                manifest.remove(path);
+            }
+            gone
+        };
+        if !gone.is_empty() {
+            let writer = self.writer.lock().expect("Lock poisoned");
+            for path in &gone {
+                writer.delete_term(Term::from_field_text(self.fields.path, path));
+            }
+        }
+        stats.removed = gone.len();
+
+        if pending > 0 || stats.removed > 0 {
+            self.commit().await?;
+        }
+        Ok(stats)
+    }
+
+    /// Confirma lo escrito, recarga el lector y guarda el manifiesto.
+    async fn commit(self: &Arc<Self>) -> anyhow::Result<()> {
+        let this = self.clone();
+        tokio::task::spawn_blocking(move || -> anyhow::Result<()> {
+            this.writer.lock().expect("Lock poisoned").commit()?;
+            this.reader.reload()?;
+            let manifest = serde_json::to_vec(&*this.manifest.lock().expect("Lock poisoned"))?;
+            // Temporal + rename: nunca queda un manifiesto a medias
+            let temp = this.dir.join("manifest.json.tmp");
+            std::fs::write(&temp, manifest)?;
+            std::fs::rename(&temp, this.dir.join("manifest.json"))?;
+            Ok(())
+        })
+        .await?
+    }
+
+    /// Busca en el nombre y el contenido. Bloquea: llamar desde `spawn_blocking`.
+    pub fn search(&self, query: &str, limit: usize) -> anyhow::Result<Vec<ContentHit>> {
+        let searcher = self.reader.searcher();
+        let parser = QueryParser::for_index(&self.index, vec![self.fields.name, self.fields.body]);
+        // Las comillas o paréntesis sueltos no son un error: se ignora lo que sobra
+        let (query, _errors) = parser.parse_query_lenient(query);
+        let top = searcher.search(&query, &TopDocs::with_limit(limit.max(1)).order_by_score())?;
+
+        let mut snippets = SnippetGenerator::create(&searcher, &*query, self.fields.body)?;
+        snippets.set_max_num_chars(SNIPPET_CHARS);
+
+        let mut hits = Vec::with_capacity(top.len());
+        for (score, address) in top {
+            let doc: TantivyDocument = searcher.doc(address)?;
+            let text = |field| doc.get_first(field).and_then(|v| v.as_str()).unwrap_or_default().to_string();
+            let number = |field| doc.get_first(field).and_then(|v| v.as_u64()).unwrap_or_default();
+            let snippet = snippets.snippet_from_doc(&doc);
+            hits.push(ContentHit {
+                path: text(self.fields.path),
+                name: text(self.fields.name),
+                score,
+                size: number(self.fields.size),
+                modified: number(self.fields.modified),
+                snippet: snippet.to_html(),
+                fragment: snippet.fragment().to_string(),
+                highlights: snippet.highlighted().iter().map(|range| (range.start, range.end)).collect(),
+            });
+        }
+        Ok(hits)
+    }
+}
+
+async fn extract_text(kind: Kind, path: PathBuf) -> anyhow::Result<String> {
+    let mut text = match kind {
+        Kind::Text => {
+            let data = tokio::fs::read(&path).await?;
+            // Un NUL al principio delata un binario con extensión de texto
+            if data.iter().take(8192).any(|&b| b == 0) {
+                anyhow::bail!("parece binario");
+            }
+            String::from_utf8_lossy(&data).into_owned()
+        }
+        Kind::Pdf => tokio::task::spawn_blocking(move || {
+            // pdf-extract entra en pánico con algunos PDFs mal formados
+            std::panic::catch_unwind(|| pdf_extract::extract_text(&path))
+                .map_err(|_| anyhow::anyhow!("PDF ilegible"))?
+                .map_err(anyhow::Error::from)
+        })
+        .await??,
+    };
+    if let Some((cut, _)) = text.char_indices().nth(MAX_INDEXED_CHARS) {
+        text.truncate(cut);
+    }
+    Ok(text)
+}
+
+/// Indexa al arrancar y después cada `SYNC_INTERVAL`.
+pub fn spawn_indexer(index: Arc<ContentIndex>, mut changes: Option<broadcast::Receiver<FsEvent>>) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SYNC_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                changed = next_change(&mut changes) => {
+                    let Some(receiver) = changes.as_mut().filter(|_| changed) else {
+                        // El watcher se ha parado: queda sólo el recorrido periódico
+                        changes = None;
+                        continue;
+                    };
+                    settle(receiver).await;
+                    interval.reset();
+                }
+            }
+            index.indexing.store(true, Ordering::Relaxed);
+            match index.sync().await {
+                Ok(stats) if stats.indexed + stats.removed > 0 => tracing::info!(
+                    "Índice de contenido: {} archivos indexados, {} eliminados ({} en total)",
+                    stats.indexed,
+                    stats.removed,
+                    index.documents()
+                ),
+                Ok(_) => {}
+                Err(e) => tracing::warn!("Error actualizando el índice de contenido: {:#}", e),
+            }
+            index.indexing.store(false, Ordering::Relaxed);
+        }
+    })
+}
+
+/// `true` al llegar un cambio (o si se han perdido eventos), `false` si el
+/// watcher se cerró. Sin watcher no termina nunca.
+async fn next_change(changes: &mut Option<broadcast::Receiver<FsEvent>>) -> bool {
+    match changes {
+        Some(receiver) => !matches!(receiver.recv().await, Err(RecvError::Closed)),
+        None => std::future::pending().await,
+    }
+}
+
+/// Descarta los eventos que siguen llegando hasta que pasa `SETTLE_DELAY` sin
+/// ninguno (o `MAX_SETTLE` en total).
+async fn settle(receiver: &mut broadcast::Receiver<FsEvent>) {
+    let deadline = tokio::time::Instant::now() + MAX_SETTLE;
+    loop {
+        let wait = SETTLE_DELAY.min(deadline.saturating_duration_since(tokio::time::Instant::now()));
+        match tokio::time::timeout(wait, receiver.recv()).await {
+            Ok(Ok(_) | Err(RecvError::Lagged(_))) if !wait.is_zero() => continue,
+            _ => return,
+        }
+    }
+}
@@ -2,6 +2,7 @@ use axum::{
     http::StatusCode,
     response::{IntoResponse, Response},
 };
+use axum::extract::multipart::MultipartError;
 use std::io;
 
 // Definimos nuestro enum de errores personalizados.
@@ -10,30 +11,70 @@ use std::io;
 pub enum AppError {
     // El cliente pide algo que no existe
     NotFound,
-    // El sistema operativo nos niega el acceso
+    // El sistema operativo (o la cuenta del usuario) nos niega el acceso
     PermissionDenied,
+    // Hace falta iniciar sesión para esta operación
+    Unauthorized,
     // Un error genérico para cosas que no deberían pasar (IO errors, etc.)
     InternalServerError(anyhow::Error),
     // Seguridad: Intentan acceder fuera de la carpeta permitida (Path Traversal)
     InvalidPath,
+    // La subida supera el límite configurado (--max-upload-size)
+    PayloadTooLarge,
+    // Enlace compartido caducado, agotado o revocado
+    Gone,
+    // La petición está mal formada (falta un campo, valor inválido...)
+    BadRequest(&'static str),
+    // El destino ya existe (crear carpeta, mover o copiar encima de otra cosa)
+    Conflict,
+    // Demasiadas contraseñas fallidas seguidas (ver `crate::throttle`)
+    TooManyRequests,
+}
+
+impl AppError {
+    // Código HTTP y mensaje para el usuario de cada error
+    fn parts(&self) -> (StatusCode, &'static str) {
+        match self {
+            AppError::Unauthorized => (StatusCode::UNAUTHORIZED, "Hace falta iniciar sesión"),
+            AppError::NotFound => (StatusCode::NOT_FOUND, "Recurso no encontrado"),
+            AppError::PermissionDenied => (StatusCode::FORBIDDEN, "Permiso denegado"),
+            AppError::InvalidPath => (StatusCode::BAD_REQUEST, "Ruta inválida o insegura"),
+            AppError::PayloadTooLarge => (StatusCode::PAYLOAD_TOO_LARGE, "Archivo demasiado grande"),
+            AppError::BadRequest(message) => (StatusCode::BAD_REQUEST, message),
+            AppError::Conflict => (StatusCode::CONFLICT, "El destino ya existe"),
+            AppError::Gone => (StatusCode::GONE, "El enlace ha caducado o ya no está disponible"),
+            AppError::TooManyRequests => (StatusCode::TOO_MANY_REQUESTS, "Demasiados intentos fallidos, espera un poco"),
+            // Al usuario le mostramos un mensaje genérico por seguridad
+            AppError::InternalServerError(_) => (StatusCode::INTERNAL_SERVER_ERROR, "Error interno del servidor"),
+        }
+    }
+
+    /// Mensaje para el usuario, para cuando el error no viaja como respuesta
+    /// HTTP (p. ej. dentro de un stream de eventos ya empezado).
+    pub fn message(&self) -> &'static str {
+        if let AppError::InternalServerError(err) = self {
+            tracing::error!("Error interno: {:?}", err);
+        }
+        self.parts().1
+    }
 }
 
 // Implementamos IntoResponse para que Axum sepa qué responder al navegador
 // cuando ocurre uno de estos errores.
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
+        // 401 con el desafío Basic para que el navegador pida credenciales
+        if let AppError::Unauthorized = self {
+            return crate::auth::challenge();
+        }
+
+        // IMPORTANTE: Logueamos el error real en la terminal del servidor
+        if let AppError::InternalServerError(err) = &self {
+            tracing::error!("Error interno: {:?}", err);
+        }
+
         // Mapeamos el error interno a un Código de Estado HTTP y un mensaje
-        let (status, error_message) = match self {
-            AppError::NotFound => (StatusCode::NOT_FOUND, "Recurso no encontrado"),
-            AppError::PermissionDenied => (StatusCode::FORBIDDEN, "Permiso denegado"),
-            AppError::InvalidPath => (StatusCode::BAD_REQUEST, "Ruta inválida o insegura"),
-            AppError::InternalServerError(err) => {
-                // IMPORTANTE: Logueamos el error real en la terminal del servidor
-                tracing::error!("Error interno: {:?}", err);
-                // Pero al usuario le mostramos un mensaje genérico por seguridad
-                (StatusCode::INTERNAL_SERVER_ERROR, "Error interno del servidor")
-            }
-        };
+        let (status, error_message) = self.parts();
 
         // Construimos la respuesta final
         (status, error_message).into_response()
@@ -57,4 +98,15 @@ impl From<anyhow::Error> for AppError {
     fn from(err: anyhow::Error) -> Self {
         AppError::InternalServerError(err)
     }
-}
\ No newline at end of file
+}
+
+// Errores del multipart: el límite de tamaño de axum se traduce a 413
+impl From<MultipartError> for AppError {
+    fn from(err: MultipartError) -> Self {
+        if err.status() == StatusCode::PAYLOAD_TOO_LARGE {
+            AppError::PayloadTooLarge
+        } else {
+            AppError::InternalServerError(anyhow::Error::new(err))
+        }
+    }
+}
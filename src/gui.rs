@@ -1,19 +1,54 @@
 use eframe::egui;
 use std::path::PathBuf;
 use tokio::task::JoinHandle;
-use crate::server::{self, Args};
+use crate::config::{self, ConfigFile, Features, Profile};
+use crate::server;
+use crate::shares::{self, NewShare, Share, ShareStore};
+use crate::users::{Permission, User};
+use crate::utils::{conflict::ConflictPolicy, paths::SymlinkPolicy};
 
 pub struct LocalShareApp {
+    // Profiles (same TOML file as the CLI)
+    config_path: Option<PathBuf>,
+    config: ConfigFile,
+    profile_name: String,
+
     // Configuration State
     path: PathBuf,
     port: String,
+    max_upload_size: String,
     tls_enabled: bool,
+    cert: Option<PathBuf>,
+    key: Option<PathBuf>,
     auth_enabled: bool,
     username: String,
     password: String,
+    // Stored account behind the username field (its hash is reused if the password is left empty)
+    primary_user: Option<User>,
+    extra_users: Vec<User>,
+    users_file: Option<PathBuf>,
+    guest: Vec<Permission>,
+    session_hours: u64,
     theme_enabled: bool,
     theme_path: Option<PathBuf>,
+    symlinks: SymlinkPolicy,
+    on_conflict: ConflictPolicy,
+    drop_box: bool,
+    drop_box_subfolders: bool,
+    trash_days: u64,
+    // Sólo desde el archivo de configuración; se conservan al guardar
+    extract_max_entries: u64,
+    extract_max_size: u64,
+    features: Features,
     
+    // Share Links (same shares.json as the server)
+    share_store: Option<ShareStore>,
+    shares: Vec<(String, Share)>,
+    share_target: Option<PathBuf>,
+    share_hours: String,
+    share_max_downloads: String,
+    share_password: String,
+
     // Runtime State
     server_handle: Option<JoinHandle<()>>,
     mdns_handle: Option<crate::utils::mdns::MdnsGuard>,
@@ -27,14 +62,39 @@ pub struct LocalShareApp {
 impl Default for LocalShareApp {
     fn default() -> Self {
         Self {
+            config_path: config::default_path(),
+            config: ConfigFile::default(),
+            profile_name: config::DEFAULT_PROFILE.to_string(),
             path: std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")),
             port: "3000".to_string(),
+            max_upload_size: "10".to_string(),
             tls_enabled: false,
+            cert: None,
+            key: None,
             auth_enabled: false,
             username: "admin".to_string(),
             password: "password".to_string(),
+            primary_user: None,
+            extra_users: Vec::new(),
+            users_file: None,
+            guest: Vec::new(),
+            session_hours: Profile::default().session_hours,
             theme_enabled: false,
             theme_path: None,
+            share_store: ShareStore::open_default(),
+            shares: Vec::new(),
+            share_target: None,
+            share_hours: "24".to_string(),
+            share_max_downloads: String::new(),
+            share_password: String::new(),
+            symlinks: SymlinkPolicy::default(),
+            on_conflict: ConflictPolicy::default(),
+            drop_box: false,
+            drop_box_subfolders: false,
+            trash_days: Profile::default().trash_days,
+            extract_max_entries: Profile::default().extract_max_entries,
+            extract_max_size: Profile::default().extract_max_size,
+            features: Features::default(),
             server_handle: None,
             mdns_handle: None,
             status_msg: "Ready".to_string(),
@@ -46,48 +106,230 @@ impl Default for LocalShareApp {
 
 impl LocalShareApp {
     pub fn new(_cc: &eframe::CreationContext<'_>) -> Self {
-        Default::default()
+        let mut app = Self::default();
+        match ConfigFile::load(None) {
+            Ok(config) => {
+                app.config = config;
+                let name = app.config.default_profile.clone()
+                    .or_else(|| app.config.profiles.keys().next().cloned());
+                if let Some(name) = name {
+                    app.load_profile(&name);
+                }
+            }
+            Err(e) => app.status_msg = format!("Config error: {:#}", e),
+        }
+        app.refresh_shares();
+        app
     }
 
-    fn start_server(&mut self, ctx: &egui::Context) {
-        if self.server_handle.is_some() {
+    fn refresh_shares(&mut self) {
+        let Some(store) = &self.share_store else {
             return;
+        };
+        match store.list() {
+            Ok(list) => self.shares = list,
+            Err(e) => self.status_msg = format!("Error loading share links: {:#}", e),
         }
+    }
 
-        let port = match self.port.parse::<u16>() {
-            Ok(p) => p,
-            Err(_) => {
-                self.status_msg = "Invalid Port".to_string();
-                return;
+    fn create_share(&mut self) {
+        let Some(store) = &self.share_store else {
+            self.status_msg = "No data directory available".to_string();
+            return;
+        };
+        let Some(target) = self.share_target.as_ref().and_then(|p| p.canonicalize().ok()) else {
+            self.status_msg = "Select a file or folder to share".to_string();
+            return;
+        };
+        let inside_share = self.path.canonicalize().is_ok_and(|base| target.starts_with(base));
+        if !inside_share {
+            self.status_msg = "The link target must be inside the shared folder".to_string();
+            return;
+        }
+
+        let hours = self.share_hours.trim();
+        let Ok(expires_in) = (!hours.is_empty()).then(|| hours.parse::<u64>()).transpose() else {
+            self.status_msg = "Invalid Expiry".to_string();
+            return;
+        };
+        let max = self.share_max_downloads.trim();
+        let Ok(max_downloads) = (!max.is_empty()).then(|| max.parse::<u32>()).transpose() else {
+            self.status_msg = "Invalid Max Downloads".to_string();
+            return;
+        };
+
+        let new = NewShare {
+            path: target,
+            expires_in: expires_in.map(|h| std::time::Duration::from_secs(h * 60 * 60)),
+            max_downloads,
+            password: (!self.share_password.is_empty()).then(|| self.share_password.clone()),
+            created_by: None,
+        };
+        match store.create(new) {
+            Ok(_) => {
+                self.status_msg = "Share link created".to_string();
+                self.share_password.clear();
+                self.refresh_shares();
             }
+            Err(e) => self.status_msg = format!("Error creating share link: {:#}", e),
+        }
+    }
+
+    fn revoke_share(&mut self, id: &str) {
+        let Some(store) = &self.share_store else {
+            return;
         };
+        if let Err(e) = store.revoke(id) {
+            self.status_msg = format!("Error revoking share link: {:#}", e);
+        }
+        self.refresh_shares();
+    }
 
-        let args = Args {
+    fn share_url(&self, token: &str) -> String {
+        let port = self.port.parse::<u16>().unwrap_or(3000);
+        let local_ip = crate::utils::net::get_local_ip();
+        let base = crate::utils::net::build_connection_url(self.tls_enabled, &local_ip, port, None, None, false);
+        format!("{}/s/{}", base, token)
+    }
+
+    fn load_profile(&mut self, name: &str) {
+        let Some(profile) = self.config.profiles.get(name).cloned() else {
+            return;
+        };
+
+        self.profile_name = name.to_string();
+        self.path = profile.path;
+        self.port = profile.port.to_string();
+        self.max_upload_size = profile.max_upload_size.to_string();
+        self.tls_enabled = profile.tls;
+        self.cert = profile.cert;
+        self.key = profile.key;
+        self.auth_enabled = !profile.users.is_empty();
+        let mut users = profile.users.into_iter();
+        if let Some(first) = users.next() {
+            self.username = first.username.clone();
+            self.password.clear();
+            self.primary_user = Some(first);
+        }
+        self.extra_users = users.collect();
+        self.users_file = profile.users_file;
+        self.guest = profile.guest;
+        self.session_hours = profile.session_hours;
+        self.theme_enabled = profile.theme.is_some();
+        self.theme_path = profile.theme;
+        self.symlinks = profile.symlinks;
+        self.on_conflict = profile.on_conflict;
+        self.drop_box = profile.drop_box;
+        self.drop_box_subfolders = profile.drop_box_subfolders;
+        self.trash_days = profile.trash_days;
+        self.extract_max_entries = profile.extract_max_entries;
+        self.extract_max_size = profile.extract_max_size;
+        self.features = profile.features;
+    }
+
+    /// Builds a profile from the form; `None` if a numeric field is invalid.
+    fn build_profile(&mut self) -> Option<Profile> {
+        let Ok(port) = self.port.parse::<u16>() else {
+            self.status_msg = "Invalid Port".to_string();
+            return None;
+        };
+        let Ok(max_upload_size) = self.max_upload_size.parse::<u64>() else {
+            self.status_msg = "Invalid Upload Limit".to_string();
+            return None;
+        };
+
+        let users = if self.auth_enabled {
+            let permissions = self.primary_user.as_ref()
+                .map(|u| u.permissions.clone())
+                .unwrap_or_else(|| Permission::ALL.to_vec());
+            let first = match &self.primary_user {
+                Some(user) if self.password.is_empty() => User { username: self.username.clone(), ..user.clone() },
+                _ if self.password.is_empty() => {
+                    self.status_msg = "Password is empty".to_string();
+                    return None;
+                }
+                _ => match User::new(self.username.clone(), &self.password, permissions) {
+                    Ok(user) => user,
+                    Err(e) => {
+                        self.status_msg = format!("Error hashing password: {:#}", e);
+                        return None;
+                    }
+                },
+            };
+            std::iter::once(first).chain(self.extra_users.iter().cloned()).collect()
+        } else {
+            Vec::new()
+        };
+
+        Some(Profile {
             path: self.path.clone(),
             port,
-            max_upload_size: 10,
+            max_upload_size,
             tls: self.tls_enabled,
-            cert: None,
-            key: None,
-            username: if self.auth_enabled { Some(self.username.clone()) } else { None },
-            password: if self.auth_enabled { Some(self.password.clone()) } else { None },
+            cert: self.cert.clone(),
+            key: self.key.clone(),
+            users,
+            users_file: self.users_file.clone(),
+            guest: self.guest.clone(),
+            session_hours: self.session_hours,
             theme: if self.theme_enabled { self.theme_path.clone() } else { None },
-            dump_theme: None,
+            symlinks: self.symlinks,
+            on_conflict: self.on_conflict,
+            drop_box: self.drop_box,
+            drop_box_subfolders: self.drop_box_subfolders,
+            trash_days: self.trash_days,
+            extract_max_entries: self.extract_max_entries,
+            extract_max_size: self.extract_max_size,
+            features: self.features.clone(),
+        })
+    }
+
+    fn save_profile(&mut self) {
+        let Some(config_path) = self.config_path.clone() else {
+            self.status_msg = "No config directory available".to_string();
+            return;
+        };
+        let Some(profile) = self.build_profile() else {
+            return;
         };
 
+        let name = self.profile_name.trim().to_string();
+        if name.is_empty() {
+            self.status_msg = "Profile name is empty".to_string();
+            return;
+        }
+
+        self.config.profiles.insert(name.clone(), profile);
+        self.config.default_profile = Some(name.clone());
+        self.status_msg = match self.config.save(&config_path) {
+            Ok(()) => format!("Profile '{}' saved to {:?}", name, config_path),
+            Err(e) => format!("Error saving profile: {:#}", e),
+        };
+    }
+
+    fn start_server(&mut self, ctx: &egui::Context) {
+        if self.server_handle.is_some() {
+            return;
+        }
+
+        let Some(profile) = self.build_profile() else {
+            return;
+        };
+        let port = profile.port;
+
         self.status_msg = format!("Running on port {}", port);
         
         // --- Identity & Discovery ---
         // 1. mDNS
-        let mdns = crate::utils::mdns::register_service(port, "local-share", self.tls_enabled);
-        self.mdns_handle = Some(mdns);
+        self.mdns_handle = profile.features.mdns
+            .then(|| crate::utils::mdns::register_service(port, "local-share", self.tls_enabled));
 
         // 2. Generate QR
         self.update_qr_code(ctx);
 
         // Spawn server task
         let handle = tokio::spawn(async move {
-            server::start_server(args).await;
+            server::start_server(profile).await;
         });
         
         self.server_handle = Some(handle);
@@ -108,7 +350,7 @@ impl LocalShareApp {
             &local_ip,
             port,
             Some(&self.username),
-            Some(&self.password),
+            (!self.password.is_empty()).then_some(self.password.as_str()),
             self.show_auth_in_qr && self.auth_enabled,
         );
 
@@ -136,6 +378,32 @@ impl eframe::App for LocalShareApp {
             ui.heading("Local Share Launcher");
             ui.add_space(10.0);
 
+            // Profiles
+            ui.horizontal(|ui| {
+                ui.label("Profile:");
+                let mut selected = None;
+                egui::ComboBox::from_id_salt("profile_select")
+                    .selected_text(self.profile_name.clone())
+                    .show_ui(ui, |ui| {
+                        for name in self.config.profiles.keys() {
+                            if ui.selectable_label(*name == self.profile_name, name).clicked() {
+                                selected = Some(name.clone());
+                            }
+                        }
+                    });
+                if let Some(name) = selected {
+                    self.load_profile(&name);
+                }
+                ui.text_edit_singleline(&mut self.profile_name);
+                if ui.button("Save Profile").clicked() {
+                    self.save_profile();
+                }
+            });
+
+            ui.add_space(10.0);
+            ui.separator();
+            ui.add_space(10.0);
+
             // Path Selection
             ui.horizontal(|ui| {
                 ui.label("Path:");
@@ -153,6 +421,43 @@ impl eframe::App for LocalShareApp {
                 ui.text_edit_singleline(&mut self.port);
             });
 
+            // Upload limit
+            ui.horizontal(|ui| {
+                ui.label("Max Upload (MB):");
+                ui.text_edit_singleline(&mut self.max_upload_size);
+            });
+
+            ui.checkbox(&mut self.features.mdns, "Announce via mDNS");
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.features.upload, "Allow uploads");
+                ui.checkbox(&mut self.features.clipboard, "Shared clipboard");
+                ui.checkbox(&mut self.features.zip, "Folder ZIP downloads");
+            });
+            ui.checkbox(&mut self.features.live_updates, "Refresh listings when files change");
+            if self.features.upload {
+                ui.horizontal(|ui| {
+                    ui.label("If an upload exists:");
+                    egui::ComboBox::from_id_salt("on_conflict_select")
+                        .selected_text(format!("{:?}", self.on_conflict))
+                        .show_ui(ui, |ui| {
+                            for policy in [ConflictPolicy::Rename, ConflictPolicy::Overwrite, ConflictPolicy::Skip, ConflictPolicy::Fail] {
+                                ui.selectable_value(&mut self.on_conflict, policy, format!("{:?}", policy));
+                            }
+                        });
+                });
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut self.features.trash, "Keep deleted files in trash for");
+                    ui.add_enabled(self.features.trash, egui::DragValue::new(&mut self.trash_days).suffix(" days"));
+                });
+            }
+
+            ui.checkbox(&mut self.drop_box, "Drop Box (visitors can only upload)");
+            if self.drop_box {
+                ui.indent("drop_box_indent", |ui| {
+                    ui.checkbox(&mut self.drop_box_subfolders, "One subfolder per submitter name");
+                });
+            }
+
             ui.add_space(10.0);
             ui.separator();
             ui.add_space(10.0);
@@ -172,6 +477,16 @@ impl eframe::App for LocalShareApp {
                         ui.label("Password:");
                         ui.text_edit_singleline(&mut self.password);
                     });
+                    if self.primary_user.is_some() {
+                        ui.small("Leave the password empty to keep the saved one.");
+                    }
+                    let mut guests_browse = self.guest.contains(&Permission::Read);
+                    if ui.checkbox(&mut guests_browse, "Guests can browse without logging in").changed() {
+                        self.guest.retain(|p| *p != Permission::Read);
+                        if guests_browse {
+                            self.guest.push(Permission::Read);
+                        }
+                    }
                 });
             }
 
@@ -206,6 +521,83 @@ impl eframe::App for LocalShareApp {
             ui.separator();
             ui.add_space(10.0);
 
+            // Share Links
+            ui.heading("Share Links");
+            ui.horizontal(|ui| {
+                let target = self.share_target.as_ref()
+                    .map(|p| p.to_string_lossy().to_string())
+                    .unwrap_or_else(|| "None".to_string());
+                ui.label("Target:");
+                ui.label(target);
+                if ui.button("Pick File").clicked() {
+                    if let Some(path) = rfd::FileDialog::new().set_directory(&self.path).pick_file() {
+                        self.share_target = Some(path);
+                    }
+                }
+                if ui.button("Pick Folder").clicked() {
+                    if let Some(path) = rfd::FileDialog::new().set_directory(&self.path).pick_folder() {
+                        self.share_target = Some(path);
+                    }
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label("Expires in (hours):");
+                ui.add(egui::TextEdit::singleline(&mut self.share_hours).desired_width(50.0));
+                ui.label("Max downloads:");
+                ui.add(egui::TextEdit::singleline(&mut self.share_max_downloads).desired_width(50.0));
+            });
+            ui.horizontal(|ui| {
+                ui.label("Password (optional):");
+                ui.add(egui::TextEdit::singleline(&mut self.share_password).password(true));
+                if ui.button("Create Link").clicked() {
+                    self.create_share();
+                }
+            });
+            ui.small("Leave expiry or max downloads empty for no limit.");
+
+            let base = self.path.canonicalize().ok();
+            let mut revoke = None;
+            for (token, share) in &self.shares {
+                // Only links for the folder currently being shared
+                let Some(relative) = base.as_ref().and_then(|b| share.relative_to(b)) else {
+                    continue;
+                };
+                ui.horizontal(|ui| {
+                    ui.label(format!("/{}", relative.to_string_lossy()));
+                    let status = if !share.is_active() {
+                        "expired".to_string()
+                    } else {
+                        let downloads = match share.max_downloads {
+                            Some(max) => format!("{}/{} downloads", share.downloads, max),
+                            None => format!("{} downloads", share.downloads),
+                        };
+                        let mut parts = vec![downloads];
+                        if let Some(expires_at) = share.expires_at {
+                            let hours = expires_at.saturating_sub(shares::now_secs()).div_ceil(3600);
+                            parts.push(format!("{}h left", hours));
+                        }
+                        if share.password_hash.is_some() {
+                            parts.push("password".to_string());
+                        }
+                        parts.join(", ")
+                    };
+                    ui.small(status);
+                    if ui.button("Copy URL").clicked() {
+                        ui.ctx().copy_text(self.share_url(token));
+                    }
+                    if ui.button("Revoke").clicked() {
+                        revoke = Some(share.id.clone());
+                    }
+                });
+            }
+            if let Some(id) = revoke {
+                self.revoke_share(&id);
+            }
+
+            ui.add_space(20.0);
+            ui.separator();
+            ui.add_space(10.0);
+
             // Actions
             ui.horizontal(|ui| {
                 if self.server_handle.is_none() {
@@ -226,10 +618,10 @@ impl eframe::App for LocalShareApp {
                 ui.separator();
                 ui.heading("Connect Mobile");
                 
-                if self.auth_enabled {
-                    if ui.checkbox(&mut self.show_auth_in_qr, "Include Credentials in QR").changed() {
-                        self.update_qr_code(ctx);
-                    }
+                if self.auth_enabled
+                    && ui.checkbox(&mut self.show_auth_in_qr, "Include Credentials in QR").changed()
+                {
+                    self.update_qr_code(ctx);
                 }
 
                 if let Some(texture) = &self.qr_texture {
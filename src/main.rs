@@ -6,6 +6,16 @@ mod routes;
 mod error;
 mod assets;
 mod auth;
+mod config;
+mod users;
+mod sessions;
+mod shares;
+mod trash;
+mod throttle;
+mod clipboard;
+mod watcher;
+#[cfg(feature = "content-search")]
+mod content_index;
 mod server;
 mod gui;
 
@@ -38,6 +48,15 @@ async fn main() {
         tracing::info!("Iniciando en modo CLI...");
         let args = server::Args::parse();
 
+        // Gestión de usuarios: `local-share user add/passwd/remove`
+        if let Some(server::Command::User { action }) = args.command.clone() {
+            if let Err(e) = users::run_command(action, args.users_file.clone()) {
+                eprintln!("Error: {:#}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+
         // Check for dump_theme
         if let Some(path) = &args.dump_theme {
             tracing::info!("Dumping default theme to: {:?}", path);
@@ -67,6 +86,18 @@ async fn main() {
             return;
         }
 
-        server::start_server(args).await;
+        // Perfil del archivo de configuración + overrides de la línea de comandos
+        let profile = config::ConfigFile::load(args.config.as_deref())
+            .and_then(|config| config.profile(args.profile.as_deref()))
+            .and_then(|profile| args.apply_to(profile));
+        let profile = match profile {
+            Ok(profile) => profile,
+            Err(e) => {
+                eprintln!("Error: {:#}", e);
+                std::process::exit(1);
+            }
+        };
+
+        server::start_server(profile).await;
     }
 }
\ No newline at end of file
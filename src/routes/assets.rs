@@ -1,51 +1,92 @@
 use axum::{
     extract::{Path, State},
-    http::{header, StatusCode},
+    http::{header, HeaderMap, StatusCode},
     response::{IntoResponse, Response},
     body::Body,
 };
-use tokio_util::io::ReaderStream;
-use tokio::fs::File;
+use tower_http::services::ServeFile;
 use std::sync::Arc;
-use crate::{assets::Assets, AppState};
+use crate::{assets::Assets, error::AppError, utils::paths, AppState};
+
+// Los assets no llevan hash en la URL: el navegador debe revalidar siempre,
+// pero gracias al ETag la respuesta suele ser un 304 sin cuerpo.
+const CACHE_CONTROL: &str = "no-cache";
 
 pub async fn assets_handler(
     State(state): State<Arc<AppState>>,
     Path(path): Path<String>,
-) -> Response {
-    let path = path.trim_start_matches('/');
-    
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let path = paths::display_path(&paths::normalize(&path)?);
+
     // 1. INTENTO DE CARGA DESDE DISCO (Personalizado)
-    if let Some(theme_dir) = &state.theme_path {
-        let custom_file_path = theme_dir.join(path);
-        
-        // Verificamos si existe el archivo personalizado dentro del tema
-        // Note: For production security we should ensure custom_file_path is inside theme_dir 
-        // to prevent traversal, but path traversal is already handled by not allowing ".." in `path`.
-        if custom_file_path.exists() && custom_file_path.is_file() {
-            if let Ok(file) = File::open(custom_file_path).await {
-                let stream = ReaderStream::new(file);
-                let body = Body::from_stream(stream);
-                
-                let mime = mime_guess::from_path(path).first_or_octet_stream();
-                
-                return (
-                    [(header::CONTENT_TYPE, mime.as_ref())],
-                    body
-                ).into_response();
+    if let Some(theme) = &state.theme {
+        // El mismo resolver que la carpeta compartida garantiza que el archivo
+        // está dentro del tema (sin `..` ni enlaces que apunten fuera).
+        match theme.resolve(&path).await {
+            Ok(custom_file_path) if custom_file_path.is_file() => {
+                // ServeFile ya gestiona Last-Modified, If-Modified-Since y rangos
+                let mut request = axum::http::Request::new(Body::empty());
+                *request.headers_mut() = headers;
+
+                let response = ServeFile::new(custom_file_path)
+                    .try_call(request)
+                    .await
+                    .map_err(|e| AppError::InternalServerError(anyhow::anyhow!(e)))?;
+                return Ok(response.into_response());
             }
+            Ok(_) | Err(AppError::NotFound) => {}
+            Err(e) => return Err(e),
         }
     }
 
     // 2. INTENTO DE CARGA DESDE MEMORIA (Default / Embedded)
-    match Assets::get(path) {
-        Some(content) => {
-            let mime = mime_guess::from_path(path).first_or_octet_stream();
-            (
-                [(header::CONTENT_TYPE, mime.as_ref())],
-                content.data,
-            ).into_response()
-        }
-        None => StatusCode::NOT_FOUND.into_response(),
+    let Some(content) = Assets::get(&path) else {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    };
+
+    let hash = content.metadata.sha256_hash();
+    let etag = format!("\"{}\"", hash[..16].iter().map(|b| format!("{:02x}", b)).collect::<String>());
+
+    let not_modified = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.split(',').any(|tag| {
+            let tag = tag.trim();
+            tag == "*" || tag.trim_start_matches("W/") == etag
+        }));
+
+    if not_modified {
+        return Ok((
+            StatusCode::NOT_MODIFIED,
+            [(header::ETAG, etag), (header::CACHE_CONTROL, CACHE_CONTROL.to_string())],
+        ).into_response());
+    }
+
+    let mime = mime_guess::from_path(&path).first_or_octet_stream();
+    Ok((
+        [
+            (header::CONTENT_TYPE, mime.as_ref().to_string()),
+            (header::ETAG, etag),
+            (header::CACHE_CONTROL, CACHE_CONTROL.to_string()),
+        ],
+        content.data,
+    ).into_response())
+}
+
+/// Lee una página HTML del tema (disco primero, embebida como respaldo).
+pub(super) async fn load_page(state: &AppState, name: &str) -> Result<String, AppError> {
+    let custom_page = match &state.theme {
+        Some(theme) => theme.resolve(name).await.ok(),
+        None => None,
+    };
+    if let Some(custom_path) = custom_page {
+        tokio::fs::read_to_string(custom_path).await
+            .map_err(|e| AppError::InternalServerError(anyhow::anyhow!("Error reading custom {}: {}", name, e)))
+    } else {
+        let file = Assets::get(name).ok_or(AppError::NotFound)?;
+        std::str::from_utf8(file.data.as_ref())
+            .map(str::to_string)
+            .map_err(|e| AppError::InternalServerError(anyhow::anyhow!("Asset encoding error: {}", e)))
     }
 }
@@ -1,17 +1,206 @@
-use axum::{extract::State, http::StatusCode};
+// Portapapeles compartido (ver `crate::clipboard`).
+//
+// `GET/POST /api/clipboard` leen la entrada más reciente y añaden una nueva como
+// texto plano, para `curl` y scripts. La web usa `/api/clipboard/ws`: al
+// conectarse recibe el historial (`snapshot`) y después cada cambio (`upsert`,
+// `delete`); por el mismo socket manda `push`, `edit`, `pin` y `delete`.
+//
+// El navegador manda la cookie de sesión también en WebSockets abiertos desde
+// otras webs, así que la conexión sólo se acepta si su `Origin` es este mismo
+// servidor. Los clientes que no son navegadores no envían `Origin`.
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Path, State,
+    },
+    http::{header, HeaderMap, StatusCode},
+    response::Response,
+    Json,
+};
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
-use crate::AppState;
+use tokio::sync::broadcast::error::RecvError;
+use crate::{
+    auth::CurrentUser,
+    clipboard::{ClipEntry, Clipboard, MAX_TEXT},
+    error::AppError,
+    users::Permission,
+    AppState,
+};
 
-pub async fn get_clipboard(State(state): State<Arc<AppState>>) -> String {
-    let clipboard = state.clipboard.read().expect("Lock poisoned");
-    clipboard.clone()
+pub async fn get_clipboard(
+    State(state): State<Arc<AppState>>,
+    user: CurrentUser,
+) -> Result<String, AppError> {
+    user.require(Permission::Clipboard)?;
+    Ok(state.clipboard.current())
 }
 
 pub async fn save_clipboard(
-    State(state): State<Arc<AppState>>, 
+    State(state): State<Arc<AppState>>,
+    user: CurrentUser,
     body: String
-) -> StatusCode {
-    let mut clipboard = state.clipboard.write().expect("Lock poisoned");
-    *clipboard = body;
-    StatusCode::OK
+) -> Result<Json<ClipEntry>, AppError> {
+    user.require(Permission::Clipboard)?;
+    if body.is_empty() {
+        return Err(AppError::BadRequest("El texto está vacío"));
+    }
+    Ok(Json(state.clipboard.push(body, user.username)?))
+}
+
+// GET /api/clipboard/history
+pub async fn history_handler(
+    State(state): State<Arc<AppState>>,
+    user: CurrentUser,
+) -> Result<Json<Vec<ClipEntry>>, AppError> {
+    user.require(Permission::Clipboard)?;
+    Ok(Json(state.clipboard.entries()))
+}
+
+#[derive(Deserialize)]
+pub struct PinRequest {
+    #[serde(default = "default_pinned")]
+    pinned: bool,
+}
+
+fn default_pinned() -> bool {
+    true
+}
+
+// POST /api/clipboard/:id/pin
+pub async fn pin_handler(
+    State(state): State<Arc<AppState>>,
+    user: CurrentUser,
+    Path(id): Path<u64>,
+    Json(request): Json<PinRequest>,
+) -> Result<Json<ClipEntry>, AppError> {
+    user.require(Permission::Clipboard)?;
+    Ok(Json(state.clipboard.pin(id, request.pinned)?))
+}
+
+// DELETE /api/clipboard/:id
+pub async fn delete_handler(
+    State(state): State<Arc<AppState>>,
+    user: CurrentUser,
+    Path(id): Path<u64>,
+) -> Result<StatusCode, AppError> {
+    user.require(Permission::Clipboard)?;
+    state.clipboard.delete(id)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Lo que manda el navegador por el socket
+#[derive(Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum ClientMessage {
+    /// `request` vuelve en el `saved` para saber qué entrada guarda el texto
+    Push { text: String, request: Option<u64> },
+    Edit { id: u64, revision: u64, text: String, request: Option<u64> },
+    Pin { id: u64, pinned: bool },
+    Delete { id: u64 },
+}
+
+/// Respuestas a un cliente concreto; los cambios van como `ClipEvent`
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ServerMessage {
+    Snapshot { entries: Vec<ClipEntry> },
+    Saved { request: Option<u64>, entry: ClipEntry },
+    Error { message: &'static str },
+}
+
+// GET /api/clipboard/ws
+pub async fn ws_handler(
+    State(state): State<Arc<AppState>>,
+    user: CurrentUser,
+    headers: HeaderMap,
+    ws: WebSocketUpgrade,
+) -> Result<Response, AppError> {
+    user.require(Permission::Clipboard)?;
+    if !same_origin(&headers) {
+        return Err(AppError::PermissionDenied);
+    }
+    let clipboard = state.clipboard.clone();
+    Ok(ws
+        // Margen para el JSON que envuelve el texto
+        .max_message_size(MAX_TEXT * 2)
+        .on_upgrade(move |socket| clipboard_session(socket, clipboard, user.username)))
+}
+
+/// Sin `Origin`, o con uno cuyo host y puerto son los de `Host`.
+fn same_origin(headers: &HeaderMap) -> bool {
+    let Some(origin) = headers.get(header::ORIGIN) else {
+        return true;
+    };
+    let authority = origin
+        .to_str()
+        .ok()
+        .and_then(|origin| origin.split_once("://"))
+        .map(|(_, authority)| authority.trim_end_matches('/'));
+    let host = headers.get(header::HOST).and_then(|host| host.to_str().ok());
+    matches!((authority, host), (Some(authority), Some(host)) if authority.eq_ignore_ascii_case(host))
+}
+
+async fn clipboard_session(mut socket: WebSocket, clipboard: Arc<Clipboard>, author: Option<String>) {
+    // Suscribirse antes de la foto: ningún cambio se queda entre medias
+    let mut events = clipboard.subscribe();
+    if send(&mut socket, &ServerMessage::Snapshot { entries: clipboard.entries() }).await.is_err() {
+        return;
+    }
+
+    loop {
+        tokio::select! {
+            message = socket.recv() => {
+                let text = match message {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(_))) | None => return,
+                    Some(Ok(_)) => continue,
+                    Some(Err(e)) => {
+                        tracing::debug!("WebSocket del portapapeles cerrado: {}", e);
+                        return;
+                    }
+                };
+                let reply = match serde_json::from_str::<ClientMessage>(&text) {
+                    Ok(request) => apply(&clipboard, request, author.clone()),
+                    Err(_) => Some(ServerMessage::Error { message: "Mensaje no válido" }),
+                };
+                if let Some(reply) = reply {
+                    if send(&mut socket, &reply).await.is_err() {
+                        return;
+                    }
+                }
+            }
+            event = events.recv() => {
+                let sent = match event {
+                    Ok(event) => send(&mut socket, &event).await,
+                    // Se ha quedado atrás: se le manda el historial entero
+                    Err(RecvError::Lagged(_)) => send(&mut socket, &ServerMessage::Snapshot { entries: clipboard.entries() }).await,
+                    Err(RecvError::Closed) => return,
+                };
+                if sent.is_err() {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+fn apply(clipboard: &Clipboard, request: ClientMessage, author: Option<String>) -> Option<ServerMessage> {
+    let result = match request {
+        ClientMessage::Push { text, request } => {
+            clipboard.push(text, author).map(|entry| Some(ServerMessage::Saved { request, entry }))
+        }
+        ClientMessage::Edit { id, revision, text, request } => {
+            clipboard.edit(id, revision, text, author).map(|entry| Some(ServerMessage::Saved { request, entry }))
+        }
+        ClientMessage::Pin { id, pinned } => clipboard.pin(id, pinned).map(|_| None),
+        ClientMessage::Delete { id } => clipboard.delete(id).map(|_| None),
+    };
+    result.unwrap_or_else(|e| Some(ServerMessage::Error { message: e.message() }))
+}
+
+async fn send(socket: &mut WebSocket, message: &impl Serialize) -> Result<(), axum::Error> {
+    let text = serde_json::to_string(message).unwrap_or_default();
+    socket.send(Message::Text(text)).await
 }
@@ -1,59 +1,80 @@
-use crate::{error::AppError, utils::archiver, AppState};
+use crate::{auth::CurrentUser, error::AppError, users::Permission, utils::{archiver::{self, ArchiveFormat, ArchiveItem}, paths, zip_plan::ZipPlan}, AppState};
 use axum::{
-    extract::{Path, State},
-    response::IntoResponse,
-    http::header,
+    extract::{FromRequest, Path, Query, Request, State},
+    response::{IntoResponse, Response},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
     body::Body,
+    Form, Json,
 };
+use serde::Deserialize;
 use std::sync::Arc;
 use tower_http::services::ServeFile;
 
+#[derive(Debug, Deserialize)]
+pub struct DownloadParams {
+    /// zip/store (stored, reanudable), deflate, tar, tgz o zst
+    format: Option<String>,
+    /// Nivel de compresión (gzip 1-9, zstd 1-22, Deflate con `zip`)
+    level: Option<i32>,
+}
+
 pub async fn download_handler(
     State(state): State<Arc<AppState>>,
+    user: CurrentUser,
     Path(request_path): Path<String>,
-) -> Result<impl IntoResponse, AppError> {
-    // 1. Saneamiento de ruta (Ciberseguridad básica: evitar Path Traversal ../../etc/passwd)
-    // Nota: En producción, esto requiere validación más estricta.
-    let clean_path = request_path.trim_start_matches('/');
-    if clean_path.contains("..") {
-        return Err(AppError::InvalidPath);
-    }
+    Query(params): Query<DownloadParams>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    user.require(Permission::Read)?;
 
-    // 2. Construir la ruta absoluta en el sistema de archivos
-    let full_path = state.base_path.join(clean_path);
+    // 1. Resolver la ruta dentro de la carpeta compartida (traversal, enlaces, existencia)
+    let full_path = state.resolver.resolve(&request_path).await?;
 
-    // 3. Verificar existencia
-    if !full_path.exists() {
-        return Err(AppError::NotFound);
-    }
+    let format = ArchiveFormat::parse(params.format.as_deref(), params.level)
+        .ok_or(AppError::BadRequest("Formato de descarga desconocido"))?;
+    serve_path(&state, full_path, format, params.level, headers).await
+}
 
-    // 4. Lógica de decisión: ¿Archivo o Carpeta?
+/// Sirve un archivo o una carpeta (comprimida en `format`) ya resuelto; lo
+/// comparten `/download` y los enlaces `/s/<token>`.
+pub(super) async fn serve_path(
+    state: &AppState,
+    full_path: std::path::PathBuf,
+    format: ArchiveFormat,
+    level: Option<i32>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    // 2. Lógica de decisión: ¿Archivo o Carpeta?
     if full_path.is_dir() {
-        // CASO CARPETA: Streaming de ZIP al vuelo
+        if !state.features.zip {
+            return Err(AppError::PermissionDenied); // --no-zip
+        }
+
         let dir_name = full_path.file_name()
             .unwrap_or_else(|| std::ffi::OsStr::new("download"))
             .to_string_lossy()
             .to_string();
-        
-        let zip_filename = format!("{}.zip", dir_name);
-
-        // Iniciamos el stream (aquí se conecta con utils/archiver.rs)
-        let stream = archiver::archive_directory_stream(full_path, dir_name);
-        let body = Body::from_stream(stream);
 
-        // Configuramos cabeceras para forzar descarga
-        let headers = [
-            (header::CONTENT_TYPE, "application/zip"),
-            (header::CONTENT_DISPOSITION, &format!("attachment; filename=\"{}\"", zip_filename)),
-        ];
+        if format != ArchiveFormat::Store {
+            // CASO CARPETA COMPRIMIDA: Streaming al vuelo (sin tamaño conocido)
+            let items = vec![ArchiveItem { source: full_path, name: dir_name.clone() }];
+            return archive_response(state, items, &dir_name, format, level);
+        }
 
-        Ok((headers, body).into_response())
+        // CASO CARPETA: ZIP stored planificado, con tamaño y rangos
+        let disposition = content_disposition(&format!("{}.zip", dir_name))?;
+        let plan = Arc::new(ZipPlan::build(&full_path, &dir_name, &state.resolver).await?);
+        zip_response(state, plan, &headers, disposition)
     } else {
         // CASO ARCHIVO: Servir directamente
-        // ServeFile maneja eficientemente la lectura del disco
+        // ServeFile maneja eficientemente la lectura del disco, incluidos Range e If-Range,
+        // así que le pasamos las cabeceras originales de la petición.
+        let mut request = axum::http::Request::new(Body::empty());
+        *request.headers_mut() = headers;
+
         let mut service = ServeFile::new(full_path);
-        let result = service.try_call(axum::http::Request::new(Body::empty())).await;
-        
+        let result = service.try_call(request).await;
+
         match result {
             Ok(res) => Ok(res.into_response()),
             Err(e) => {
@@ -62,4 +83,224 @@ pub async fn download_handler(
             }
         }
     }
-}
\ No newline at end of file
+}
+
+/// Archivo generado al vuelo con `items`, descargado como `<name>.<extensión>`.
+fn archive_response(
+    state: &AppState,
+    items: Vec<ArchiveItem>,
+    name: &str,
+    format: ArchiveFormat,
+    level: Option<i32>,
+) -> Result<Response, AppError> {
+    let disposition = content_disposition(&format!("{}.{}", name, format.extension()))?;
+    let stream = archiver::archive_stream(items, state.resolver.clone(), format, level);
+    let body = Body::from_stream(stream);
+
+    let headers = [
+        (header::CONTENT_TYPE, HeaderValue::from_static(format.content_type())),
+        (header::CONTENT_DISPOSITION, disposition),
+    ];
+
+    Ok((headers, body).into_response())
+}
+
+/// `Content-Disposition` para descargar como `file_name`: `filename=` con una
+/// versión ASCII para clientes antiguos y `filename*=` (RFC 5987) con el nombre
+/// completo en UTF-8. Comillas, barras y caracteres de control nunca llegan tal
+/// cual a la cabecera.
+fn content_disposition(file_name: &str) -> Result<HeaderValue, AppError> {
+    let fallback: String = file_name
+        .chars()
+        .map(|c| if (c.is_ascii_graphic() && c != '"' && c != '\\') || c == ' ' { c } else { '_' })
+        .collect();
+    let mut encoded = String::new();
+    for byte in file_name.bytes() {
+        // attr-char de RFC 5987; el resto va como %XX
+        if byte.is_ascii_alphanumeric() || b"!#$&+-.^_`|~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    let value = format!("attachment; filename=\"{}\"; filename*=UTF-8''{}", fallback, encoded);
+    HeaderValue::from_str(&value).map_err(|e| AppError::InternalServerError(e.into()))
+}
+
+#[derive(Deserialize)]
+pub struct Selection {
+    paths: Vec<String>,
+    #[serde(default)]
+    format: Option<String>,
+    #[serde(default)]
+    level: Option<i32>,
+}
+
+/// La misma selección enviada como formulario: `paths` es un array JSON.
+#[derive(Deserialize)]
+struct SelectionForm {
+    paths: String,
+    format: Option<String>,
+    level: Option<i32>,
+}
+
+// POST /api/download {"paths": ["/a.txt", "/fotos"], "format": "zip"}
+//
+// Un solo archivo con lo seleccionado, aunque venga de carpetas distintas. La
+// web lo envía como formulario para que el navegador guarde la descarga en
+// disco en vez de acumularla en memoria.
+pub async fn selection_handler(
+    State(state): State<Arc<AppState>>,
+    user: CurrentUser,
+    request: Request,
+) -> Result<Response, AppError> {
+    user.require(Permission::Read)?;
+    if !state.features.zip {
+        return Err(AppError::PermissionDenied); // --no-zip
+    }
+
+    let is_form = request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/x-www-form-urlencoded"));
+    let selection = if is_form {
+        let Form(form) = Form::<SelectionForm>::from_request(request, &()).await.map_err(|_| AppError::BadRequest("Selección inválida"))?;
+        let paths = serde_json::from_str(&form.paths).map_err(|_| AppError::BadRequest("Selección inválida"))?;
+        Selection { paths, format: form.format, level: form.level }
+    } else {
+        let Json(selection) = Json::<Selection>::from_request(request, &()).await.map_err(|_| AppError::BadRequest("Selección inválida"))?;
+        selection
+    };
+
+    let format = ArchiveFormat::parse(selection.format.as_deref(), selection.level)
+        .ok_or(AppError::BadRequest("Formato de descarga desconocido"))?;
+    let (items, name) = selection_items(&state, &selection.paths).await?;
+    archive_response(&state, items, &name, format, selection.level)
+}
+
+/// Resuelve la selección y nombra cada elemento por su ruta relativa a la
+/// carpeta común más profunda (`/docs/a.txt` + `/docs/q3/b.pdf` ->
+/// `a.txt`, `q3/b.pdf`). Lo que ya va dentro de otra carpeta seleccionada se
+/// omite. Devuelve también el nombre del archivo descargado.
+async fn selection_items(state: &AppState, requested: &[String]) -> Result<(Vec<ArchiveItem>, String), AppError> {
+    let mut selected = Vec::with_capacity(requested.len());
+    for path in requested {
+        let relative = paths::normalize(path)?;
+        if relative.as_os_str().is_empty() {
+            return Err(AppError::BadRequest("La carpeta compartida no se puede seleccionar; descárgala entera"));
+        }
+        let source = state.resolver.resolve(path).await?;
+        selected.push((relative, source));
+    }
+
+    // Ordenadas por componentes, cada carpeta va justo antes de su contenido
+    selected.sort_by(|a, b| a.0.cmp(&b.0));
+    selected.dedup_by(|item, kept| item.0.starts_with(&kept.0));
+    let (first, _) = selected.first().ok_or(AppError::BadRequest("No hay nada seleccionado"))?;
+
+    let mut common = first.parent().map(std::path::Path::to_path_buf).unwrap_or_default();
+    for (relative, _) in &selected {
+        while !relative.starts_with(&common) {
+            common.pop();
+        }
+    }
+
+    let name = match selected.as_slice() {
+        [(relative, _)] => relative.file_name(),
+        _ => common.file_name(),
+    }
+    .map(|n| n.to_string_lossy().into_owned())
+    .unwrap_or_else(|| "download".to_string());
+
+    let items = selected
+        .into_iter()
+        .map(|(relative, source)| ArchiveItem {
+            name: paths::display_path(relative.strip_prefix(&common).unwrap_or(&relative)),
+            source,
+        })
+        .collect();
+    Ok((items, name))
+}
+
+/// Construye la respuesta del ZIP virtual: completa (200), parcial (206) o 416.
+fn zip_response(state: &AppState, plan: Arc<ZipPlan>, headers: &HeaderMap, disposition: HeaderValue) -> Result<Response, AppError> {
+    let total = plan.total_size();
+    let etag = plan.etag().to_string();
+
+    // If-Range: si el ETag no coincide, el árbol cambió y hay que empezar de cero
+    let range_allowed = match headers.get(header::IF_RANGE).and_then(|v| v.to_str().ok()) {
+        Some(if_range) => if_range == etag,
+        None => true,
+    };
+    let range = headers
+        .get(header::RANGE)
+        .and_then(|v| v.to_str().ok())
+        .filter(|_| range_allowed)
+        .and_then(|v| parse_range(v, total));
+
+    let (status, start, end) = match range {
+        None => (StatusCode::OK, 0, total.saturating_sub(1)),
+        Some(Ok((start, end))) => (StatusCode::PARTIAL_CONTENT, start, end),
+        Some(Err(())) => {
+            return Response::builder()
+                .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                .header(header::CONTENT_RANGE, format!("bytes */{}", total))
+                .body(Body::empty())
+                .map_err(|e| AppError::InternalServerError(e.into()));
+        }
+    };
+
+    let length = if total == 0 { 0 } else { end - start + 1 };
+    let body = Body::from_stream(plan.stream_range(state.crc_cache.clone(), start, end));
+
+    let mut builder = Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "application/zip")
+        .header(header::CONTENT_DISPOSITION, disposition)
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header(header::CONTENT_LENGTH, length)
+        .header(header::ETAG, etag);
+
+    if status == StatusCode::PARTIAL_CONTENT {
+        builder = builder.header(header::CONTENT_RANGE, format!("bytes {}-{}/{}", start, end, total));
+    }
+
+    builder.body(body).map_err(|e| AppError::InternalServerError(e.into()))
+}
+
+/// Interpreta una cabecera `Range` de un único rango de bytes.
+/// `None` significa "ignorar y servir completo" (sintaxis desconocida o varios rangos),
+/// `Some(Err(()))` que el rango no se puede satisfacer.
+fn parse_range(value: &str, total: u64) -> Option<Result<(u64, u64), ()>> {
+    let spec = value.trim().strip_prefix("bytes=")?;
+    if spec.contains(',') {
+        return None;
+    }
+    let (start, end) = spec.trim().split_once('-')?;
+
+    let range = match (start.trim(), end.trim()) {
+        // bytes=-N: los últimos N bytes
+        ("", suffix) => {
+            let n: u64 = suffix.parse().ok()?;
+            if n == 0 || total == 0 {
+                return Some(Err(()));
+            }
+            (total.saturating_sub(n), total - 1)
+        }
+        // bytes=N- o bytes=N-M
+        (start, end) => {
+            let start: u64 = start.parse().ok()?;
+            let end: u64 = if end.is_empty() { u64::MAX } else { end.parse().ok()? };
+            if end < start {
+                return None;
+            }
+            if start >= total {
+                return Some(Err(()));
+            }
+            (start, end.min(total - 1))
+        }
+    };
+
+    Some(Ok(range))
+}
@@ -0,0 +1,67 @@
+// Cambios en vivo: `GET /api/events?path=/docs` (Server-Sent Events).
+//
+// Cada cambio en la carpeta que mira el cliente llega como un evento `change`
+// con el `FsEvent` en JSON (`created`, `modified`, `deleted`, `renamed`). Si el
+// cliente se queda atrás y se pierden eventos, recibe `resync` y debe volver a
+// pedir el listado.
+
+use crate::{
+    auth::CurrentUser,
+    error::AppError,
+    users::Permission,
+    utils::paths,
+    watcher::FsEvent,
+    AppState,
+};
+use axum::{
+    extract::{Query, State},
+    response::sse::{Event, KeepAlive, Sse},
+};
+use futures::Stream;
+use serde::Deserialize;
+use std::{convert::Infallible, sync::Arc};
+use tokio::sync::broadcast::{error::RecvError, Receiver};
+
+#[derive(Deserialize)]
+pub struct EventsParams {
+    /// Carpeta que se está viendo; por defecto la raíz
+    path: Option<String>,
+}
+
+// GET /api/events
+pub async fn events_handler(
+    State(state): State<Arc<AppState>>,
+    user: CurrentUser,
+    Query(params): Query<EventsParams>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, AppError> {
+    user.require(Permission::Read)?;
+    let watcher = state.watcher.clone().ok_or(AppError::NotFound)?;
+
+    let path = params.path.unwrap_or_default();
+    state.resolver.resolve_dir(&path).await?;
+    let scope = format!("/{}", paths::display_path(&paths::normalize(&path)?));
+
+    let events = futures::stream::unfold((watcher.subscribe(), scope), |(mut rx, scope)| async move {
+        let event = next_event(&mut rx, &scope).await?;
+        Some((Ok(event), (rx, scope)))
+    });
+
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+/// Siguiente evento que afecta a `scope`; `None` si el watcher se ha parado.
+async fn next_event(rx: &mut Receiver<FsEvent>, scope: &str) -> Option<Event> {
+    loop {
+        match rx.recv().await {
+            Ok(change) if change.concerns(scope) => {
+                return Some(Event::default().event("change").json_data(&change).unwrap_or_default());
+            }
+            Ok(_) => continue,
+            Err(RecvError::Lagged(missed)) => {
+                tracing::debug!("Cliente de /api/events atrasado: {} eventos perdidos", missed);
+                return Some(Event::default().event("resync").data(missed.to_string()));
+            }
+            Err(RecvError::Closed) => return None,
+        }
+    }
+}
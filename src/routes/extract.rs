@@ -0,0 +1,296 @@
+// Extracción de .zip / .tar / .tar.gz subidos con `/upload?extract=true`.
+//
+// El archivo comprimido se recibe entero en un temporal (cuenta para el límite
+// de subida) y después se extrae entrada a entrada: cada nombre pasa por
+// `paths::upload_destination` (nada de `../` ni rutas absolutas: zip-slip), se
+// escribe en su propio temporal y se publica con la política de conflictos,
+// igual que una subida normal. Los límites de entradas y de bytes
+// descomprimidos cortan las bombas ZIP.
+//
+// La respuesta es un stream NDJSON con un evento por entrada, así el cliente
+// ve el progreso de archivos con miles de entradas.
+
+use super::upload::TempUpload;
+use crate::{
+    error::AppError,
+    utils::{
+        conflict::{ConflictPolicy, Outcome, Placement},
+        paths,
+    },
+    AppState,
+};
+use axum::{
+    body::Body,
+    http::header,
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use std::sync::Arc;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader},
+    sync::mpsc,
+};
+use tokio_util::compat::FuturesAsyncReadCompatExt;
+
+const BUFFER_SIZE: usize = 64 * 1024;
+
+/// Formatos que se saben extraer, según la extensión.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArchiveKind {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+impl ArchiveKind {
+    pub fn detect(file_name: &str) -> Option<Self> {
+        let name = file_name.to_ascii_lowercase();
+        if name.ends_with(".zip") {
+            Some(Self::Zip)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(Self::TarGz)
+        } else if name.ends_with(".tar") {
+            Some(Self::Tar)
+        } else {
+            None
+        }
+    }
+}
+
+/// Topes por archivo comprimido contra las bombas ZIP.
+#[derive(Clone, Copy, Debug)]
+pub struct ExtractLimits {
+    pub max_entries: u64,
+    /// Bytes descomprimidos en total
+    pub max_bytes: u64,
+}
+
+/// Archivo comprimido ya recibido, pendiente de extraer. El temporal se borra
+/// al terminar (o si el cliente se va antes).
+pub(super) struct PendingArchive {
+    /// Nombre con el que llegó
+    pub name: String,
+    pub kind: ArchiveKind,
+    /// Carpeta donde extraer, relativa a la carpeta compartida
+    pub target: String,
+    pub temp: TempUpload,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub(super) enum ExtractEvent {
+    /// Archivo normal subido en la misma petición
+    File { name: String, path: String, status: Outcome },
+    /// Entrada extraída
+    Entry { archive: String, name: String, path: String, status: Outcome, size: u64 },
+    /// Entrada ignorada (enlaces, dispositivos, rutas inseguras...)
+    Skipped { archive: String, name: String, reason: &'static str },
+    Done { archive: String, entries: u64, bytes: u64 },
+    Error { archive: String, message: &'static str },
+}
+
+/// Respuesta NDJSON: primero los archivos normales, luego cada archivo comprimido.
+pub(super) fn respond(
+    state: Arc<AppState>,
+    user: Option<String>,
+    policy: ConflictPolicy,
+    files: Vec<ExtractEvent>,
+    archives: Vec<PendingArchive>,
+) -> Response {
+    let (tx, rx) = mpsc::channel::<ExtractEvent>(64);
+
+    tokio::spawn(async move {
+        for event in files {
+            if tx.send(event).await.is_err() {
+                return;
+            }
+        }
+        for archive in archives {
+            let placement = Placement {
+                policy,
+                trash: state.trash.as_deref(),
+                base_path: &state.base_path,
+                user: user.as_deref(),
+            };
+            let mut extractor = Extractor { state: &state, placement, archive: &archive, events: &tx, entries: 0, bytes: 0 };
+
+            let event = match extractor.run().await {
+                Ok(()) => ExtractEvent::Done { archive: archive.name.clone(), entries: extractor.entries, bytes: extractor.bytes },
+                Err(e) => {
+                    tracing::warn!("Extracción de {} interrumpida: {:?}", archive.name, e);
+                    ExtractEvent::Error { archive: archive.name.clone(), message: e.message() }
+                }
+            };
+            tracing::info!("Extraído {}: {} entradas, {} bytes", archive.name, extractor.entries, extractor.bytes);
+            if tx.send(event).await.is_err() {
+                return;
+            }
+        }
+    });
+
+    let lines = futures::stream::unfold(rx, |mut rx| async move {
+        let event = rx.recv().await?;
+        let mut line = serde_json::to_vec(&event).unwrap_or_default();
+        line.push(b'\n');
+        Some((Ok::<_, std::convert::Infallible>(line), rx))
+    });
+
+    ([(header::CONTENT_TYPE, "application/x-ndjson")], Body::from_stream(lines)).into_response()
+}
+
+struct Extractor<'a> {
+    state: &'a AppState,
+    placement: Placement<'a>,
+    archive: &'a PendingArchive,
+    events: &'a mpsc::Sender<ExtractEvent>,
+    entries: u64,
+    bytes: u64,
+}
+
+impl Extractor<'_> {
+    async fn run(&mut self) -> Result<(), AppError> {
+        let path = self.archive.temp.path.clone();
+        match self.archive.kind {
+            ArchiveKind::Zip => self.extract_zip().await,
+            ArchiveKind::Tar => self.extract_tar(tokio::fs::File::open(&path).await?).await,
+            ArchiveKind::TarGz => {
+                let file = BufReader::new(tokio::fs::File::open(&path).await?);
+                self.extract_tar(async_compression::tokio::bufread::GzipDecoder::new(file)).await
+            }
+        }
+    }
+
+    async fn extract_zip(&mut self) -> Result<(), AppError> {
+        let reader = async_zip::tokio::read::fs::ZipFileReader::new(&self.archive.temp.path)
+            .await
+            .map_err(invalid_archive)?;
+
+        // El directorio central ya dice cuántas entradas hay
+        if reader.file().entries().len() as u64 > self.state.extract_limits.max_entries {
+            return Err(AppError::BadRequest("El archivo tiene demasiadas entradas"));
+        }
+
+        for (index, entry) in reader.file().entries().iter().enumerate() {
+            let Ok(name) = entry.filename().as_str().map(str::to_string) else {
+                self.count_entry()?;
+                self.skip(String::from_utf8_lossy(entry.filename().as_bytes()).into_owned(), "nombre no UTF-8").await?;
+                continue;
+            };
+
+            // Los enlaces simbólicos vienen como archivos con modo S_IFLNK
+            let is_symlink = entry.unix_permissions().is_some_and(|mode| mode & 0o170000 == 0o120000);
+            if entry.dir().map_err(invalid_archive)? {
+                self.directory(&name).await?;
+            } else if is_symlink {
+                self.count_entry()?;
+                self.skip(name, "enlace simbólico").await?;
+            } else {
+                let data = reader.reader_without_entry(index).await.map_err(invalid_archive)?;
+                self.file(&name, data.compat()).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn extract_tar<R: AsyncRead + Unpin + Send>(&mut self, reader: R) -> Result<(), AppError> {
+        use futures::StreamExt;
+
+        let mut archive = tokio_tar::Archive::new(reader);
+        let mut entries = archive.entries()?;
+        while let Some(entry) = entries.next().await {
+            let mut entry = entry.map_err(|e| invalid_archive(anyhow::Error::new(e)))?;
+            let name = entry.path()?.to_string_lossy().into_owned();
+            let kind = entry.header().entry_type();
+
+            if kind.is_dir() {
+                self.directory(&name).await?;
+            } else if kind.is_file() || kind.is_contiguous() {
+                self.file(&name, &mut entry).await?;
+            } else {
+                self.count_entry()?;
+                let reason = if kind.is_symlink() || kind.is_hard_link() { "enlace" } else { "tipo de entrada no admitido" };
+                self.skip(name, reason).await?;
+            }
+        }
+        Ok(())
+    }
+
+    fn count_entry(&mut self) -> Result<(), AppError> {
+        self.entries += 1;
+        if self.entries > self.state.extract_limits.max_entries {
+            return Err(AppError::BadRequest("El archivo tiene demasiadas entradas"));
+        }
+        Ok(())
+    }
+
+    async fn emit(&self, event: ExtractEvent) -> Result<(), AppError> {
+        self.events.send(event).await.map_err(|_| anyhow::anyhow!("cliente desconectado").into())
+    }
+
+    async fn skip(&self, name: String, reason: &'static str) -> Result<(), AppError> {
+        self.emit(ExtractEvent::Skipped { archive: self.archive.name.clone(), name, reason }).await
+    }
+
+    async fn directory(&mut self, name: &str) -> Result<(), AppError> {
+        self.count_entry()?;
+        let target = paths::normalize(&self.archive.target)?;
+        let relative = paths::normalize(&format!("{}/{}", self.archive.target, name));
+        match relative {
+            Ok(relative) if relative.starts_with(&target) => {
+                self.state.resolver.create_dir_all(&paths::display_path(&relative)).await?;
+                Ok(())
+            }
+            Ok(_) | Err(AppError::InvalidPath | AppError::NotFound) => self.skip(name.to_string(), "ruta insegura").await,
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn file<R: AsyncRead + Unpin>(&mut self, name: &str, mut reader: R) -> Result<(), AppError> {
+        self.count_entry()?;
+        // Zip-slip: la ruta de la entrada no puede salir de la carpeta destino
+        let (dir, file_name) = match paths::upload_destination(&self.archive.target, name) {
+            Ok(destination) => destination,
+            Err(AppError::InvalidPath | AppError::NotFound) => return self.skip(name.to_string(), "ruta insegura").await,
+            Err(e) => return Err(e),
+        };
+        let target_dir = self.state.resolver.create_dir_all(&dir).await?;
+
+        let temp = TempUpload::new(&target_dir);
+        let mut file = tokio::fs::File::create(&temp.path).await?;
+        let mut buffer = vec![0; BUFFER_SIZE];
+        let mut size = 0u64;
+        loop {
+            let read = reader.read(&mut buffer).await?;
+            if read == 0 {
+                break;
+            }
+            size += read as u64;
+            self.bytes += read as u64;
+            if self.bytes > self.state.extract_limits.max_bytes {
+                return Err(AppError::BadRequest("El contenido descomprimido supera el límite"));
+            }
+            file.write_all(&buffer[..read]).await?;
+        }
+        file.flush().await?;
+        drop(file);
+
+        let placed = self.placement.place(&temp.path, &target_dir, &file_name).await?;
+        if placed.outcome != Outcome::Skipped {
+            temp.persist();
+        }
+
+        self.emit(ExtractEvent::Entry {
+            archive: self.archive.name.clone(),
+            name: name.to_string(),
+            path: placed.relative,
+            status: placed.outcome,
+            size,
+        })
+        .await
+    }
+}
+
+fn invalid_archive(err: impl Into<anyhow::Error>) -> AppError {
+    tracing::warn!("Archivo comprimido inválido: {:#}", err.into());
+    AppError::BadRequest("Archivo comprimido inválido o dañado")
+}
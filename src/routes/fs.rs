@@ -0,0 +1,182 @@
+// Gestión de archivos por API: borrar, crear carpetas, mover/renombrar y copiar.
+//
+// Todas las rutas pasan por el mismo `PathResolver` que listar y descargar, así
+// que valen las mismas reglas de `..`, carpetas reservadas y enlaces simbólicos.
+
+use crate::{auth::CurrentUser, error::AppError, users::Permission, utils::paths, AppState};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use std::{path::PathBuf, sync::Arc};
+
+#[derive(Deserialize)]
+pub struct MkdirRequest {
+    path: String,
+}
+
+#[derive(Deserialize)]
+pub struct TransferRequest {
+    from: String,
+    to: String,
+}
+
+/// Ruta resultante, relativa a la carpeta compartida
+#[derive(Serialize)]
+pub struct FsResult {
+    path: String,
+}
+
+impl FsResult {
+    fn new(request_path: &str) -> Result<Self, AppError> {
+        Ok(Self { path: format!("/{}", paths::display_path(&paths::normalize(request_path)?)) })
+    }
+}
+
+/// Origen de un borrado o traslado: nunca la propia carpeta compartida.
+async fn resolve_source(state: &AppState, request_path: &str) -> Result<PathBuf, AppError> {
+    if paths::normalize(request_path)?.as_os_str().is_empty() {
+        return Err(AppError::BadRequest("No se puede modificar la carpeta compartida"));
+    }
+    state.resolver.resolve(request_path).await
+}
+
+// DELETE /api/fs/delete/*path
+pub async fn delete_handler(
+    State(state): State<Arc<AppState>>,
+    user: CurrentUser,
+    Path(path): Path<String>,
+) -> Result<StatusCode, AppError> {
+    user.require(Permission::Delete)?;
+    let full_path = resolve_source(&state, &path).await?;
+
+    // Con papelera se mueve allí; sin ella se borra del todo
+    if let Some(trash) = &state.trash {
+        let original = FsResult::new(&path)?.path;
+        trash.put(&full_path, original, user.username.clone()).await?;
+        return Ok(StatusCode::NO_CONTENT);
+    }
+
+    // symlink_metadata: un enlace se borra a sí mismo, nunca su destino
+    if tokio::fs::symlink_metadata(&full_path).await?.is_dir() {
+        tokio::fs::remove_dir_all(&full_path).await?;
+    } else {
+        tokio::fs::remove_file(&full_path).await?;
+    }
+
+    tracing::info!("Borrado: {:?}", full_path);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// POST /api/fs/mkdir { "path": "/docs/nueva" }
+pub async fn mkdir_handler(
+    State(state): State<Arc<AppState>>,
+    user: CurrentUser,
+    Json(req): Json<MkdirRequest>,
+) -> Result<(StatusCode, Json<FsResult>), AppError> {
+    user.require(Permission::Upload)?;
+    let target = state.resolver.resolve_new(&req.path).await?;
+
+    tokio::fs::create_dir(&target).await.map_err(conflict)?;
+    Ok((StatusCode::CREATED, Json(FsResult::new(&req.path)?)))
+}
+
+// POST /api/fs/move { "from": "/a.txt", "to": "/docs/b.txt" } (también renombra)
+pub async fn move_handler(
+    State(state): State<Arc<AppState>>,
+    user: CurrentUser,
+    Json(req): Json<TransferRequest>,
+) -> Result<Json<FsResult>, AppError> {
+    user.require(Permission::Delete)?;
+    let source = resolve_source(&state, &req.from).await?;
+    let target = state.resolver.resolve_new(&req.to).await?;
+    // Se mueve la entrada en sí (si es un enlace, el enlace)
+    check_not_inside(&real_entry(&source).await?, &target).await?;
+
+    tokio::fs::rename(&source, &target).await?;
+    tracing::info!("Movido: {:?} -> {:?}", source, target);
+    Ok(Json(FsResult::new(&req.to)?))
+}
+
+// POST /api/fs/copy { "from": "/docs", "to": "/docs-copia" }
+pub async fn copy_handler(
+    State(state): State<Arc<AppState>>,
+    user: CurrentUser,
+    Json(req): Json<TransferRequest>,
+) -> Result<(StatusCode, Json<FsResult>), AppError> {
+    user.require(Permission::Read)?;
+    user.require(Permission::Upload)?;
+    let source = resolve_source(&state, &req.from).await?;
+    let target = state.resolver.resolve_new(&req.to).await?;
+    // Se copia lo que hay al final de los enlaces
+    check_not_inside(&tokio::fs::canonicalize(&source).await?, &target).await?;
+
+    copy_tree(source.clone(), target.clone()).await?;
+    tracing::info!("Copiado: {:?} -> {:?}", source, target);
+    Ok((StatusCode::CREATED, Json(FsResult::new(&req.to)?)))
+}
+
+/// Una carpeta no puede moverse ni copiarse dentro de sí misma. Se comparan
+/// rutas reales: a través de un enlace simbólico el destino puede quedar dentro
+/// del origen aunque las rutas pedidas no lo parezcan.
+async fn check_not_inside(source: &std::path::Path, target: &std::path::Path) -> Result<(), AppError> {
+    if real_entry(target).await?.starts_with(source) {
+        return Err(AppError::BadRequest("El destino está dentro del origen"));
+    }
+    Ok(())
+}
+
+/// Ruta real de la carpeta que contiene `path`, más su nombre: el último
+/// componente no se sigue (puede no existir todavía o ser un enlace).
+async fn real_entry(path: &std::path::Path) -> Result<PathBuf, AppError> {
+    let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+        return Err(AppError::InvalidPath);
+    };
+    Ok(tokio::fs::canonicalize(parent).await?.join(name))
+}
+
+/// Otro proceso creó el destino entre la comprobación y la operación.
+fn conflict(err: std::io::Error) -> AppError {
+    match err.kind() {
+        std::io::ErrorKind::AlreadyExists => AppError::Conflict,
+        _ => err.into(),
+    }
+}
+
+/// Copia un archivo o una carpeta completa. Los enlaces simbólicos de dentro no
+/// se copian: seguirlos podría sacar datos de fuera o entrar en un bucle.
+async fn copy_tree(source: PathBuf, target: PathBuf) -> Result<(), AppError> {
+    if !tokio::fs::metadata(&source).await?.is_dir() {
+        copy_file(&source, &target).await?;
+        return Ok(());
+    }
+
+    let mut pending = vec![(source, target)];
+    while let Some((from, to)) = pending.pop() {
+        tokio::fs::create_dir(&to).await.map_err(conflict)?;
+
+        let mut entries = tokio::fs::read_dir(&from).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let file_type = entry.file_type().await?;
+            let dest = to.join(entry.file_name());
+            if file_type.is_symlink() {
+                tracing::warn!("Copia: se omite el enlace simbólico {:?}", entry.path());
+            } else if file_type.is_dir() {
+                pending.push((entry.path(), dest));
+            } else {
+                copy_file(&entry.path(), &dest).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `create_new` para no pisar nada que haya aparecido mientras tanto.
+async fn copy_file(from: &std::path::Path, to: &std::path::Path) -> Result<(), AppError> {
+    let mut reader = tokio::fs::File::open(from).await?;
+    let mut writer = tokio::fs::OpenOptions::new().write(true).create_new(true).open(to).await.map_err(conflict)?;
+    tokio::io::copy(&mut reader, &mut writer).await?;
+    Ok(())
+}
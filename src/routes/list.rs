@@ -1,4 +1,10 @@
-use crate::{error::AppError, utils::html, AppState, assets::Assets};
+use crate::{
+    auth::CurrentUser,
+    error::AppError,
+    users::Permission,
+    utils::{html, listing::{self, FileEntry, Page, Sort, Start}, paths},
+    AppState,
+};
 use axum::{
     extract::{Path, State, Query},
     response::{Html, IntoResponse, Response},
@@ -7,23 +13,84 @@ use axum::{
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
+/// Página de la web moderna y del modo legacy cuando no se pide `limit`.
+const PAGE_SIZE: usize = 500;
+
 #[derive(Debug, Deserialize)]
 pub struct ListParams {
     format: Option<String>, // "json" or empty
     mode: Option<String>,   // "legacy" or empty
-}
-
-#[derive(Serialize)]
-struct FileEntry {
-    name: String,
-    is_dir: bool,
-    size: u64,
+    /// `name`, `size` o `mtime`
+    sort: Option<String>,
+    /// `asc` o `desc`
+    order: Option<String>,
+    offset: Option<usize>,
+    limit: Option<usize>,
+    /// `next_cursor` de la página anterior
+    cursor: Option<String>,
 }
 
 #[derive(Serialize)]
 struct DirectoryListing {
     current_path: String,
     entries: Vec<FileEntry>,
+    sort: &'static str,
+    order: &'static str,
+    /// Entradas visibles en la carpeta (no sólo en esta página)
+    total: usize,
+    offset: usize,
+    /// Para pedir la página siguiente con `?cursor=`
+    next_cursor: Option<String>,
+    /// Usuario con sesión iniciada (la web muestra "Logout")
+    #[serde(skip_serializing_if = "Option::is_none")]
+    user: Option<String>,
+    /// Permisos de quien pide el listado, para mostrar sólo las acciones posibles
+    permissions: Vec<Permission>,
+    /// Qué funciones están activas, para ocultar los botones que no sirven
+    features: ListingFeatures,
+}
+
+#[derive(Serialize)]
+struct ListingFeatures {
+    upload: bool,
+    clipboard: bool,
+    zip: bool,
+    /// Lo borrado va a la papelera (y hay API para recuperarlo)
+    trash: bool,
+    /// Hay índice de contenido (`/api/search/content`)
+    content_search: bool,
+    /// `/api/events` avisa de los cambios en la carpeta
+    live_updates: bool,
+}
+
+impl From<&crate::config::Features> for ListingFeatures {
+    fn from(features: &crate::config::Features) -> Self {
+        Self {
+            upload: features.upload,
+            clipboard: features.clipboard,
+            zip: features.zip,
+            trash: features.upload && features.trash,
+            content_search: features.content_search && cfg!(feature = "content-search"),
+            live_updates: features.live_updates,
+        }
+    }
+}
+
+impl DirectoryListing {
+    fn new(page: Page, req_path: &str, user: &CurrentUser, state: &AppState) -> Self {
+        Self {
+            current_path: if req_path.is_empty() { "/".to_string() } else { format!("/{}", req_path) },
+            entries: page.entries,
+            sort: page.sort.key.as_str(),
+            order: page.sort.order(),
+            total: page.total,
+            offset: page.offset,
+            next_cursor: page.next_cursor,
+            user: user.username.clone(),
+            permissions: user.permissions.clone(),
+            features: ListingFeatures { live_updates: state.watcher.is_some(), ..(&state.features).into() },
+        }
+    }
 }
 
 // Redirige "/" a "/list/"
@@ -34,96 +101,57 @@ pub async fn root_handler() -> axum::response::Redirect {
 // Muestra el contenido del directorio
 pub async fn list_handler(
     State(state): State<Arc<AppState>>,
+    user: CurrentUser,
     path: Option<Path<String>>,
     Query(params): Query<ListParams>,
 ) -> Result<Response, AppError> { // Changed return type to Response to allow mix of Html and Json
+    user.require(Permission::Read)?;
+
     // Manejar caso donde path es None (la raíz /list/)
     let req_path = path.map(|p| p.0).unwrap_or_default();
-    
-    // Saneamiento básico
-    if req_path.contains("..") {
-        return Err(AppError::InvalidPath);
-    }
 
-    let full_path = state.base_path.join(req_path.trim_start_matches('/'));
+    // Resolución segura y forma canónica de la ruta para mostrarla
+    let full_path = state.resolver.resolve_dir(&req_path).await?;
+    let req_path = paths::display_path(&paths::normalize(&req_path)?);
 
-    if !full_path.exists() {
-        return Err(AppError::NotFound);
-    }
+    let sort = Sort::parse(params.sort.as_deref(), params.order.as_deref())
+        .ok_or(AppError::BadRequest("Orden no válido: usa sort=name|size|mtime y order=asc|desc"))?;
+    let start = match params.cursor.as_deref() {
+        Some(cursor) => Start::Cursor(cursor),
+        None => Start::Offset(params.offset.unwrap_or(0)),
+    };
+    // En JSON, sin `limit` ni cursor se devuelve la carpeta entera como siempre
+    let json = params.format.as_deref() == Some("json");
+    let limit = match params.limit {
+        Some(limit) => Some(limit),
+        None if json && params.cursor.is_none() => None,
+        None => Some(PAGE_SIZE),
+    };
 
-    // Leemos el directorio
-    let mut entries = tokio::fs::read_dir(&full_path).await?;
-    
-    // Recopilamos items
-    let mut items = Vec::new();
-    while let Some(entry) = entries.next_entry().await? {
-        let metadata = entry.metadata().await?;
-        let name = entry.file_name().to_string_lossy().to_string();
-        // Ignorar archivos ocultos unix
-        if name.starts_with('.') { continue; }
-        items.push((name, metadata.is_dir(), metadata.len()));
+    let entries = listing::read_entries(&full_path, &state.resolver).await?;
+    let mut page = listing::paginate(entries, sort, start, limit).ok_or(AppError::BadRequest("Cursor no válido"))?;
+    listing::count_children(&full_path, &mut page.entries).await;
+
+    // Check Mode
+    if !json && params.mode.as_deref() == Some("legacy") {
+        let html = html::generate_file_list_html(&page, limit.unwrap_or(PAGE_SIZE), &req_path, state.max_upload_size, &state.features);
+        return Ok(Html(html).into_response());
     }
 
-    // Ordenar: Carpetas primero, luego alfabético
-    items.sort_by(|a, b| {
-        b.1.cmp(&a.1).then(a.0.cmp(&b.0))
-    });
+    let listing = DirectoryListing::new(page, &req_path, &user, &state);
 
     // Check format
-    if params.format.as_deref() == Some("json") {
-        let file_entries: Vec<FileEntry> = items.into_iter().map(|(name, is_dir, size)| FileEntry {
-            name,
-            is_dir,
-            size,
-        }).collect();
-
-        let listing = DirectoryListing {
-            current_path: if req_path.is_empty() { "/".to_string() } else { format!("/{}", req_path) },
-            entries: file_entries,
-        };
-
+    if json {
         return Ok(Json(listing).into_response());
     }
 
-    // Check Mode
-    if params.mode.as_deref() == Some("legacy") {
-        let html = html::generate_file_list_html(items, &req_path, state.max_upload_size);
-        return Ok(Html(html).into_response());
-    }
-
     // Modern Mode (Default)
     // 1. Get DirectoryListing struct for injection
-    let file_entries: Vec<FileEntry> = items.into_iter().map(|(name, is_dir, size)| FileEntry {
-        name,
-        is_dir,
-        size,
-    }).collect();
-
-    let listing = DirectoryListing {
-        current_path: if req_path.is_empty() { "/".to_string() } else { format!("/{}", req_path) },
-        entries: file_entries,
-    };
-    
-    let initial_data_json = serde_json::to_string(&listing).unwrap_or_default();
+    // Dentro de <script>: un nombre con `</script>` no debe cerrar la etiqueta
+    let initial_data_json = html::script_json(&listing);
 
     // 2. Load index.html (Disk First -> Embedded Fallback)
-    let index_content = if let Some(theme_dir) = &state.theme_path {
-        let custom_path = theme_dir.join("index.html");
-        if custom_path.exists() {
-             tokio::fs::read_to_string(custom_path).await
-                .map_err(|e| AppError::InternalServerError(anyhow::anyhow!("Error reading custom index.html: {}", e)))?
-        } else {
-             let index_file = Assets::get("index.html").ok_or(AppError::NotFound)?;
-             std::str::from_utf8(index_file.data.as_ref())
-                .map_err(|e| AppError::InternalServerError(anyhow::anyhow!("Asset encoding error: {}", e)))?
-                .to_string()
-        }
-    } else {
-         let index_file = Assets::get("index.html").ok_or(AppError::NotFound)?;
-         std::str::from_utf8(index_file.data.as_ref())
-            .map_err(|e| AppError::InternalServerError(anyhow::anyhow!("Asset encoding error: {}", e)))?
-            .to_string()
-    };
+    let index_content = super::assets::load_page(&state, "index.html").await?;
 
     // 3. Inject Data
     // Note: We used `__INITIAL_DATA__` placeholder in `index.html`. 
@@ -135,4 +163,4 @@ pub async fn list_handler(
     let html_string = index_content.replace("__INITIAL_DATA__", &initial_data_json);
 
     Ok(Html(html_string).into_response())
-}
\ No newline at end of file
+}
@@ -1,5 +1,5 @@
 use axum::{routing::{get, post}, Router};
-use crate::AppState;
+use crate::{config::Features, AppState};
 use std::sync::Arc;
 
 mod download;
@@ -7,9 +7,26 @@ mod list;
 mod upload;
 mod assets;
 mod clipboard;
+mod tus;
+mod session;
+mod shares;
+mod fs;
+mod trash;
+mod extract;
+mod search;
+mod events;
+
+pub use extract::ExtractLimits;
+pub use tus::{spawn_upload_sweeper, UploadLocks};
+
+/// Las rutas de las funciones desactivadas (`--read-only`, `--no-clipboard`)
+/// ni se montan: responden 404. `--read-only` también quita la gestión de archivos.
+pub fn app_router(features: &Features) -> Router<Arc<AppState>> {
+    let mut router = Router::new()
+        // Inicio y cierre de sesión (cookie)
+        .route("/login", get(session::login_page).post(session::login_handler))
+        .route("/logout", get(session::logout_handler).post(session::logout_handler))
 
-pub fn app_router() -> Router<Arc<AppState>> {
-    Router::new()
         // Ruta raíz: redirige a la lista principal
         .route("/", get(list::root_handler))
         
@@ -24,12 +41,80 @@ pub fn app_router() -> Router<Arc<AppState>> {
         // Ruta para descargar
         .route("/download/*path", get(download::download_handler))
 
-        // Ruta para subir archivos
-        .route("/upload", post(upload::upload_handler))
+        // Descarga de varios archivos y carpetas seleccionados en un solo archivo
+        .route("/api/download", post(download::selection_handler))
+
+        // Búsqueda recursiva por nombre (NDJSON)
+        .route("/api/search", get(search::search_handler))
+
+        // Enlaces compartidos: API y descarga pública
+        .route("/api/shares", get(shares::list_handler).post(shares::create_handler))
+        .route("/api/shares/:id", axum::routing::delete(shares::revoke_handler))
+        .route("/s/:token", get(shares::open_handler).post(shares::unlock_handler))
 
         // Ruta para assets estáticos
-        .route("/assets/*path", get(assets::assets_handler))
+        .route("/assets/*path", get(assets::assets_handler));
+
+    if features.upload {
+        router = router
+            // Ruta para subir archivos
+            .route("/upload", post(upload::upload_handler))
+
+            // Subidas reanudables (protocolo tus)
+            .route("/api/tus", post(tus::create_handler).options(tus::options_handler))
+            .route(
+                "/api/tus/:id",
+                axum::routing::head(tus::head_handler)
+                    .patch(tus::patch_handler)
+                    .delete(tus::terminate_handler),
+            )
 
+            // Gestión de archivos: borrar, crear carpetas, mover y copiar. El borrado
+            // lleva su propio prefijo para que `/mkdir`, `/move` o `/copy` en la raíz
+            // no choquen con las rutas fijas.
+            .route("/api/fs/delete/*path", axum::routing::delete(fs::delete_handler))
+            .route("/api/fs/mkdir", post(fs::mkdir_handler))
+            .route("/api/fs/move", post(fs::move_handler))
+            .route("/api/fs/copy", post(fs::copy_handler))
+
+            // Papelera (404 con --no-trash)
+            .route("/api/trash", get(trash::list_handler).delete(trash::empty_handler))
+            .route("/api/trash/:id", axum::routing::delete(trash::purge_handler))
+            .route("/api/trash/:id/restore", post(trash::restore_handler));
+    }
+
+    // Búsqueda en el contenido (índice tantivy)
+    #[cfg(feature = "content-search")]
+    if features.content_search {
+        router = router.route("/api/search/content", get(search::content_handler));
+    }
+
+    // Cambios en vivo de la carpeta (SSE)
+    if features.live_updates {
+        router = router.route("/api/events", get(events::events_handler));
+    }
+
+    if features.clipboard {
         // Clipboard
-        .route("/api/clipboard", get(clipboard::get_clipboard).post(clipboard::save_clipboard))
+        router = router
+            .route("/api/clipboard", get(clipboard::get_clipboard).post(clipboard::save_clipboard))
+            // Historial, fijar y borrar entradas
+            .route("/api/clipboard/history", get(clipboard::history_handler))
+            .route("/api/clipboard/:id", axum::routing::delete(clipboard::delete_handler))
+            .route("/api/clipboard/:id/pin", post(clipboard::pin_handler))
+            // Cambios en tiempo real (WebSocket)
+            .route("/api/clipboard/ws", get(clipboard::ws_handler));
+    }
+
+    router
+}
+
+/// Modo buzón (`--drop-box`): se puede subir, pero no listar ni descargar nada.
+pub fn drop_box_router() -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/login", get(session::login_page).post(session::login_handler))
+        .route("/logout", get(session::logout_handler).post(session::logout_handler))
+        .route("/", get(upload::drop_box_page))
+        .route("/upload", post(upload::drop_box_handler))
+        .route("/assets/*path", get(assets::assets_handler))
 }
\ No newline at end of file
@@ -0,0 +1,241 @@
+// Búsqueda por nombre: `GET /api/search?q=&path=&glob=&regex=&type=`.
+//
+// Recorre el árbol desde `path` con las mismas reglas que el listado (sin
+// ocultos ni carpetas internas, enlaces según la política) y va devolviendo
+// los resultados como NDJSON según aparecen. Si el cliente corta la conexión
+// el canal se cierra y el recorrido se detiene.
+//
+// Con la feature `content-search`, `GET /api/search/content?q=` busca dentro
+// de los archivos en el índice de `crate::content_index`.
+
+use crate::{
+    auth::CurrentUser,
+    error::AppError,
+    users::Permission,
+    utils::{listing::unix_seconds, paths, walk::TreeWalker},
+    AppState,
+};
+use axum::{
+    body::Body,
+    extract::{Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use globset::{GlobBuilder, GlobMatcher};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+const DEFAULT_DEPTH: usize = 32;
+const MAX_DEPTH: usize = 64;
+const DEFAULT_LIMIT: usize = 200;
+const MAX_LIMIT: usize = 5000;
+
+#[derive(Deserialize)]
+pub struct SearchParams {
+    /// Palabras que deben aparecer en el nombre (sin distinguir mayúsculas)
+    q: Option<String>,
+    /// Carpeta desde la que buscar; por defecto la raíz
+    path: Option<String>,
+    /// `*.jpg`; con `/` se compara con la ruta relativa a `path`
+    glob: Option<String>,
+    /// Expresión regular sobre el nombre
+    regex: Option<String>,
+    /// `file` o `dir`
+    #[serde(rename = "type")]
+    kind: Option<String>,
+    depth: Option<usize>,
+    limit: Option<usize>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum SearchEvent {
+    Match { path: String, name: String, is_dir: bool, size: u64, modified: Option<u64> },
+    /// Última línea: `truncated` si se llegó al límite de resultados
+    Done { matches: usize, scanned: u64, truncated: bool },
+    Error { message: &'static str },
+}
+
+struct Matcher {
+    terms: Vec<String>,
+    glob: Option<GlobMatcher>,
+    /// El glob lleva `/`: se compara con la ruta y no sólo con el nombre
+    glob_path: bool,
+    regex: Option<Regex>,
+    /// `Some(true)`: sólo carpetas; `Some(false)`: sólo archivos
+    dirs: Option<bool>,
+}
+
+impl Matcher {
+    fn new(params: &SearchParams) -> Result<Self, AppError> {
+        let terms: Vec<String> = params.q.as_deref().unwrap_or("").split_whitespace().map(str::to_lowercase).collect();
+        let glob = params.glob.as_deref().filter(|g| !g.is_empty());
+        let regex = params.regex.as_deref().filter(|r| !r.is_empty());
+        if terms.is_empty() && glob.is_none() && regex.is_none() {
+            return Err(AppError::BadRequest("Indica q, glob o regex"));
+        }
+
+        let glob_path = glob.is_some_and(|g| g.contains('/'));
+        let glob = glob
+            .map(|g| {
+                GlobBuilder::new(g.trim_start_matches('/'))
+                    .case_insensitive(true)
+                    .literal_separator(true)
+                    .build()
+                    .map(|glob| glob.compile_matcher())
+                    .map_err(|_| AppError::BadRequest("Patrón glob no válido"))
+            })
+            .transpose()?;
+        let regex = regex
+            .map(|r| {
+                RegexBuilder::new(r)
+                    .case_insensitive(true)
+                    .size_limit(1 << 20)
+                    .build()
+                    .map_err(|_| AppError::BadRequest("Expresión regular no válida"))
+            })
+            .transpose()?;
+        let dirs = match params.kind.as_deref() {
+            None | Some("") | Some("any") => None,
+            Some("dir") | Some("d") | Some("directory") => Some(true),
+            Some("file") | Some("f") => Some(false),
+            Some(_) => return Err(AppError::BadRequest("type debe ser file o dir")),
+        };
+        Ok(Self { terms, glob, glob_path, regex, dirs })
+    }
+
+    fn matches(&self, name: &str, relative: &str, is_dir: bool) -> bool {
+        if self.dirs.is_some_and(|dirs| dirs != is_dir) {
+            return false;
+        }
+        let lower = name.to_lowercase();
+        if !self.terms.iter().all(|term| lower.contains(term.as_str())) {
+            return false;
+        }
+        if let Some(glob) = &self.glob {
+            if !glob.is_match(if self.glob_path { relative } else { name }) {
+                return false;
+            }
+        }
+        self.regex.as_ref().is_none_or(|regex| regex.is_match(name))
+    }
+}
+
+// GET /api/search
+pub async fn search_handler(
+    State(state): State<Arc<AppState>>,
+    user: CurrentUser,
+    Query(params): Query<SearchParams>,
+) -> Result<Response, AppError> {
+    user.require(Permission::Read)?;
+
+    let matcher = Matcher::new(&params)?;
+    let start = params.path.unwrap_or_default();
+    let full_path = state.resolver.resolve_dir(&start).await?;
+    let start = paths::display_path(&paths::normalize(&start)?);
+    let depth = params.depth.unwrap_or(DEFAULT_DEPTH).clamp(1, MAX_DEPTH);
+    let limit = params.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+
+    let (tx, rx) = mpsc::channel::<SearchEvent>(64);
+
+    tokio::spawn(async move {
+        let prefix = if start.is_empty() { String::new() } else { format!("/{}", start) };
+        let mut walker = match TreeWalker::new(&full_path, "", &state.resolver).await {
+            Ok(walker) => walker.skip_hidden().max_depth(depth),
+            Err(e) => {
+                let _ = tx.send(SearchEvent::Error { message: AppError::from(e).message() }).await;
+                return;
+            }
+        };
+
+        let (mut matches, mut scanned) = (0, 0);
+        let outcome = loop {
+            // El cliente se ha ido: no seguir recorriendo
+            if tx.is_closed() {
+                tracing::debug!("Búsqueda cancelada por el cliente");
+                return;
+            }
+            let entry = match walker.next().await {
+                Ok(Some(entry)) => entry,
+                Ok(None) => break SearchEvent::Done { matches, scanned, truncated: false },
+                Err(e) => {
+                    tracing::warn!("Búsqueda interrumpida: {:?}", e);
+                    break SearchEvent::Error { message: AppError::from(e).message() };
+                }
+            };
+            scanned += 1;
+
+            // `entry.name` es `/sub/archivo`, relativo a la carpeta de inicio
+            let relative = entry.name.trim_start_matches('/');
+            let name = relative.rsplit('/').next().unwrap_or(relative);
+            let is_dir = entry.metadata.is_dir();
+            if !matcher.matches(name, relative, is_dir) {
+                continue;
+            }
+
+            let event = SearchEvent::Match {
+                path: format!("{}{}", prefix, entry.name),
+                name: name.to_string(),
+                is_dir,
+                size: if is_dir { 0 } else { entry.metadata.len() },
+                modified: entry.metadata.modified().ok().and_then(unix_seconds),
+            };
+            if tx.send(event).await.is_err() {
+                return;
+            }
+            matches += 1;
+            if matches >= limit {
+                break SearchEvent::Done { matches, scanned, truncated: true };
+            }
+        };
+        let _ = tx.send(outcome).await;
+    });
+
+    let lines = futures::stream::unfold(rx, |mut rx| async move {
+        let event = rx.recv().await?;
+        let mut line = serde_json::to_vec(&event).unwrap_or_default();
+        line.push(b'\n');
+        Some((Ok::<_, std::convert::Infallible>(line), rx))
+    });
+
+    Ok(([(header::CONTENT_TYPE, "application/x-ndjson")], Body::from_stream(lines)).into_response())
+}
+
+#[cfg(feature = "content-search")]
+#[derive(Deserialize)]
+pub struct ContentParams {
+    q: String,
+    limit: Option<usize>,
+}
+
+#[cfg(feature = "content-search")]
+#[derive(Serialize)]
+pub struct ContentResults {
+    /// El indexador está recorriendo la carpeta: puede faltar algo todavía
+    indexing: bool,
+    documents: u64,
+    results: Vec<crate::content_index::ContentHit>,
+}
+
+// GET /api/search/content
+#[cfg(feature = "content-search")]
+pub async fn content_handler(
+    State(state): State<Arc<AppState>>,
+    user: CurrentUser,
+    Query(params): Query<ContentParams>,
+) -> Result<axum::Json<ContentResults>, AppError> {
+    user.require(Permission::Read)?;
+    let index = state.content_index.clone().ok_or(AppError::NotFound)?;
+    if params.q.trim().is_empty() {
+        return Err(AppError::BadRequest("Indica q"));
+    }
+    let limit = params.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+
+    let (indexing, documents) = (index.is_indexing(), index.documents());
+    let results = tokio::task::spawn_blocking(move || index.search(&params.q, limit))
+        .await
+        .map_err(anyhow::Error::from)??;
+    Ok(axum::Json(ContentResults { indexing, documents, results }))
+}
@@ -0,0 +1,86 @@
+// Login por formulario y logout. Sólo tienen efecto con usuarios configurados;
+// sin autenticación, ambas rutas llevan directamente a la lista.
+
+use crate::{auth, error::AppError, sessions, AppState};
+use axum::{
+    extract::{ConnectInfo, State},
+    http::{header, HeaderMap},
+    response::{Html, IntoResponse, Redirect, Response},
+    Form,
+};
+use serde::Deserialize;
+use std::{net::SocketAddr, sync::Arc};
+
+#[derive(Deserialize)]
+pub struct LoginForm {
+    username: String,
+    password: String,
+    next: Option<String>,
+}
+
+/// Sólo redirigimos a rutas locales (`/algo`), nunca a otro dominio (`//evil`).
+fn safe_next(next: Option<&str>) -> &str {
+    match next {
+        Some(next) if next.starts_with('/') && !next.starts_with("//") && !next.starts_with("/\\") => next,
+        _ => "/",
+    }
+}
+
+// GET /login -> formulario (login.html del tema)
+pub async fn login_page(State(state): State<Arc<AppState>>) -> Result<Response, AppError> {
+    if state.auth.is_none() {
+        return Ok(Redirect::to("/").into_response());
+    }
+    let page = super::assets::load_page(&state, "login.html").await?;
+    Ok(([(header::CACHE_CONTROL, "no-store")], Html(page)).into_response())
+}
+
+// POST /login -> crea la sesión y vuelve a donde estaba el usuario
+pub async fn login_handler(
+    State(state): State<Arc<AppState>>,
+    connect: Option<ConnectInfo<SocketAddr>>,
+    Form(form): Form<LoginForm>,
+) -> Response {
+    let Some(auth) = &state.auth else {
+        return Redirect::to("/").into_response();
+    };
+    let next = safe_next(form.next.as_deref());
+
+    let client = auth::client_key(connect.map(|info| info.0));
+    match auth.verify(&client, &form.username, &form.password).await {
+        Ok(Some(user)) => {
+            tracing::info!("Inicio de sesión: {}", user.username);
+            let cookie = auth.sessions.create(&user.username);
+            (
+                [(header::SET_COOKIE, auth.sessions.set_cookie(&cookie))],
+                Redirect::to(next),
+            ).into_response()
+        }
+        result => {
+            let error = match result {
+                Err(AppError::TooManyRequests) => "wait",
+                Err(e) => return e.into_response(),
+                _ => "1",
+            };
+            tracing::warn!("Inicio de sesión fallido para: {}", form.username);
+            let query = serde_urlencoded::to_string([("error", error), ("next", next)]).unwrap_or_default();
+            Redirect::to(&format!("/login?{}", query)).into_response()
+        }
+    }
+}
+
+// GET|POST /logout -> borra la sesión y la cookie
+pub async fn logout_handler(State(state): State<Arc<AppState>>, headers: HeaderMap) -> Response {
+    let Some(auth) = &state.auth else {
+        return Redirect::to("/").into_response();
+    };
+
+    if let Some(cookie) = sessions::cookie_value(&headers) {
+        auth.sessions.remove(cookie);
+    }
+
+    (
+        [(header::SET_COOKIE, auth.sessions.clear_cookie())],
+        Redirect::to("/login"),
+    ).into_response()
+}
@@ -0,0 +1,186 @@
+// Enlaces compartidos: la API para crearlos/listarlos/revocarlos y la ruta
+// pública `/s/<token>`, que no pasa por la autenticación del servidor.
+
+use crate::{
+    auth::CurrentUser,
+    error::AppError,
+    shares::{NewShare, Share, ShareStore},
+    users::{self, Permission},
+    utils::{archiver::ArchiveFormat, paths},
+    AppState,
+};
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap, Method, StatusCode},
+    response::{Html, IntoResponse, Redirect, Response},
+    Form, Json,
+};
+use serde::{Deserialize, Serialize};
+use std::{sync::Arc, time::Duration};
+
+#[derive(Deserialize)]
+pub struct CreateShare {
+    /// Ruta relativa a la carpeta compartida
+    path: String,
+    expires_in_hours: Option<u64>,
+    max_downloads: Option<u32>,
+    password: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ShareInfo {
+    id: String,
+    url: String,
+    path: String,
+    created_at: u64,
+    expires_at: Option<u64>,
+    max_downloads: Option<u32>,
+    downloads: u32,
+    protected: bool,
+    active: bool,
+}
+
+impl ShareInfo {
+    fn new(token: &str, share: &Share, relative: &std::path::Path) -> Self {
+        Self {
+            id: share.id.clone(),
+            url: format!("/s/{}", token),
+            path: format!("/{}", relative.to_string_lossy()),
+            created_at: share.created_at,
+            expires_at: share.expires_at,
+            max_downloads: share.max_downloads,
+            downloads: share.downloads,
+            protected: share.password_hash.is_some(),
+            active: share.is_active(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct SharePassword {
+    password: String,
+}
+
+fn store(state: &AppState) -> Result<&ShareStore, AppError> {
+    state.shares.as_deref().ok_or(AppError::NotFound)
+}
+
+// POST /api/shares
+pub async fn create_handler(
+    State(state): State<Arc<AppState>>,
+    user: CurrentUser,
+    Json(req): Json<CreateShare>,
+) -> Result<Response, AppError> {
+    user.require(Permission::Share)?;
+    let store = store(&state)?;
+
+    let full_path = state.resolver.resolve(&req.path).await?;
+    let relative = paths::display_path(&paths::normalize(&req.path)?);
+
+    let (token, share) = store.create(NewShare {
+        path: full_path,
+        expires_in: req.expires_in_hours.map(|h| Duration::from_secs(h * 60 * 60)),
+        max_downloads: req.max_downloads,
+        password: req.password.filter(|p| !p.is_empty()),
+        created_by: user.username.clone(),
+    })?;
+
+    tracing::info!("Enlace compartido creado para /{}", relative);
+    let info = ShareInfo::new(&token, &share, std::path::Path::new(&relative));
+    Ok((StatusCode::CREATED, Json(info)).into_response())
+}
+
+// GET /api/shares -> enlaces de esta carpeta compartida
+pub async fn list_handler(
+    State(state): State<Arc<AppState>>,
+    user: CurrentUser,
+) -> Result<Json<Vec<ShareInfo>>, AppError> {
+    user.require(Permission::Share)?;
+    let shares = store(&state)?.list()?;
+
+    let infos = shares
+        .iter()
+        .filter_map(|(token, share)| {
+            let relative = share.relative_to(&state.base_path)?;
+            Some(ShareInfo::new(token, share, relative))
+        })
+        .collect();
+    Ok(Json(infos))
+}
+
+// DELETE /api/shares/:id
+pub async fn revoke_handler(
+    State(state): State<Arc<AppState>>,
+    user: CurrentUser,
+    Path(id): Path<String>,
+) -> Result<StatusCode, AppError> {
+    user.require(Permission::Share)?;
+    if store(&state)?.revoke(&id)? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(AppError::NotFound)
+    }
+}
+
+// GET|HEAD /s/:token -> descarga (o formulario si tiene contraseña)
+pub async fn open_handler(
+    State(state): State<Arc<AppState>>,
+    Path(token): Path<String>,
+    method: Method,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let share = find_active(&state, &token)?;
+    if share.password_hash.is_some() {
+        let page = super::assets::load_page(&state, "share.html").await?;
+        return Ok(([(header::CACHE_CONTROL, "no-store")], Html(page)).into_response());
+    }
+    serve_share(&state, share, method, headers).await
+}
+
+// POST /s/:token -> descarga tras comprobar la contraseña
+pub async fn unlock_handler(
+    State(state): State<Arc<AppState>>,
+    Path(token): Path<String>,
+    headers: HeaderMap,
+    Form(form): Form<SharePassword>,
+) -> Result<Response, AppError> {
+    let share = find_active(&state, &token)?;
+    // Cada enlace acumula sus propios fallos, vengan de donde vengan
+    if state.share_failures.check(&share.id).is_err() {
+        return Ok(Redirect::to(&format!("/s/{}?error=wait", token)).into_response());
+    }
+    let checked = share.clone();
+    if !users::verify_blocking(move || checked.check_password(&form.password)).await? {
+        state.share_failures.failure(&share.id);
+        return Ok(Redirect::to(&format!("/s/{}?error=1", token)).into_response());
+    }
+    state.share_failures.success(&share.id);
+    serve_share(&state, share, Method::POST, headers).await
+}
+
+fn find_active(state: &AppState, token: &str) -> Result<Share, AppError> {
+    match store(state)?.find(token)? {
+        Some(share) if share.is_active() => Ok(share),
+        Some(_) => Err(AppError::Gone),
+        None => Err(AppError::NotFound),
+    }
+}
+
+async fn serve_share(state: &AppState, share: Share, method: Method, headers: HeaderMap) -> Result<Response, AppError> {
+    // El enlace sólo vale para la carpeta compartida de este servidor, con su
+    // misma política de enlaces simbólicos.
+    let relative = share.relative_to(&state.base_path).ok_or(AppError::NotFound)?;
+    let full_path = state.resolver.resolve(&relative.to_string_lossy()).await?;
+
+    let response = super::download::serve_path(state, full_path, ArchiveFormat::Store, None, headers).await?;
+
+    // Cada GET servido cuenta como una descarga, también los que llevan
+    // `Range`: el cliente elige el rango, así que no sirve para distinguir
+    // reanudaciones. No gastan usos los HEAD ni lo que no se llega a servir
+    // (errores, 416...). Se cuenta antes de enviar el cuerpo, así que si otro
+    // agotó el enlace entretanto esta respuesta se descarta.
+    if method != Method::HEAD && response.status().is_success() && !store(state)?.record_download(&share.id)? {
+        return Err(AppError::Gone);
+    }
+    Ok(response)
+}
@@ -0,0 +1,87 @@
+// API de la papelera: listar, restaurar y purgar lo borrado desde la web.
+
+use crate::{auth::CurrentUser, error::AppError, trash::{Trash, TrashEntry}, users::Permission, utils::paths, AppState};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+#[derive(Deserialize)]
+pub struct RestoreRequest {
+    /// Destino alternativo si la ruta original está ocupada
+    to: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct RestoreResult {
+    path: String,
+}
+
+#[derive(Serialize)]
+pub struct PurgeResult {
+    purged: usize,
+}
+
+fn trash(state: &AppState) -> Result<&Trash, AppError> {
+    state.trash.as_deref().ok_or(AppError::NotFound)
+}
+
+// GET /api/trash
+pub async fn list_handler(
+    State(state): State<Arc<AppState>>,
+    user: CurrentUser,
+) -> Result<Json<Vec<TrashEntry>>, AppError> {
+    user.require(Permission::Delete)?;
+    Ok(Json(trash(&state)?.list().await?))
+}
+
+// POST /api/trash/:id/restore [{ "to": "/otra/ruta" }]
+pub async fn restore_handler(
+    State(state): State<Arc<AppState>>,
+    user: CurrentUser,
+    Path(id): Path<String>,
+    body: Option<Json<RestoreRequest>>,
+) -> Result<Json<RestoreResult>, AppError> {
+    user.require(Permission::Delete)?;
+    let trash = trash(&state)?;
+    let entry = trash.get(&id).await?.ok_or(AppError::NotFound)?;
+
+    let target = body.and_then(|Json(req)| req.to).unwrap_or(entry.original_path);
+    let relative = paths::normalize(&target)?;
+    let parent = relative.parent().map(paths::display_path).unwrap_or_default();
+
+    // La carpeta original pudo borrarse después: se vuelve a crear
+    state.resolver.create_dir_all(&parent).await?;
+    let dest = state.resolver.resolve_new(&target).await?;
+    trash.restore(&id, &dest).await?;
+
+    Ok(Json(RestoreResult { path: format!("/{}", paths::display_path(&relative)) }))
+}
+
+// DELETE /api/trash/:id
+pub async fn purge_handler(
+    State(state): State<Arc<AppState>>,
+    user: CurrentUser,
+    Path(id): Path<String>,
+) -> Result<StatusCode, AppError> {
+    user.require(Permission::Delete)?;
+    if trash(&state)?.purge(&id).await? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(AppError::NotFound)
+    }
+}
+
+// DELETE /api/trash -> vaciar la papelera
+pub async fn empty_handler(
+    State(state): State<Arc<AppState>>,
+    user: CurrentUser,
+) -> Result<Json<PurgeResult>, AppError> {
+    user.require(Permission::Delete)?;
+    let purged = trash(&state)?.purge_all().await?;
+    tracing::info!("Papelera vaciada: {} elementos", purged);
+    Ok(Json(PurgeResult { purged }))
+}
@@ -0,0 +1,471 @@
+// Subidas reanudables siguiendo el protocolo tus 1.0.0 (core + creation,
+// expiration y termination). https://tus.io/protocols/resumable-upload
+//
+// Cada subida vive en `<base_path>/.local-share-uploads/` como dos archivos:
+// `<id>.part` con los bytes recibidos (su tamaño ES el offset) y `<id>.json`
+// con los metadatos. Al completarse, el `.part` se renombra de forma atómica
+// a su destino final; al estar en el mismo sistema de archivos no hay copia.
+
+use crate::{
+    auth::CurrentUser,
+    error::AppError,
+    users::Permission,
+    utils::{conflict::{ConflictPolicy, Outcome, Placed, Placement}, paths},
+    AppState,
+};
+use axum::{
+    body::Body,
+    extract::{Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::Response,
+};
+use base64::{engine::general_purpose, Engine as _};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashSet,
+    path::{Path as FsPath, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::io::AsyncWriteExt;
+
+const TUS_VERSION: &str = "1.0.0";
+const TUS_EXTENSIONS: &str = "creation,expiration,termination";
+const UPLOAD_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+const SWEEP_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Serialize, Deserialize)]
+struct UploadInfo {
+    length: u64,
+    file_name: String,
+    target: String, // carpeta destino, relativa a base_path
+    expires_at: u64, // segundos UNIX
+    /// Metadato `on_conflict`; si falta, la política del servidor
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    on_conflict: Option<ConflictPolicy>,
+    /// Quién la creó (para la papelera si se sobrescribe algo)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    user: Option<String>,
+}
+
+/// Subidas con un PATCH en curso; el protocolo no admite escrituras concurrentes.
+#[derive(Default)]
+pub struct UploadLocks(Mutex<HashSet<String>>);
+
+struct LockGuard<'a> {
+    locks: &'a UploadLocks,
+    id: String,
+}
+
+impl UploadLocks {
+    fn try_lock(&self, id: &str) -> Option<LockGuard<'_>> {
+        let mut set = self.0.lock().expect("Lock poisoned");
+        set.insert(id.to_string()).then(|| LockGuard { locks: self, id: id.to_string() })
+    }
+}
+
+impl Drop for LockGuard<'_> {
+    fn drop(&mut self) {
+        self.locks.0.lock().expect("Lock poisoned").remove(&self.id);
+    }
+}
+
+fn staging_dir(state: &AppState) -> PathBuf {
+    state.base_path.join(paths::STAGING_DIR)
+}
+
+fn upload_paths(state: &AppState, id: &str) -> (PathBuf, PathBuf) {
+    let dir = staging_dir(state);
+    (dir.join(format!("{}.part", id)), dir.join(format!("{}.json", id)))
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+fn new_upload_id() -> String {
+    let bytes: [u8; 16] = rand::random();
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Los IDs son hexadecimales generados por nosotros; cualquier otra cosa no existe.
+fn valid_id(id: &str) -> bool {
+    id.len() == 32 && id.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+fn tus_response(status: StatusCode) -> axum::http::response::Builder {
+    Response::builder()
+        .status(status)
+        .header("Tus-Resumable", TUS_VERSION)
+}
+
+fn tus_status(status: StatusCode) -> Response {
+    tus_response(status).body(Body::empty()).unwrap()
+}
+
+/// Todas las peticiones (salvo OPTIONS) deben declarar la versión del protocolo.
+fn check_version(headers: &HeaderMap) -> Option<Response> {
+    match headers.get("Tus-Resumable").and_then(|v| v.to_str().ok()) {
+        Some(TUS_VERSION) => None,
+        _ => Some(
+            tus_response(StatusCode::PRECONDITION_FAILED)
+                .header("Tus-Version", TUS_VERSION)
+                .body(Body::empty())
+                .unwrap(),
+        ),
+    }
+}
+
+fn header_u64(headers: &HeaderMap, name: &str) -> Option<u64> {
+    headers.get(name)?.to_str().ok()?.trim().parse().ok()
+}
+
+/// `Upload-Metadata: filename d29ybGQ=,path Lw==` -> pares clave/valor decodificados.
+fn parse_metadata(headers: &HeaderMap) -> Vec<(String, String)> {
+    let Some(raw) = headers.get("Upload-Metadata").and_then(|v| v.to_str().ok()) else {
+        return Vec::new();
+    };
+
+    raw.split(',')
+        .filter_map(|pair| {
+            let mut parts = pair.trim().splitn(2, ' ');
+            let key = parts.next()?.to_string();
+            let value = match parts.next() {
+                Some(v) => String::from_utf8(general_purpose::STANDARD.decode(v.trim()).ok()?).ok()?,
+                None => String::new(),
+            };
+            Some((key, value))
+        })
+        .collect()
+}
+
+async fn read_info(path: &FsPath) -> Result<Option<UploadInfo>, AppError> {
+    match tokio::fs::read(path).await {
+        Ok(data) => Ok(serde_json::from_slice(&data).ok()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn expires_header(info: &UploadInfo) -> String {
+    httpdate::fmt_http_date(UNIX_EPOCH + Duration::from_secs(info.expires_at))
+}
+
+// OPTIONS /api/tus
+pub async fn options_handler(State(state): State<Arc<AppState>>) -> Response {
+    tus_response(StatusCode::NO_CONTENT)
+        .header("Tus-Version", TUS_VERSION)
+        .header("Tus-Extension", TUS_EXTENSIONS)
+        .header("Tus-Max-Size", state.max_upload_size)
+        .body(Body::empty())
+        .unwrap()
+}
+
+// POST /api/tus -> crea la subida y devuelve su URL en `Location`
+pub async fn create_handler(
+    State(state): State<Arc<AppState>>,
+    user: CurrentUser,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    user.require(Permission::Upload)?;
+    if let Some(res) = check_version(&headers) {
+        return Ok(res);
+    }
+
+    let Some(length) = header_u64(&headers, "Upload-Length") else {
+        // Upload-Defer-Length no está soportado
+        return Ok(tus_status(StatusCode::BAD_REQUEST));
+    };
+    if length > state.max_upload_size {
+        return Ok(tus_status(StatusCode::PAYLOAD_TOO_LARGE));
+    }
+
+    let metadata = parse_metadata(&headers);
+    let meta = |key: &str| metadata.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str());
+
+    // Misma resolución que `upload_handler`; guardamos la forma normalizada
+    let target = meta("path").unwrap_or("/");
+    state.resolver.resolve_dir(target).await?;
+
+    // `filename` puede traer subcarpetas (subida de carpetas); se crean al terminar
+    let (target, file_name) = paths::upload_destination(target, meta("filename").unwrap_or_default())?;
+
+    let on_conflict = match meta("on_conflict") {
+        Some(value) => Some(ConflictPolicy::parse(value).ok_or(AppError::BadRequest("on_conflict inválido"))?),
+        None => None,
+    };
+    if on_conflict == Some(ConflictPolicy::Overwrite) {
+        user.require(Permission::Delete)?;
+    }
+    // Con `fail` mejor avisar antes de recibir nada (se vuelve a comprobar al final)
+    let policy = on_conflict.unwrap_or(state.on_conflict);
+    if policy == ConflictPolicy::Fail && tokio::fs::symlink_metadata(state.base_path.join(&target).join(&file_name)).await.is_ok() {
+        return Err(AppError::Conflict);
+    }
+
+    let info = UploadInfo {
+        length,
+        file_name,
+        target,
+        expires_at: now_secs() + UPLOAD_TTL.as_secs(),
+        on_conflict,
+        user: user.username.clone(),
+    };
+
+    let id = new_upload_id();
+    let (part_path, info_path) = upload_paths(&state, &id);
+    tokio::fs::create_dir_all(staging_dir(&state)).await?;
+    tokio::fs::File::create(&part_path).await?;
+    tokio::fs::write(&info_path, serde_json::to_vec(&info).map_err(anyhow::Error::new)?).await?;
+
+    tracing::info!("Subida reanudable creada: {} ({} bytes)", id, length);
+
+    let response = tus_response(StatusCode::CREATED)
+        .header(header::LOCATION, format!("/api/tus/{}", id))
+        .header("Upload-Expires", expires_header(&info));
+
+    // Un archivo vacío ya está completo
+    let response = if length == 0 {
+        let placed = finalize_upload(&state, &id, &info).await?;
+        with_result(response, &placed)
+    } else {
+        response
+    };
+    Ok(response.body(Body::empty()).unwrap())
+}
+
+// HEAD /api/tus/:id -> offset actual
+pub async fn head_handler(
+    State(state): State<Arc<AppState>>,
+    user: CurrentUser,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    user.require(Permission::Upload)?;
+    if let Some(res) = check_version(&headers) {
+        return Ok(res);
+    }
+    if !valid_id(&id) {
+        return Ok(tus_status(StatusCode::NOT_FOUND));
+    }
+
+    let (part_path, info_path) = upload_paths(&state, &id);
+    let Some(info) = read_info(&info_path).await? else {
+        return Ok(tus_status(StatusCode::NOT_FOUND));
+    };
+    let offset = tokio::fs::metadata(&part_path).await?.len();
+
+    Ok(tus_response(StatusCode::OK)
+        .header("Upload-Offset", offset)
+        .header("Upload-Length", info.length)
+        .header("Upload-Expires", expires_header(&info))
+        .header(header::CACHE_CONTROL, "no-store")
+        .body(Body::empty())
+        .unwrap())
+}
+
+// PATCH /api/tus/:id -> añade bytes a partir de `Upload-Offset`
+pub async fn patch_handler(
+    State(state): State<Arc<AppState>>,
+    user: CurrentUser,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<Response, AppError> {
+    user.require(Permission::Upload)?;
+    if let Some(res) = check_version(&headers) {
+        return Ok(res);
+    }
+    if headers.get(header::CONTENT_TYPE).and_then(|v| v.to_str().ok())
+        != Some("application/offset+octet-stream")
+    {
+        return Ok(tus_status(StatusCode::UNSUPPORTED_MEDIA_TYPE));
+    }
+    let Some(offset) = header_u64(&headers, "Upload-Offset") else {
+        return Ok(tus_status(StatusCode::BAD_REQUEST));
+    };
+    if !valid_id(&id) {
+        return Ok(tus_status(StatusCode::NOT_FOUND));
+    }
+
+    let Some(_guard) = state.tus_locks.try_lock(&id) else {
+        return Ok(tus_status(StatusCode::CONFLICT));
+    };
+
+    let (part_path, info_path) = upload_paths(&state, &id);
+    let Some(mut info) = read_info(&info_path).await? else {
+        return Ok(tus_status(StatusCode::NOT_FOUND));
+    };
+    if info.expires_at <= now_secs() {
+        return Ok(tus_status(StatusCode::GONE));
+    }
+
+    let current = tokio::fs::metadata(&part_path).await?.len();
+    if offset != current {
+        return Ok(tus_status(StatusCode::CONFLICT));
+    }
+
+    // Escribimos por chunks; si el cliente se desconecta, lo recibido hasta
+    // ese momento queda en disco y será el offset desde el que reanudar.
+    let mut file = tokio::fs::OpenOptions::new().append(true).open(&part_path).await?;
+    let mut written = 0u64;
+    let mut stream = body.into_data_stream();
+    let mut outcome = Ok(());
+
+    while let Some(chunk) = stream.next().await {
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                tracing::warn!("Subida {} interrumpida en {} bytes: {}", id, current + written, e);
+                outcome = Err(tus_status(StatusCode::BAD_REQUEST));
+                break;
+            }
+        };
+        if current + written + chunk.len() as u64 > info.length {
+            outcome = Err(tus_status(StatusCode::PAYLOAD_TOO_LARGE));
+            break;
+        }
+        file.write_all(&chunk).await?;
+        written += chunk.len() as u64;
+    }
+
+    file.flush().await?;
+    file.sync_data().await?;
+    drop(file);
+
+    // Mientras avance, la subida no caduca: el plazo cuenta desde el último PATCH
+    let new_offset = current + written;
+    if written > 0 && new_offset < info.length {
+        info.expires_at = now_secs() + UPLOAD_TTL.as_secs();
+        tokio::fs::write(&info_path, serde_json::to_vec(&info).map_err(anyhow::Error::new)?).await?;
+    }
+
+    if let Err(res) = outcome {
+        return Ok(res);
+    }
+
+    let response = tus_response(StatusCode::NO_CONTENT)
+        .header("Upload-Offset", new_offset)
+        .header("Upload-Expires", expires_header(&info));
+
+    let response = if new_offset == info.length {
+        let placed = finalize_upload(&state, &id, &info).await?;
+        with_result(response, &placed)
+    } else {
+        response
+    };
+    Ok(response.body(Body::empty()).unwrap())
+}
+
+// DELETE /api/tus/:id -> cancela la subida y borra lo recibido
+pub async fn terminate_handler(
+    State(state): State<Arc<AppState>>,
+    user: CurrentUser,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    user.require(Permission::Upload)?;
+    if let Some(res) = check_version(&headers) {
+        return Ok(res);
+    }
+    if !valid_id(&id) {
+        return Ok(tus_status(StatusCode::NOT_FOUND));
+    }
+    let Some(_guard) = state.tus_locks.try_lock(&id) else {
+        return Ok(tus_status(StatusCode::CONFLICT));
+    };
+
+    let (part_path, info_path) = upload_paths(&state, &id);
+    if read_info(&info_path).await?.is_none() {
+        return Ok(tus_status(StatusCode::NOT_FOUND));
+    }
+    remove_upload(&part_path, &info_path).await;
+
+    Ok(tus_status(StatusCode::NO_CONTENT))
+}
+
+/// Mueve el `.part` terminado a su carpeta destino (rename atómico), aplicando
+/// la política de conflictos. Si se omite, lo recibido se descarta.
+async fn finalize_upload(state: &AppState, id: &str, info: &UploadInfo) -> Result<Placed, AppError> {
+    let (part_path, info_path) = upload_paths(state, id);
+    // La carpeta pudo cambiar (o volverse un enlace) desde que se creó la subida;
+    // las subcarpetas de una subida de carpeta se crean ahora
+    let target_dir = state.resolver.create_dir_all(&info.target).await?;
+
+    let placement = placement(state, info);
+    let placed = placement.place(&part_path, &target_dir, &info.file_name).await?;
+    if placed.outcome == Outcome::Skipped {
+        remove_upload(&part_path, &info_path).await;
+        tracing::info!("Subida reanudable omitida, ya existe: {:?}", placed.path);
+    } else {
+        let _ = tokio::fs::remove_file(&info_path).await;
+        tracing::info!("Archivo subido (reanudable, {}): {:?}", placed.outcome.as_str(), placed.path);
+    }
+    Ok(placed)
+}
+
+fn placement<'a>(state: &'a AppState, info: &'a UploadInfo) -> Placement<'a> {
+    Placement {
+        policy: info.on_conflict.unwrap_or(state.on_conflict),
+        trash: state.trash.as_deref(),
+        base_path: &state.base_path,
+        user: info.user.as_deref(),
+    }
+}
+
+/// Al completarse: `Upload-Status` (created, renamed...) y la ruta final en
+/// `Upload-Stored-Path`, en base64 como los valores de `Upload-Metadata`.
+fn with_result(response: axum::http::response::Builder, placed: &Placed) -> axum::http::response::Builder {
+    response
+        .header("Upload-Status", placed.outcome.as_str())
+        .header("Upload-Stored-Path", general_purpose::STANDARD.encode(&placed.relative))
+}
+
+async fn remove_upload(part_path: &FsPath, info_path: &FsPath) {
+    let _ = tokio::fs::remove_file(part_path).await;
+    let _ = tokio::fs::remove_file(info_path).await;
+}
+
+/// Borra periódicamente las subidas caducadas de la carpeta de staging.
+pub fn spawn_upload_sweeper(base_path: PathBuf) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let staging = base_path.join(paths::STAGING_DIR);
+        let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = sweep_expired(&staging).await {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    tracing::warn!("Error limpiando subidas caducadas: {}", e);
+                }
+            }
+        }
+    })
+}
+
+async fn sweep_expired(staging: &FsPath) -> std::io::Result<()> {
+    let now = now_secs();
+    let mut entries = tokio::fs::read_dir(staging).await?;
+
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("json") {
+            continue;
+        }
+
+        let expired = match tokio::fs::read(&path).await {
+            Ok(data) => serde_json::from_slice::<UploadInfo>(&data)
+                .map(|info| info.expires_at <= now)
+                .unwrap_or(true),
+            Err(_) => false,
+        };
+
+        if expired {
+            tracing::info!("Eliminando subida caducada: {:?}", path);
+            remove_upload(&path.with_extension("part"), &path).await;
+        }
+    }
+
+    Ok(())
+}
+
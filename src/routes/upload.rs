@@ -1,76 +1,236 @@
-use crate::{error::AppError, AppState};
+use super::extract::{self, ArchiveKind, ExtractEvent, PendingArchive};
+use crate::{
+    auth::CurrentUser,
+    error::AppError,
+    users::Permission,
+    utils::{conflict::{ConflictPolicy, Outcome, Placement}, paths},
+    AppState,
+};
 use axum::{
     extract::{Multipart, Query, State},
-    response::IntoResponse,
-    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
 };
+use serde::Serialize;
 use std::sync::Arc;
 
 use tokio::fs::File;
 use tokio::io::AsyncWriteExt;
 
+/// Archivo temporal de una subida en curso. Si la petición falla o el cliente se
+/// desconecta (el future del handler se descarta), se borra al salir de ámbito.
+pub(super) struct TempUpload {
+    pub path: std::path::PathBuf,
+    keep: bool,
+}
+
+impl TempUpload {
+    /// El nombre es de longitud fija (no deriva del archivo subido), así que
+    /// cualquier nombre final que admita el sistema cabe también como temporal.
+    pub fn new(target_dir: &std::path::Path) -> Self {
+        let suffix: u128 = rand::random();
+        let path = target_dir.join(format!(".local-share-{:032x}.upload", suffix));
+        Self { path, keep: false }
+    }
+
+    /// El temporal ya se renombró a su destino final: no hay nada que limpiar.
+    pub fn persist(mut self) {
+        self.keep = true;
+    }
+}
+
+impl Drop for TempUpload {
+    fn drop(&mut self) {
+        if !self.keep {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
 #[derive(serde::Deserialize)]
 pub struct UploadParams {
     path: String,
+    /// Cambia la política del servidor para esta subida
+    on_conflict: Option<ConflictPolicy>,
+    /// Extraer los .zip / .tar / .tar.gz en vez de guardarlos tal cual
+    #[serde(default)]
+    extract: bool,
+}
+
+/// Respuesta de `/upload`: dónde acabó cada archivo.
+#[derive(Serialize)]
+pub struct UploadResult {
+    files: Vec<StoredFile>,
+}
+
+#[derive(Serialize)]
+struct StoredFile {
+    /// Nombre con el que llegó
+    name: String,
+    /// Ruta final relativa a la carpeta compartida (la existente si se omitió)
+    path: String,
+    status: Outcome,
 }
 
 pub async fn upload_handler(
     State(state): State<Arc<AppState>>,
+    user: CurrentUser,
     Query(params): Query<UploadParams>,
     mut multipart: Multipart,
-) -> Result<impl IntoResponse, AppError> {
-    // 1. Saneamiento de ruta base
-    let clean_path = params.path.trim_start_matches('/');
-    if clean_path.contains("..") {
-        return Err(AppError::InvalidPath);
+) -> Result<Response, AppError> {
+    user.require(Permission::Upload)?;
+    // Pedir sobrescribir es borrar lo anterior
+    if params.on_conflict == Some(ConflictPolicy::Overwrite) {
+        user.require(Permission::Delete)?;
     }
 
-    let target_dir = state.base_path.join(clean_path);
-    if !target_dir.exists() {
-        return Err(AppError::NotFound);
+    // 1. Resolver la carpeta destino dentro de la carpeta compartida
+    state.resolver.resolve_dir(&params.path).await?;
+    let target = paths::display_path(&paths::normalize(&params.path)?);
+
+    let policy = params.on_conflict.unwrap_or(state.on_conflict);
+    let placement = placement(&state, &user, policy);
+    let (files, archives) = receive_files(&state, target, &mut multipart, false, params.extract, &placement).await?;
+    if !params.extract {
+        return Ok(Json(UploadResult { files }).into_response());
+    }
+
+    // Con `?extract=true` la respuesta es un stream NDJSON con el progreso
+    let files = files
+        .into_iter()
+        .map(|file| ExtractEvent::File { name: file.name, path: file.path, status: file.status })
+        .collect();
+    Ok(extract::respond(state.clone(), user.username.clone(), policy, files, archives))
+}
+
+fn placement<'a>(state: &'a AppState, user: &'a CurrentUser, policy: ConflictPolicy) -> Placement<'a> {
+    Placement {
+        policy,
+        trash: state.trash.as_deref(),
+        base_path: &state.base_path,
+        user: user.username.as_deref(),
     }
+}
+
+// POST /upload en modo buzón (--drop-box): siempre a la raíz compartida, o a
+// una subcarpeta con el nombre de quien envía si se pidió `--drop-box-subfolders`.
+pub async fn drop_box_handler(
+    State(state): State<Arc<AppState>>,
+    user: CurrentUser,
+    mut multipart: Multipart,
+) -> Result<impl IntoResponse, AppError> {
+    user.require(Permission::Upload)?;
 
+    // En el buzón manda la política del servidor, salvo que sobrescribir
+    // (borrar lo que otro envió) sólo lo puede un usuario con permiso `delete`.
+    // Sin usuarios configurados todos los visitantes son anónimos: renombrar.
+    let may_overwrite = user.username.is_some() && user.require(Permission::Delete).is_ok();
+    let policy = match state.on_conflict {
+        ConflictPolicy::Overwrite if !may_overwrite => ConflictPolicy::Rename,
+        policy => policy,
+    };
+    let placement = placement(&state, &user, policy);
+    let (files, _) = receive_files(&state, String::new(), &mut multipart, state.drop_box_subfolders, false, &placement).await?;
+    Ok(Json(UploadResult { files }))
+}
+
+// GET / en modo buzón: sólo el formulario de subida
+pub async fn drop_box_page(State(state): State<Arc<AppState>>) -> Result<impl IntoResponse, AppError> {
+    let page = super::assets::load_page(&state, "dropbox.html").await?;
+    let config = serde_json::json!({
+        "subfolders": state.drop_box_subfolders,
+        "max_upload_size": state.max_upload_size,
+    });
+    Ok(axum::response::Html(page.replace("__DROP_BOX_CONFIG__", &config.to_string())))
+}
+
+/// Guarda los archivos del multipart en `target` (relativa a la carpeta
+/// compartida). El nombre de cada archivo puede traer subcarpetas, que se crean
+/// si faltan. Con `per_submitter`, un campo de texto `name` previo a los
+/// archivos elige la subcarpeta de destino. Con `extract`, los archivos
+/// comprimidos no se publican: se devuelven aparte para extraerlos.
+async fn receive_files(
+    state: &AppState,
+    mut target: String,
+    multipart: &mut Multipart,
+    per_submitter: bool,
+    extract: bool,
+    placement: &Placement<'_>,
+) -> Result<(Vec<StoredFile>, Vec<PendingArchive>), AppError> {
     // 2. Procesar cada campo del multipart
-    while let Some(field) = multipart.next_field().await.map_err(|e| AppError::InternalServerError(anyhow::anyhow!(e)))? {
+    let mut stored = Vec::new();
+    let mut archives = Vec::new();
+    let mut received: u64 = 0;
+    let mut submitter: Option<String> = None;
+    while let Some(mut field) = multipart.next_field().await? {
         let file_name = if let Some(name) = field.file_name() {
             name.to_string()
         } else {
+            if per_submitter && field.name() == Some("name") {
+                let name = submitter_folder(&field.text().await?).ok_or(AppError::BadRequest("Nombre inválido"))?;
+                state.resolver.create_dir_all(&name).await?;
+                target = name.clone();
+                submitter = Some(name);
+            }
             continue; // Ignorar campos que no son archivos
         };
 
-        // Saneamiento básico del nombre de archivo
-        let file_name = std::path::Path::new(&file_name)
-            .file_name()
-            .and_then(|n| n.to_str())
-            .unwrap_or("uploaded_file");
-
-        // 3. Manejo de colisiones
-        let mut dest_path = target_dir.join(file_name);
-        let mut counter = 1;
-        let file_stem = std::path::Path::new(file_name)
-            .file_stem()
-            .and_then(|s| s.to_str())
-            .unwrap_or(file_name);
-        let extension = std::path::Path::new(file_name)
-            .extension()
-            .and_then(|s| s.to_str())
-            .map(|e| format!(".{}", e))
-            .unwrap_or_default();
-
-        while dest_path.exists() {
-            let new_name = format!("{}({}){}", file_stem, counter, extension);
-            dest_path = target_dir.join(new_name);
-            counter += 1;
+        if per_submitter && submitter.is_none() {
+            return Err(AppError::BadRequest("Falta el nombre antes de los archivos"));
+        }
+
+        // Saneamiento del nombre (y de sus carpetas, si viene de una carpeta
+        // arrastrada) con las mismas reglas que cualquier otra ruta
+        let uploaded_name = file_name;
+        let (dir, file_name) = paths::upload_destination(&target, &uploaded_name)?;
+        let target_dir = state.resolver.create_dir_all(&dir).await?;
+
+        // 3. Escribir por chunks en un temporal oculto junto al destino, de modo que
+        // el archivo final sólo aparece completo y en la misma partición (rename atómico).
+        let temp = TempUpload::new(&target_dir);
+        let mut file = File::create(&temp.path).await?;
+
+        while let Some(chunk) = field.chunk().await? {
+            received += chunk.len() as u64;
+            if received > state.max_upload_size {
+                return Err(AppError::PayloadTooLarge);
+            }
+            file.write_all(&chunk).await?;
+        }
+
+        file.flush().await?;
+        file.sync_all().await?;
+        drop(file);
+
+        if let Some(kind) = ArchiveKind::detect(&file_name).filter(|_| extract) {
+            archives.push(PendingArchive { name: uploaded_name, kind, target: dir, temp });
+            continue;
         }
 
-        // 4. Guardar el archivo
-        let data = field.bytes().await.map_err(|e| AppError::InternalServerError(anyhow::anyhow!(e)))?;
-        
-        let mut file = File::create(&dest_path).await.map_err(|e| AppError::InternalServerError(anyhow::anyhow!(e)))?;
-        file.write_all(&data).await.map_err(|e| AppError::InternalServerError(anyhow::anyhow!(e)))?;
-        
-        tracing::info!("Archivo subido: {:?}", dest_path);
+        // 4. Manejo de colisiones y publicación del archivo
+        let placed = placement.place(&temp.path, &target_dir, &file_name).await?;
+        if placed.outcome == Outcome::Skipped {
+            tracing::info!("Subida omitida, ya existe: {:?}", placed.path);
+        } else {
+            temp.persist();
+            tracing::info!("Archivo subido ({}): {:?}", placed.outcome.as_str(), placed.path);
+        }
+
+        stored.push(StoredFile { name: uploaded_name, path: placed.relative, status: placed.outcome });
     }
 
-    Ok((StatusCode::OK, "Subida completada"))
+    Ok((stored, archives))
+}
+
+/// Nombre de carpeta a partir de lo que escribe quien envía: sólo letras,
+/// dígitos, espacios, `-`, `_` y `.`, sin empezar por punto.
+fn submitter_folder(name: &str) -> Option<String> {
+    let folder: String = name
+        .trim()
+        .chars()
+        .filter(|c| c.is_alphanumeric() || matches!(c, ' ' | '-' | '_' | '.'))
+        .take(64)
+        .collect();
+    let folder = folder.trim().trim_start_matches('.').trim().to_string();
+    (!folder.is_empty()).then_some(folder)
 }
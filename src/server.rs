@@ -1,29 +1,53 @@
 use axum::{middleware, routing::get, Router};
 use axum_server::tls_rustls::RustlsConfig;
 use clap::Parser;
-use std::{net::SocketAddr, path::PathBuf, sync::{Arc, RwLock}};
+use std::{net::SocketAddr, path::PathBuf, sync::Arc};
 
-use crate::{auth, routes};
+use crate::{
+    auth,
+    clipboard::Clipboard,
+    config::{Features, Profile},
+    routes::{self, ExtractLimits},
+    sessions::SessionStore,
+    shares::ShareStore,
+    throttle::FailureThrottle,
+    trash::{spawn_trash_sweeper, Trash},
+    users::{Permission, User, UserCommand, UserTable},
+    utils::{conflict::ConflictPolicy, paths::{PathResolver, SymlinkPolicy}, zip_plan::CrcCache},
+    watcher::DirWatcher,
+};
 
-// Definimos la estructura para los argumentos de la línea de comandos
+// Definimos la estructura para los argumentos de la línea de comandos.
+// Todas las opciones son opcionales: lo que no se indique sale del perfil
+// del archivo de configuración (o de los valores por defecto de `Profile`).
+// Los interruptores aceptan `=false` para anular lo que active el perfil
+// (`--read-only=false`, `--tls=false`).
 #[derive(Parser, Debug, Clone)] // Added Clone
 #[command(author, version, about, long_about = None)]
 pub struct Args {
-    /// La ruta del directorio que quieres compartir
-    #[arg(short, long, default_value = ".")]
-    pub path: PathBuf,
+    /// Archivo de configuración TOML (por defecto ~/.config/local-share/config.toml)
+    #[arg(short = 'c', long)]
+    pub config: Option<PathBuf>,
 
-    /// El puerto donde correrá el servidor
-    #[arg(short = 'P', long, default_value_t = 3000)]
-    pub port: u16,
+    /// Perfil del archivo de configuración a usar
+    #[arg(long)]
+    pub profile: Option<String>,
 
-    /// Tamaño máximo de subida en MB
-    #[arg(short = 'S', long, default_value_t = 10)]
-    pub max_upload_size: u64,
+    /// La ruta del directorio que quieres compartir [default: .]
+    #[arg(short, long)]
+    pub path: Option<PathBuf>,
+
+    /// El puerto donde correrá el servidor [default: 3000]
+    #[arg(short = 'P', long)]
+    pub port: Option<u16>,
+
+    /// Tamaño máximo de subida en MB [default: 10]
+    #[arg(short = 'S', long)]
+    pub max_upload_size: Option<u64>,
 
     /// Habilitar HTTPS (TLS)
-    #[arg(long)]
-    pub tls: bool,
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    pub tls: Option<bool>,
 
     /// Ruta al certificado .pem (opcional)
     #[arg(long)]
@@ -33,40 +57,238 @@ pub struct Args {
     #[arg(long)]
     pub key: Option<PathBuf>,
 
-    /// Usuario para autenticación básica
-    #[arg(short = 'u', long)]
+    /// Usuario para autenticación básica (junto con `--password`)
+    #[arg(short = 'u', long, requires = "password")]
     pub username: Option<String>,
 
-    /// Contraseña para autenticación básica
-    #[arg(short = 'w', long)]
+    /// Contraseña para autenticación básica (junto con `--username`)
+    #[arg(short = 'w', long, requires = "username")]
     pub password: Option<String>,
 
     /// Ruta a una carpeta con archivos CSS/HTML personalizados
     #[arg(long)]
     pub theme: Option<PathBuf>,
 
+    /// Política de enlaces simbólicos [default: within-root]
+    #[arg(long, value_enum)]
+    pub symlinks: Option<SymlinkPolicy>,
+
+    /// Si una subida ya existe: renombrarla, sobrescribir, omitirla o fallar (409) [default: rename]
+    #[arg(long, value_enum)]
+    pub on_conflict: Option<ConflictPolicy>,
+
+    /// Sólo lectura: desactiva las subidas
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    pub read_only: Option<bool>,
+
+    /// Desactivar el portapapeles compartido
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    pub no_clipboard: Option<bool>,
+
+    /// Desactivar la descarga de carpetas como ZIP
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    pub no_zip: Option<bool>,
+
+    /// Borrar directamente, sin pasar por la papelera
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    pub no_trash: Option<bool>,
+
+    /// Indexar el contenido de los archivos para buscar dentro de ellos
+    /// (requiere compilar con `--features content-search`)
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    pub content_search: Option<bool>,
+
+    /// No vigilar la carpeta: los listados no se actualizan solos
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    pub no_live_updates: Option<bool>,
+
+    /// Días que se guarda lo borrado en la papelera, 0 = sin purga automática [default: 30]
+    #[arg(long)]
+    pub trash_days: Option<u64>,
+
+    /// Máximo de entradas al extraer un .zip/.tar subido [default: 10000]
+    #[arg(long)]
+    pub extract_max_entries: Option<u64>,
+
+    /// Máximo descomprimido por archivo extraído, en MB [default: 4096]
+    #[arg(long)]
+    pub extract_max_size: Option<u64>,
+
+    /// Modo buzón: los visitantes sólo pueden subir archivos
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    pub drop_box: Option<bool>,
+
+    /// En modo buzón, guardar cada envío en una subcarpeta con el nombre del remitente
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    pub drop_box_subfolders: Option<bool>,
+
     /// Exportar el tema por defecto a una carpeta y salir
     #[arg(long)]
     pub dump_theme: Option<PathBuf>,
+
+    /// Tabla de usuarios TOML (por defecto ~/.config/local-share/users.toml)
+    #[arg(long, global = true)]
+    pub users_file: Option<PathBuf>,
+
+    /// Permisos de los invitados, separados por comas (ej. `read`)
+    #[arg(long, value_enum, value_delimiter = ',')]
+    pub guest: Option<Vec<Permission>>,
+
+    /// Duración de las sesiones del login web, en horas [default: 12]
+    #[arg(long)]
+    pub session_hours: Option<u64>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(clap::Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Gestionar la tabla de usuarios
+    User {
+        #[command(subcommand)]
+        action: UserCommand,
+    },
+}
+
+impl Args {
+    /// Las opciones de la línea de comandos tienen prioridad sobre el perfil.
+    pub fn apply_to(self, mut profile: Profile) -> anyhow::Result<Profile> {
+        if let Some(path) = self.path {
+            profile.path = path;
+        }
+        if let Some(port) = self.port {
+            profile.port = port;
+        }
+        if let Some(size) = self.max_upload_size {
+            profile.max_upload_size = size;
+        }
+        if let Some(tls) = self.tls {
+            profile.tls = tls;
+        }
+        if self.cert.is_some() {
+            profile.cert = self.cert;
+        }
+        if self.key.is_some() {
+            profile.key = self.key;
+        }
+        if let (Some(username), Some(password)) = (self.username, self.password) {
+            profile.users = vec![User::new(username, &password, Permission::ALL.to_vec())?];
+        }
+        if self.users_file.is_some() {
+            profile.users_file = self.users_file;
+        }
+        if let Some(guest) = self.guest {
+            profile.guest = guest;
+        }
+        if let Some(hours) = self.session_hours {
+            profile.session_hours = hours;
+        }
+        if self.theme.is_some() {
+            profile.theme = self.theme;
+        }
+        if let Some(symlinks) = self.symlinks {
+            profile.symlinks = symlinks;
+        }
+        if let Some(policy) = self.on_conflict {
+            profile.on_conflict = policy;
+        }
+        if let Some(read_only) = self.read_only {
+            profile.features.upload = !read_only;
+        }
+        if let Some(no_clipboard) = self.no_clipboard {
+            profile.features.clipboard = !no_clipboard;
+        }
+        if let Some(no_zip) = self.no_zip {
+            profile.features.zip = !no_zip;
+        }
+        if let Some(no_trash) = self.no_trash {
+            profile.features.trash = !no_trash;
+        }
+        if let Some(content_search) = self.content_search {
+            profile.features.content_search = content_search;
+        }
+        if let Some(no_live_updates) = self.no_live_updates {
+            profile.features.live_updates = !no_live_updates;
+        }
+        if let Some(days) = self.trash_days {
+            profile.trash_days = days;
+        }
+        if let Some(entries) = self.extract_max_entries {
+            profile.extract_max_entries = entries;
+        }
+        if let Some(size) = self.extract_max_size {
+            profile.extract_max_size = size;
+        }
+        if let Some(drop_box) = self.drop_box {
+            profile.drop_box = drop_box;
+        }
+        if let Some(subfolders) = self.drop_box_subfolders {
+            profile.drop_box_subfolders = subfolders;
+        }
+        Ok(profile)
+    }
 }
 
 // Estado compartido
 #[derive(Clone)]
 pub struct AppState {
     pub base_path: PathBuf,
+    pub resolver: PathResolver,
     pub max_upload_size: u64,
-    pub theme_path: Option<PathBuf>,
-    pub clipboard: Arc<RwLock<String>>,
+    pub theme: Option<PathResolver>,
+    /// Historial del portapapeles compartido
+    pub clipboard: Arc<Clipboard>,
+    pub crc_cache: Arc<CrcCache>,
+    pub tus_locks: Arc<routes::UploadLocks>,
+    /// `None` si no hay usuarios configurados
+    pub auth: Option<auth::AuthConfig>,
+    /// `None` si el sistema no tiene carpeta de datos
+    pub shares: Option<Arc<ShareStore>>,
+    /// Contraseñas fallidas por enlace compartido
+    pub share_failures: Arc<FailureThrottle>,
+    pub drop_box_subfolders: bool,
+    /// `None` con `--no-trash`: se borra directamente
+    pub trash: Option<Arc<Trash>>,
+    /// Política por defecto ante nombres repetidos (`?on_conflict=` la cambia)
+    pub on_conflict: ConflictPolicy,
+    /// Topes de `/upload?extract=true`
+    pub extract_limits: ExtractLimits,
+    /// `None` sin `--content-search` o si no se pudo abrir el índice
+    #[cfg(feature = "content-search")]
+    pub content_index: Option<Arc<crate::content_index::ContentIndex>>,
+    /// `None` con `--no-live-updates`, en modo buzón o si no se pudo vigilar la carpeta
+    pub watcher: Option<Arc<DirWatcher>>,
+    pub features: Features,
+}
+
+#[cfg(feature = "content-search")]
+fn open_content_index(base_path: &std::path::Path, symlinks: SymlinkPolicy) -> Option<Arc<crate::content_index::ContentIndex>> {
+    let Some(dir) = crate::content_index::default_dir(base_path) else {
+        tracing::warn!("Sin carpeta de datos: la búsqueda de contenido queda desactivada");
+        return None;
+    };
+    let resolver = PathResolver::new(base_path.to_path_buf(), symlinks);
+    match crate::content_index::ContentIndex::open(dir.clone(), base_path.to_path_buf(), resolver) {
+        Ok(index) => {
+            tracing::info!("Índice de contenido en {:?}", dir);
+            Some(Arc::new(index))
+        }
+        Err(e) => {
+            tracing::error!("No se pudo abrir el índice de contenido: {:#}", e);
+            None
+        }
+    }
 }
 
-async fn get_tls_config(args: &Args) -> Option<RustlsConfig> {
-    if !args.tls {
+async fn get_tls_config(profile: &Profile) -> Option<RustlsConfig> {
+    if !profile.tls {
         return None;
     }
 
-    if let (Some(cert), Some(key)) = (&args.cert, &args.key) {
+    if let (Some(cert), Some(key)) = (&profile.cert, &profile.key) {
         match RustlsConfig::from_pem_file(cert, key).await {
-            Ok(config) => return Some(config),
+            Ok(config) => Some(config),
             Err(e) => {
                 eprintln!("Error cargando certificados: {}", e);
                 std::process::exit(1);
@@ -84,47 +306,138 @@ async fn get_tls_config(args: &Args) -> Option<RustlsConfig> {
             key_pem.into_bytes(),
         ).await.unwrap();
         
-        return Some(config);
+        Some(config)
     }
 }
 
-pub async fn start_server(args: Args) {
+pub async fn start_server(profile: Profile) {
     // Validamos que la ruta exista antes de arrancar
-    if !args.path.exists() || !args.path.is_dir() {
-        eprintln!("Error: La ruta especificada no existe o no es un directorio: {:?}", args.path);
+    if !profile.path.exists() || !profile.path.is_dir() {
+        eprintln!("Error: La ruta especificada no existe o no es un directorio: {:?}", profile.path);
         // In GUI mode, we might want to return an error instead of exiting process, 
         // but for now keeping it simple.
         std::process::exit(1); 
     }
 
     // Canonicalizamos la ruta
-    let base_path = args.path.canonicalize().expect("No se pudo resolver la ruta absoluta");
+    let base_path = profile.path.canonicalize().expect("No se pudo resolver la ruta absoluta");
+
+    // El tema se sirve con las mismas garantías de contención que la carpeta compartida
+    let theme = match &profile.theme {
+        Some(theme_dir) => match theme_dir.canonicalize() {
+            Ok(theme_dir) if theme_dir.is_dir() => Some(PathResolver::new(theme_dir, SymlinkPolicy::WithinRoot)),
+            _ => {
+                eprintln!("Error: La carpeta del tema no existe o no es un directorio: {:?}", theme_dir);
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
     
+    // Usuarios del perfil + tabla de usuarios
+    let mut users = UserTable { users: profile.users.clone() };
+    match UserTable::load(profile.users_file.as_deref()) {
+        Ok(table) => users.merge(table),
+        Err(e) => {
+            eprintln!("Error: {:#}", e);
+            std::process::exit(1);
+        }
+    }
+
+    let auth_config = (!users.is_empty()).then(|| {
+        let names: Vec<&str> = users.users.iter().map(|u| u.username.as_str()).collect();
+        tracing::info!("Autenticación habilitada para: {}", names.join(", "));
+        let session_ttl = std::time::Duration::from_secs(profile.session_hours * 60 * 60);
+        auth::AuthConfig {
+            users: Arc::new(users),
+            guest: profile.guest.clone(),
+            sessions: Arc::new(SessionStore::new(session_ttl, profile.tls)),
+            verified: Arc::new(auth::CredentialCache::default()),
+            failures: Arc::new(FailureThrottle::default()),
+        }
+    });
+
+    let trash = profile.features.trash.then(|| Arc::new(Trash::new(&base_path, profile.trash_days)));
+
+    #[cfg(feature = "content-search")]
+    let content_index = if profile.features.content_search && !profile.drop_box {
+        open_content_index(&base_path, profile.symlinks)
+    } else {
+        None
+    };
+    #[cfg(not(feature = "content-search"))]
+    if profile.features.content_search {
+        tracing::warn!("--content-search no tiene efecto: compilado sin la feature `content-search`");
+    }
+
+    let watcher = if profile.features.live_updates && !profile.drop_box {
+        match DirWatcher::start(base_path.clone()) {
+            Ok(watcher) => Some(Arc::new(watcher)),
+            Err(e) => {
+                tracing::warn!("No se pudo vigilar la carpeta, los listados no se actualizarán solos: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
     // Crear el estado compartido
     let state = Arc::new(AppState { 
         base_path: base_path.clone(),
-        max_upload_size: args.max_upload_size * 1024 * 1024, // Convertir a bytes
-        theme_path: args.theme.clone(),
-        clipboard: Arc::new(RwLock::new(String::new())),
+        resolver: PathResolver::new(base_path.clone(), profile.symlinks),
+        max_upload_size: profile.max_upload_size * 1024 * 1024, // Convertir a bytes
+        theme,
+        clipboard: Arc::new(Clipboard::default()),
+        crc_cache: Arc::new(CrcCache::default()),
+        tus_locks: Arc::new(routes::UploadLocks::default()),
+        auth: auth_config.clone(),
+        shares: ShareStore::open_default().map(Arc::new),
+        share_failures: Arc::new(FailureThrottle::default()),
+        drop_box_subfolders: profile.drop_box_subfolders,
+        trash: trash.clone(),
+        on_conflict: profile.on_conflict,
+        extract_limits: ExtractLimits {
+            max_entries: profile.extract_max_entries,
+            max_bytes: profile.extract_max_size * 1024 * 1024,
+        },
+        #[cfg(feature = "content-search")]
+        content_index: content_index.clone(),
+        watcher,
+        features: profile.features.clone(),
     });
 
+    // Limpieza periódica de subidas reanudables abandonadas
+    let _sweeper = routes::spawn_upload_sweeper(base_path.clone());
+    // ...y de la papelera, según su política de retención
+    let _trash_sweeper = trash.map(spawn_trash_sweeper);
+    // ...y el índice de contenido, al día con la carpeta
+    #[cfg(feature = "content-search")]
+    let _indexer = content_index
+        .map(|index| crate::content_index::spawn_indexer(index, state.watcher.as_ref().map(|watcher| watcher.subscribe())));
+
+    if profile.drop_box {
+        tracing::info!("Modo buzón: sólo se admiten subidas en {:?}", base_path);
+    }
+
     let mut app = Router::new()
         .route("/health", get(|| async { "Servidor activo" }))
-        .merge(routes::app_router())
-        .layer(axum::extract::DefaultBodyLimit::max((args.max_upload_size * 1024 * 1024) as usize))
+        .merge(if profile.drop_box { routes::drop_box_router() } else { routes::app_router(&profile.features) })
+        .layer(axum::extract::DefaultBodyLimit::max((profile.max_upload_size * 1024 * 1024) as usize))
         .with_state(state);
 
     // Middleware de Autenticación Condicional
-    if let (Some(username), Some(password)) = (args.username.clone(), args.password.clone()) {
-        tracing::info!("Autenticación habilitada para usuario: {}", username);
-        let auth_config = auth::AuthConfig { username, password };
+    if let Some(auth_config) = auth_config {
         app = app.layer(middleware::from_fn(move |req, next| {
             auth::auth_middleware(auth_config.clone(), req, next)
         }));
+    } else {
+        // Sin usuarios todo el mundo tiene todos los permisos, pero de forma explícita
+        app = app.layer(axum::Extension(auth::CurrentUser::open_access()));
     }
 
     // Configurar TLS
-    let tls_config = get_tls_config(&args).await;
+    let tls_config = get_tls_config(&profile).await;
 
     // --- Identity & Discovery ---
     let local_ip = crate::utils::net::get_local_ip();
@@ -136,15 +449,16 @@ pub async fn start_server(args: Args) {
     // But `register_service` calls `tokio::spawn` internally and returns a handle to it.
     // If we drop the handle, the task continues unless we explicitly abort it.
     // So we can just let it run.
-    let _mdns_service = crate::utils::mdns::register_service(args.port, "local-share", tls_config.is_some());
+    let _mdns_service = profile.features.mdns
+        .then(|| crate::utils::mdns::register_service(profile.port, "local-share", tls_config.is_some()));
 
     // Build Connection URL
     let full_url = crate::utils::net::build_connection_url(
         tls_config.is_some(),
         &local_ip,
-        args.port,
-        args.username.as_deref(),
-        args.password.as_deref(),
+        profile.port,
+        None, // Sólo guardamos hashes: no hay contraseña que incluir
+        None,
         false, // Don't include credentials in CLI text output by default for security, or maybe we want to?
                // The user requested a toggle in GUI. For CLI, maybe just base URL.
                // Let's print the base URL for the text and maybe the autologin one for QR?
@@ -165,23 +479,25 @@ pub async fn start_server(args: Args) {
     tracing::info!("Connection URL: {}", full_url);
     
     // Generate QR
-    if let Ok(qr_code) = crate::utils::qr::generate_ascii_qr(&full_url) {
-        println!("\nScan this QR code to connect:\n{}", qr_code);
-    } else {
-        tracing::warn!("Could not generate QR code.");
+    if profile.features.qr {
+        if let Ok(qr_code) = crate::utils::qr::generate_ascii_qr(&full_url) {
+            println!("\nScan this QR code to connect:\n{}", qr_code);
+        } else {
+            tracing::warn!("Could not generate QR code.");
+        }
     }
 
-    let addr = SocketAddr::from(([0, 0, 0, 0], args.port));
+    let addr = SocketAddr::from(([0, 0, 0, 0], profile.port));
     
     if let Some(config) = tls_config {
-        tracing::info!("Server listening on {}://0.0.0.0:{}", protocol, args.port);
+        tracing::info!("Server listening on {}://0.0.0.0:{}", protocol, profile.port);
         axum_server::bind_rustls(addr, config)
-            .serve(app.into_make_service())
+            .serve(app.into_make_service_with_connect_info::<SocketAddr>())
             .await
             .unwrap();
     } else {
-        tracing::info!("Server listening on {}://0.0.0.0:{}", protocol, args.port);
+        tracing::info!("Server listening on {}://0.0.0.0:{}", protocol, profile.port);
         let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
-        axum::serve(listener, app).await.unwrap();
+        axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await.unwrap();
     }
 }
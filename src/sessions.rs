@@ -0,0 +1,128 @@
+// Sesiones de navegador: el login por formulario crea una sesión en memoria y
+// el navegador la presenta en una cookie HttpOnly firmada con HMAC-SHA256.
+//
+// La cookie sólo lleva `<id>.<firma>`; el usuario y la caducidad viven en el
+// servidor, así que cerrar sesión (o reiniciar el servidor) la invalida.
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+pub const COOKIE_NAME: &str = "local_share_session";
+
+type HmacSha256 = Hmac<Sha256>;
+
+struct Session {
+    username: String,
+    expires_at: Instant,
+}
+
+pub struct SessionStore {
+    /// Clave de firma, nueva en cada arranque
+    secret: [u8; 32],
+    ttl: Duration,
+    /// Añadir `Secure` a la cookie (servidor con TLS)
+    secure: bool,
+    sessions: Mutex<HashMap<String, Session>>,
+}
+
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub(crate) fn from_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+impl SessionStore {
+    pub fn new(ttl: Duration, secure: bool) -> Self {
+        Self {
+            secret: rand::random(),
+            ttl,
+            secure,
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn mac(&self, id: &str) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(&self.secret).expect("HMAC admite cualquier longitud de clave");
+        mac.update(id.as_bytes());
+        mac
+    }
+
+    /// Crea una sesión y devuelve el valor de la cookie.
+    pub fn create(&self, username: &str) -> String {
+        let id = to_hex(&rand::random::<[u8; 32]>());
+        let now = Instant::now();
+
+        let mut sessions = self.sessions.lock().expect("Lock poisoned");
+        sessions.retain(|_, s| s.expires_at > now);
+        sessions.insert(id.clone(), Session { username: username.to_string(), expires_at: now + self.ttl });
+
+        let signature = to_hex(&self.mac(&id).finalize().into_bytes());
+        format!("{}.{}", id, signature)
+    }
+
+    /// Valida la firma (en tiempo constante) y devuelve el usuario de la sesión.
+    pub fn get(&self, cookie: &str) -> Option<String> {
+        let id = self.verify(cookie)?;
+        let mut sessions = self.sessions.lock().expect("Lock poisoned");
+        match sessions.get(id) {
+            Some(session) if session.expires_at > Instant::now() => Some(session.username.clone()),
+            Some(_) => {
+                sessions.remove(id);
+                None
+            }
+            None => None,
+        }
+    }
+
+    pub fn remove(&self, cookie: &str) {
+        if let Some(id) = self.verify(cookie) {
+            self.sessions.lock().expect("Lock poisoned").remove(id);
+        }
+    }
+
+    fn verify<'a>(&self, cookie: &'a str) -> Option<&'a str> {
+        let (id, signature) = cookie.split_once('.')?;
+        self.mac(id).verify_slice(&from_hex(signature)?).ok()?;
+        Some(id)
+    }
+
+    /// Cabecera `Set-Cookie` para una sesión recién creada.
+    pub fn set_cookie(&self, value: &str) -> String {
+        self.cookie(value, self.ttl.as_secs())
+    }
+
+    /// Cabecera `Set-Cookie` que borra la cookie del navegador.
+    pub fn clear_cookie(&self) -> String {
+        self.cookie("", 0)
+    }
+
+    fn cookie(&self, value: &str, max_age: u64) -> String {
+        let secure = if self.secure { "; Secure" } else { "" };
+        format!("{}={}; Path=/; HttpOnly; SameSite=Lax; Max-Age={}{}", COOKIE_NAME, value, max_age, secure)
+    }
+}
+
+/// Busca la cookie de sesión en las cabeceras `Cookie` de la petición.
+pub fn cookie_value(headers: &axum::http::HeaderMap) -> Option<&str> {
+    headers
+        .get_all(axum::http::header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(name, _)| *name == COOKIE_NAME)
+        .map(|(_, value)| value)
+}
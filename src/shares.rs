@@ -0,0 +1,214 @@
+// Enlaces para compartir un único archivo o carpeta: `/s/<token>`.
+//
+// Se guardan en `shares.json` dentro de la carpeta de datos del usuario
+// (`~/.local/share/local-share/` en Linux), así la GUI y el servidor ven y
+// revocan los mismos enlaces aunque corran en procesos distintos. El token es
+// el id del enlace seguido de su firma HMAC con un secreto guardado en ese
+// mismo archivo, de modo que los enlaces sobreviven a los reinicios.
+
+use crate::sessions::{from_hex, to_hex};
+use crate::users;
+use anyhow::Context;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::{
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Bytes de la firma que viajan en el token (128 bits)
+const SIGNATURE_LEN: usize = 16;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Share {
+    pub id: String,
+    /// Ruta absoluta del archivo o carpeta compartidos
+    pub path: PathBuf,
+    pub created_at: u64, // segundos UNIX
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_downloads: Option<u32>,
+    #[serde(default)]
+    pub downloads: u32,
+    /// Hash Argon2 de la contraseña opcional del enlace
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password_hash: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_by: Option<String>,
+}
+
+impl Share {
+    /// Ni caducado ni con las descargas agotadas.
+    pub fn is_active(&self) -> bool {
+        let expired = self.expires_at.is_some_and(|t| t <= now_secs());
+        let exhausted = self.max_downloads.is_some_and(|max| self.downloads >= max);
+        !expired && !exhausted
+    }
+
+    /// Ruta relativa a la carpeta compartida, si está dentro de ella.
+    pub fn relative_to(&self, base_path: &Path) -> Option<&Path> {
+        self.path.strip_prefix(base_path).ok()
+    }
+
+    pub fn check_password(&self, password: &str) -> bool {
+        match &self.password_hash {
+            Some(hash) => users::verify_hash(hash, password),
+            None => true,
+        }
+    }
+}
+
+/// Parámetros de un enlace nuevo.
+pub struct NewShare {
+    pub path: PathBuf,
+    pub expires_in: Option<Duration>,
+    pub max_downloads: Option<u32>,
+    pub password: Option<String>,
+    pub created_by: Option<String>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct ShareFile {
+    /// Clave HMAC en hexadecimal
+    secret: String,
+    shares: Vec<Share>,
+}
+
+pub struct ShareStore {
+    path: PathBuf,
+    /// Serializa las lecturas-modificaciones-escrituras de este proceso
+    lock: Mutex<()>,
+}
+
+pub fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// `~/.local/share/local-share/shares.json` (o el equivalente de cada sistema).
+pub fn default_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("local-share").join("shares.json"))
+}
+
+impl ShareStore {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path, lock: Mutex::new(()) }
+    }
+
+    pub fn open_default() -> Option<Self> {
+        default_path().map(Self::new)
+    }
+
+    fn read(&self) -> anyhow::Result<ShareFile> {
+        match std::fs::read(&self.path) {
+            Ok(data) => serde_json::from_slice(&data).with_context(|| format!("Enlaces inválidos en {:?}", self.path)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(ShareFile::default()),
+            Err(e) => Err(e).with_context(|| format!("No se pudo leer {:?}", self.path)),
+        }
+    }
+
+    fn write(&self, file: &ShareFile) -> anyhow::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        // Temporal + rename: un lector concurrente nunca ve el archivo a medias
+        let temp = self.path.with_extension("json.tmp");
+        std::fs::write(&temp, serde_json::to_vec_pretty(file)?)?;
+        std::fs::rename(&temp, &self.path).with_context(|| format!("No se pudo escribir {:?}", self.path))?;
+        Ok(())
+    }
+
+    fn modify<T>(&self, f: impl FnOnce(&mut ShareFile) -> anyhow::Result<T>) -> anyhow::Result<T> {
+        let _guard = self.lock.lock().expect("Lock poisoned");
+        let mut file = self.read()?;
+        let result = f(&mut file)?;
+        self.write(&file)?;
+        Ok(result)
+    }
+
+    /// Crea el enlace y devuelve su token.
+    pub fn create(&self, new: NewShare) -> anyhow::Result<(String, Share)> {
+        let password_hash = new.password.as_deref().map(users::hash_password).transpose()?;
+        self.modify(|file| {
+            if file.secret.is_empty() {
+                file.secret = to_hex(&rand::random::<[u8; 32]>());
+            }
+            let now = now_secs();
+            let share = Share {
+                id: to_hex(&rand::random::<[u8; 16]>()),
+                path: new.path,
+                created_at: now,
+                expires_at: new.expires_in.map(|d| now + d.as_secs()),
+                max_downloads: new.max_downloads,
+                downloads: 0,
+                password_hash,
+                created_by: new.created_by,
+            };
+            file.shares.push(share.clone());
+            Ok((token(&file.secret, &share.id), share))
+        })
+    }
+
+    /// Todos los enlaces (también los caducados) con su token.
+    pub fn list(&self) -> anyhow::Result<Vec<(String, Share)>> {
+        let _guard = self.lock.lock().expect("Lock poisoned");
+        let file = self.read()?;
+        Ok(file.shares.iter().map(|s| (token(&file.secret, &s.id), s.clone())).collect())
+    }
+
+    /// `false` si el enlace no existía.
+    pub fn revoke(&self, id: &str) -> anyhow::Result<bool> {
+        self.modify(|file| {
+            let before = file.shares.len();
+            file.shares.retain(|s| s.id != id);
+            Ok(file.shares.len() != before)
+        })
+    }
+
+    /// Comprueba la firma del token y devuelve su enlace.
+    pub fn find(&self, token: &str) -> anyhow::Result<Option<Share>> {
+        let _guard = self.lock.lock().expect("Lock poisoned");
+        let file = self.read()?;
+        let Some(id) = verify(&file.secret, token) else {
+            return Ok(None);
+        };
+        Ok(file.shares.into_iter().find(|s| s.id == id))
+    }
+
+    /// Cuenta una descarga; `false` si el enlace ya no está activo.
+    pub fn record_download(&self, id: &str) -> anyhow::Result<bool> {
+        self.modify(|file| {
+            match file.shares.iter_mut().find(|s| s.id == id) {
+                Some(share) if share.is_active() => {
+                    share.downloads += 1;
+                    Ok(true)
+                }
+                _ => Ok(false),
+            }
+        })
+    }
+}
+
+fn mac(secret: &str, id: &str) -> Hmac<Sha256> {
+    let key = from_hex(secret).unwrap_or_default();
+    let mut mac = Hmac::<Sha256>::new_from_slice(&key).expect("HMAC admite cualquier longitud de clave");
+    mac.update(id.as_bytes());
+    mac
+}
+
+/// `<id><firma>`, ambos en hexadecimal.
+fn token(secret: &str, id: &str) -> String {
+    let signature = mac(secret, id).finalize().into_bytes();
+    format!("{}{}", id, to_hex(&signature[..SIGNATURE_LEN]))
+}
+
+fn verify<'a>(secret: &str, token: &'a str) -> Option<&'a str> {
+    if secret.is_empty() || !token.is_ascii() || token.len() != 32 + SIGNATURE_LEN * 2 {
+        return None;
+    }
+    let (id, signature) = token.split_at(32);
+    mac(secret, id).verify_truncated_left(&from_hex(signature)?).ok()?;
+    Some(id)
+}
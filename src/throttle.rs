@@ -0,0 +1,97 @@
+// Freno a los intentos de contraseña fallidos (login, Basic y enlaces con
+// contraseña).
+//
+// Tras `FREE_ATTEMPTS` fallos seguidos de una misma clave (una IP, un enlace),
+// cada fallo más la bloquea el doble de tiempo que el anterior, desde
+// `BASE_DELAY` hasta `MAX_DELAY`. Mientras está bloqueada los intentos se
+// rechazan sin llegar a Argon2. Un acierto, o `FORGET_AFTER` sin fallos, la
+// deja limpia.
+
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+const FREE_ATTEMPTS: u32 = 5;
+const BASE_DELAY: Duration = Duration::from_secs(1);
+const MAX_DELAY: Duration = Duration::from_secs(60);
+const FORGET_AFTER: Duration = Duration::from_secs(15 * 60);
+/// Claves recordadas; al llenarse se olvidan las más viejas
+const MAX_KEYS: usize = 10_000;
+
+struct Failures {
+    count: u32,
+    last: Instant,
+    blocked_until: Option<Instant>,
+}
+
+#[derive(Default)]
+pub struct FailureThrottle {
+    entries: Mutex<HashMap<String, Failures>>,
+}
+
+impl FailureThrottle {
+    /// `Err` con lo que falta de espera si `key` está bloqueada.
+    pub fn check(&self, key: &str) -> Result<(), Duration> {
+        let entries = self.entries.lock().expect("Lock poisoned");
+        let now = Instant::now();
+        match entries.get(key).and_then(|failures| failures.blocked_until) {
+            Some(until) if until > now => Err(until - now),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn failure(&self, key: &str) {
+        let mut entries = self.entries.lock().expect("Lock poisoned");
+        let now = Instant::now();
+        if entries.len() >= MAX_KEYS && !entries.contains_key(key) {
+            entries.retain(|_, failures| now.duration_since(failures.last) < FORGET_AFTER);
+            if entries.len() >= MAX_KEYS {
+                entries.clear();
+            }
+        }
+
+        let failures = entries.entry(key.to_string()).or_insert(Failures { count: 0, last: now, blocked_until: None });
+        if now.duration_since(failures.last) >= FORGET_AFTER {
+            failures.count = 0;
+        }
+        failures.count += 1;
+        failures.last = now;
+        if let Some(extra) = failures.count.checked_sub(FREE_ATTEMPTS + 1) {
+            let delay = BASE_DELAY.saturating_mul(1 << extra.min(16)).min(MAX_DELAY);
+            failures.blocked_until = Some(now + delay);
+        }
+    }
+
+    pub fn success(&self, key: &str) {
+        self.entries.lock().expect("Lock poisoned").remove(key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blocks_after_free_attempts_until_success() {
+        let throttle = FailureThrottle::default();
+        for _ in 0..FREE_ATTEMPTS {
+            assert!(throttle.check("1.2.3.4").is_ok());
+            throttle.failure("1.2.3.4");
+        }
+        assert!(throttle.check("1.2.3.4").is_ok());
+
+        throttle.failure("1.2.3.4");
+        let wait = throttle.check("1.2.3.4").unwrap_err();
+        assert!(wait > Duration::ZERO && wait <= BASE_DELAY);
+        // Otras claves no se ven afectadas
+        assert!(throttle.check("5.6.7.8").is_ok());
+
+        throttle.failure("1.2.3.4");
+        assert!(throttle.check("1.2.3.4").unwrap_err() > BASE_DELAY);
+
+        throttle.success("1.2.3.4");
+        assert!(throttle.check("1.2.3.4").is_ok());
+    }
+}
@@ -0,0 +1,297 @@
+// Papelera: lo que se borra (o se sobrescribe) desde la web no desaparece,
+// sino que se mueve a `<base_path>/.local-share-trash/`.
+//
+// Cada elemento ocupa dos entradas: `<id>` con el archivo o carpeta tal cual y
+// `<id>.json` con su ruta original, cuándo se borró y quién. Al estar dentro de
+// la carpeta compartida basta un `rename`, sin copiar datos, salvo que lo
+// borrado esté en otro sistema de archivos montado dentro de ella: entonces se
+// copia a la papelera y después se borra el original.
+
+use crate::utils::paths::TRASH_DIR;
+use serde::{Deserialize, Serialize};
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashEntry {
+    pub id: String,
+    /// Ruta original, relativa a la carpeta compartida (`/docs/a.txt`)
+    pub original_path: String,
+    pub deleted_at: u64, // segundos UNIX
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_by: Option<String>,
+    pub is_dir: bool,
+    pub size: u64,
+}
+
+pub struct Trash {
+    dir: PathBuf,
+    /// Tiempo que se conserva cada elemento; `None` = hasta vaciarla a mano
+    retention: Option<Duration>,
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// Los ids son 32 caracteres hexadecimales; cualquier otra cosa (`..`, `/`)
+/// no puede nombrar nada dentro de la papelera.
+fn valid_id(id: &str) -> bool {
+    id.len() == 32 && id.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+impl Trash {
+    /// `retention_days == 0` desactiva la purga automática.
+    pub fn new(base_path: &Path, retention_days: u64) -> Self {
+        Self {
+            dir: base_path.join(TRASH_DIR),
+            retention: (retention_days > 0).then(|| Duration::from_secs(retention_days * 24 * 60 * 60)),
+        }
+    }
+
+    fn paths(&self, id: &str) -> (PathBuf, PathBuf) {
+        (self.dir.join(id), self.dir.join(format!("{}.json", id)))
+    }
+
+    /// Mueve `full_path` a la papelera.
+    pub async fn put(&self, full_path: &Path, original_path: String, deleted_by: Option<String>) -> io::Result<TrashEntry> {
+        tokio::fs::create_dir_all(&self.dir).await?;
+
+        let metadata = tokio::fs::symlink_metadata(full_path).await?;
+        let entry = TrashEntry {
+            id: crate::sessions::to_hex(&rand::random::<[u8; 16]>()),
+            original_path,
+            deleted_at: now_secs(),
+            deleted_by,
+            is_dir: metadata.is_dir(),
+            size: if metadata.is_dir() { 0 } else { metadata.len() },
+        };
+
+        // Primero los metadatos: si el rename falla no queda nada huérfano
+        let (data, meta) = self.paths(&entry.id);
+        tokio::fs::write(&meta, serde_json::to_vec_pretty(&entry)?).await?;
+        if let Err(e) = move_path(full_path, &data).await {
+            let _ = tokio::fs::remove_file(&meta).await;
+            return Err(e);
+        }
+
+        tracing::info!("A la papelera: {} ({})", entry.original_path, entry.id);
+        Ok(entry)
+    }
+
+    /// Elementos de la papelera, los más recientes primero.
+    pub async fn list(&self) -> io::Result<Vec<TrashEntry>> {
+        let mut entries = match tokio::fs::read_dir(&self.dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+
+        let mut items = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            match tokio::fs::read(&path).await.map(|data| serde_json::from_slice::<TrashEntry>(&data)) {
+                Ok(Ok(item)) => items.push(item),
+                _ => tracing::warn!("Metadatos de papelera ilegibles: {:?}", path),
+            }
+        }
+
+        items.sort_by_key(|item| std::cmp::Reverse(item.deleted_at));
+        Ok(items)
+    }
+
+    pub async fn get(&self, id: &str) -> io::Result<Option<TrashEntry>> {
+        if !valid_id(id) {
+            return Ok(None);
+        }
+        match tokio::fs::read(self.paths(id).1).await {
+            Ok(data) => Ok(Some(serde_json::from_slice(&data)?)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Saca el elemento de la papelera y lo deja en `dest`.
+    pub async fn restore(&self, id: &str, dest: &Path) -> io::Result<()> {
+        let (data, meta) = self.paths(id);
+        move_path(&data, dest).await?;
+        tokio::fs::remove_file(&meta).await?;
+        tracing::info!("Restaurado de la papelera: {:?}", dest);
+        Ok(())
+    }
+
+    /// Borra definitivamente un elemento; `false` si no existía.
+    pub async fn purge(&self, id: &str) -> io::Result<bool> {
+        if !valid_id(id) {
+            return Ok(false);
+        }
+        let (data, meta) = self.paths(id);
+        match tokio::fs::symlink_metadata(&data).await {
+            Ok(metadata) if metadata.is_dir() => tokio::fs::remove_dir_all(&data).await?,
+            Ok(_) => tokio::fs::remove_file(&data).await?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        match tokio::fs::remove_file(&meta).await {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Vacía la papelera y devuelve cuántos elementos había.
+    pub async fn purge_all(&self) -> io::Result<usize> {
+        let mut purged = 0;
+        for item in self.list().await? {
+            if self.purge(&item.id).await? {
+                purged += 1;
+            }
+        }
+        Ok(purged)
+    }
+
+    /// Política de retención: purga lo que lleva más tiempo del configurado.
+    async fn sweep(&self) -> io::Result<()> {
+        let Some(retention) = self.retention else {
+            return Ok(());
+        };
+        let limit = now_secs().saturating_sub(retention.as_secs());
+        for item in self.list().await? {
+            if item.deleted_at <= limit {
+                tracing::info!("Purgando de la papelera: {}", item.original_path);
+                self.purge(&item.id).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `rename`, o copiar y borrar el original si `from` y `to` están en sistemas de
+/// archivos distintos (`EXDEV`).
+async fn move_path(from: &Path, to: &Path) -> io::Result<()> {
+    match tokio::fs::rename(from, to).await {
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => copy_then_remove(from, to).await,
+        result => result,
+    }
+}
+
+/// Si la copia falla se deshace y el original queda intacto. Si lo que falla es
+/// borrar el original, la copia ya está completa: se conserva y lo que quede
+/// del original se puede volver a borrar.
+async fn copy_then_remove(from: &Path, to: &Path) -> io::Result<()> {
+    if let Err(e) = copy_tree(from, to).await {
+        let _ = remove_any(to).await;
+        return Err(e);
+    }
+    if let Err(e) = remove_any(from).await {
+        tracing::warn!("Copiado a {:?}, pero no se pudo borrar del todo {:?}: {}", to, from, e);
+    }
+    Ok(())
+}
+
+/// Copia tal cual: los enlaces simbólicos como enlaces, y los archivos con sus
+/// permisos y su fecha de modificación.
+async fn copy_tree(from: &Path, to: &Path) -> io::Result<()> {
+    let mut pending = vec![(from.to_path_buf(), to.to_path_buf())];
+    while let Some((from, to)) = pending.pop() {
+        let metadata = tokio::fs::symlink_metadata(&from).await?;
+        if metadata.is_symlink() {
+            copy_symlink(&from, &to).await?;
+        } else if metadata.is_dir() {
+            tokio::fs::create_dir(&to).await?;
+            tokio::fs::set_permissions(&to, metadata.permissions()).await?;
+            let mut entries = tokio::fs::read_dir(&from).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                pending.push((entry.path(), to.join(entry.file_name())));
+            }
+        } else {
+            tokio::fs::copy(&from, &to).await?;
+            if let Ok(modified) = metadata.modified() {
+                tokio::fs::File::options().write(true).open(&to).await?.into_std().await.set_modified(modified)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+async fn copy_symlink(from: &Path, to: &Path) -> io::Result<()> {
+    tokio::fs::symlink(tokio::fs::read_link(from).await?, to).await
+}
+
+#[cfg(not(unix))]
+async fn copy_symlink(from: &Path, _to: &Path) -> io::Result<()> {
+    tracing::warn!("Papelera: se omite el enlace simbólico {:?}", from);
+    Ok(())
+}
+
+async fn remove_any(path: &Path) -> io::Result<()> {
+    if tokio::fs::symlink_metadata(path).await?.is_dir() {
+        tokio::fs::remove_dir_all(path).await
+    } else {
+        tokio::fs::remove_file(path).await
+    }
+}
+
+pub fn spawn_trash_sweeper(trash: Arc<Trash>) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = trash.sweep().await {
+                tracing::warn!("Error purgando la papelera: {}", e);
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn copies_trees_when_rename_cannot_be_used() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let from = tmp.path().join("docs");
+        std::fs::create_dir_all(from.join("sub")).unwrap();
+        std::fs::write(from.join("sub/a.txt"), "hola").unwrap();
+        std::fs::set_permissions(from.join("sub/a.txt"), std::fs::Permissions::from_mode(0o600)).unwrap();
+        let mtime = UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        std::fs::File::options().write(true).open(from.join("sub/a.txt")).unwrap().set_modified(mtime).unwrap();
+        std::os::unix::fs::symlink("sub/a.txt", from.join("link")).unwrap();
+
+        let to = tmp.path().join("trash-item");
+        copy_then_remove(&from, &to).await.unwrap();
+
+        assert!(!from.exists());
+        let copied = std::fs::metadata(to.join("sub/a.txt")).unwrap();
+        assert_eq!(std::fs::read_to_string(to.join("sub/a.txt")).unwrap(), "hola");
+        assert_eq!(copied.permissions().mode() & 0o777, 0o600);
+        assert_eq!(copied.modified().unwrap(), mtime);
+        assert_eq!(std::fs::read_link(to.join("link")).unwrap(), Path::new("sub/a.txt"));
+    }
+
+    #[tokio::test]
+    async fn failed_copy_leaves_the_original_untouched() {
+        let tmp = tempfile::tempdir().unwrap();
+        let from = tmp.path().join("a.txt");
+        std::fs::write(&from, "hola").unwrap();
+
+        // El destino no tiene carpeta padre: la copia falla
+        let to = tmp.path().join("missing/a.txt");
+        assert!(copy_then_remove(&from, &to).await.is_err());
+        assert_eq!(std::fs::read_to_string(&from).unwrap(), "hola");
+    }
+}
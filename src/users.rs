@@ -0,0 +1,257 @@
+// Tabla de usuarios con contraseñas Argon2 y permisos por cuenta.
+//
+// Se guarda como TOML (por defecto `~/.config/local-share/users.toml`) y se
+// gestiona con `local-share user add/passwd/remove`:
+//
+//     [[users]]
+//     username = "ana"
+//     password_hash = "$argon2id$v=19$m=19456,t=2,p=1$..."
+//     permissions = ["read", "upload"]
+
+use anyhow::{bail, Context};
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use clap::{Subcommand, ValueEnum};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Lo que una cuenta puede hacer en el servidor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Permission {
+    /// Navegar y descargar
+    Read,
+    /// Subir archivos
+    Upload,
+    /// Borrar, renombrar y mover
+    Delete,
+    /// Leer y escribir el portapapeles compartido
+    Clipboard,
+    /// Crear, listar y revocar enlaces `/s/<token>`
+    Share,
+}
+
+impl Permission {
+    pub const ALL: &'static [Permission] = &[Self::Read, Self::Upload, Self::Delete, Self::Clipboard, Self::Share];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Read => "read",
+            Self::Upload => "upload",
+            Self::Delete => "delete",
+            Self::Clipboard => "clipboard",
+            Self::Share => "share",
+        }
+    }
+}
+
+fn all_permissions() -> Vec<Permission> {
+    Permission::ALL.to_vec()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct User {
+    pub username: String,
+    /// Hash Argon2 en formato PHC; la contraseña nunca se guarda en claro
+    pub password_hash: String,
+    #[serde(default = "all_permissions")]
+    pub permissions: Vec<Permission>,
+}
+
+impl User {
+    pub fn new(username: String, password: &str, permissions: Vec<Permission>) -> anyhow::Result<Self> {
+        Ok(Self { username, password_hash: hash_password(password)?, permissions })
+    }
+
+    /// Comparación en tiempo constante (la hace `argon2` sobre el hash derivado).
+    pub fn verify(&self, password: &str) -> bool {
+        verify_hash(&self.password_hash, password)
+    }
+}
+
+pub fn hash_password(password: &str) -> anyhow::Result<String> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| anyhow::anyhow!("No se pudo calcular el hash: {}", e))
+}
+
+pub fn verify_hash(hash: &str, password: &str) -> bool {
+    match PasswordHash::new(hash) {
+        Ok(parsed) => Argon2::default().verify_password(password.as_bytes(), &parsed).is_ok(),
+        Err(_) => false,
+    }
+}
+
+/// Verificaciones Argon2 simultáneas: cada una ocupa un núcleo y unos 19 MiB.
+const MAX_CONCURRENT_VERIFICATIONS: usize = 4;
+
+/// Ejecuta `check` (una verificación Argon2, lenta a propósito) en el pool de
+/// hilos bloqueantes, para que una ráfaga de contraseñas no pare los hilos del
+/// runtime, y sin más de `MAX_CONCURRENT_VERIFICATIONS` a la vez.
+pub async fn verify_blocking<T: Send + 'static>(check: impl FnOnce() -> T + Send + 'static) -> anyhow::Result<T> {
+    static SLOTS: tokio::sync::Semaphore = tokio::sync::Semaphore::const_new(MAX_CONCURRENT_VERIFICATIONS);
+    let _slot = SLOTS.acquire().await?;
+    Ok(tokio::task::spawn_blocking(check).await?)
+}
+
+/// Hash contra el que se verifica cuando el usuario no existe, para que el
+/// tiempo de respuesta no delate qué nombres de usuario son válidos.
+fn dummy_hash() -> &'static str {
+    static DUMMY: OnceLock<String> = OnceLock::new();
+    DUMMY.get_or_init(|| hash_password("local-share").unwrap_or_default())
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct UserTable {
+    #[serde(default)]
+    pub users: Vec<User>,
+}
+
+/// `~/.config/local-share/users.toml` (o el equivalente de cada sistema).
+pub fn default_path() -> Option<PathBuf> {
+    crate::config::default_path().map(|path| path.with_file_name("users.toml"))
+}
+
+impl UserTable {
+    /// Carga `path`, o la ubicación por defecto si no se indica. Un archivo
+    /// explícito debe existir; el de por defecto es opcional.
+    pub fn load(path: Option<&Path>) -> anyhow::Result<Self> {
+        let (path, required) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => match default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Self::default()),
+            },
+        };
+
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if !required && e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e).with_context(|| format!("No se pudo leer {:?}", path)),
+        };
+
+        toml::from_str(&text).with_context(|| format!("Tabla de usuarios inválida en {:?}", path))
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let text = toml::to_string_pretty(self)?;
+        std::fs::write(path, text).with_context(|| format!("No se pudo escribir {:?}", path))?;
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.users.is_empty()
+    }
+
+    pub fn get(&self, username: &str) -> Option<&User> {
+        self.users.iter().find(|u| u.username == username)
+    }
+
+    fn get_mut(&mut self, username: &str) -> Option<&mut User> {
+        self.users.iter_mut().find(|u| u.username == username)
+    }
+
+    /// Devuelve el usuario si las credenciales son correctas.
+    pub fn authenticate(&self, username: &str, password: &str) -> Option<&User> {
+        match self.get(username) {
+            Some(user) => user.verify(password).then_some(user),
+            None => {
+                verify_hash(dummy_hash(), password);
+                None
+            }
+        }
+    }
+
+    /// Añade los usuarios de `other`; los de `self` tienen prioridad.
+    pub fn merge(&mut self, other: UserTable) {
+        for user in other.users {
+            if self.get(&user.username).is_none() {
+                self.users.push(user);
+            }
+        }
+    }
+}
+
+// --- Subcomando `local-share user ...` ---
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum UserCommand {
+    /// Crear un usuario (pide la contraseña por terminal)
+    Add {
+        username: String,
+        /// Permisos separados por comas [default: todos]
+        #[arg(long, value_enum, value_delimiter = ',')]
+        permissions: Vec<Permission>,
+    },
+    /// Cambiar la contraseña de un usuario
+    Passwd { username: String },
+    /// Eliminar un usuario
+    Remove { username: String },
+    /// Listar los usuarios y sus permisos
+    List,
+}
+
+pub fn run_command(command: UserCommand, file: Option<PathBuf>) -> anyhow::Result<()> {
+    let path = match file.or_else(default_path) {
+        Some(path) => path,
+        None => bail!("No hay directorio de configuración; usa --users-file"),
+    };
+    let mut table = UserTable::load(Some(&path)).or_else(|e| {
+        if path.exists() { Err(e) } else { Ok(UserTable::default()) }
+    })?;
+
+    match command {
+        UserCommand::Add { username, permissions } => {
+            if table.get(&username).is_some() {
+                bail!("El usuario '{}' ya existe", username);
+            }
+            let permissions = if permissions.is_empty() { all_permissions() } else { permissions };
+            let password = prompt_password()?;
+            table.users.push(User::new(username.clone(), &password, permissions)?);
+            println!("Usuario '{}' creado", username);
+        }
+        UserCommand::Passwd { username } => {
+            let user = table.get_mut(&username).with_context(|| format!("El usuario '{}' no existe", username))?;
+            user.password_hash = hash_password(&prompt_password()?)?;
+            println!("Contraseña de '{}' actualizada", username);
+        }
+        UserCommand::Remove { username } => {
+            let before = table.users.len();
+            table.users.retain(|u| u.username != username);
+            if table.users.len() == before {
+                bail!("El usuario '{}' no existe", username);
+            }
+            println!("Usuario '{}' eliminado", username);
+        }
+        UserCommand::List => {
+            for user in &table.users {
+                let permissions: Vec<&str> = user.permissions.iter().map(|p| p.as_str()).collect();
+                println!("{}\t{}", user.username, permissions.join(","));
+            }
+            return Ok(());
+        }
+    }
+
+    table.save(&path)?;
+    println!("Guardado en {:?}", path);
+    Ok(())
+}
+
+fn prompt_password() -> anyhow::Result<String> {
+    let password = rpassword::prompt_password("Contraseña: ")?;
+    if password.is_empty() {
+        bail!("La contraseña no puede estar vacía");
+    }
+    if rpassword::prompt_password("Repite la contraseña: ")? != password {
+        bail!("Las contraseñas no coinciden");
+    }
+    Ok(password)
+}
@@ -1,74 +1,260 @@
 // src/utils/archiver.rs
 
+//! Carpetas comprimidas al vuelo: ZIP Deflate, tar, tar.gz y tar.zst. Todos los
+//! formatos recorren el árbol igual ([`TreeWalker`]) y escriben en el mismo
+//! `duplex`, cuyo extremo de lectura es el cuerpo de la respuesta. El ZIP sin
+//! compresión (reanudable) no pasa por aquí: lo genera [`super::zip_plan`].
+//!
+//! Las entradas conservan la fecha de modificación y los permisos Unix: en
+//! tar van en la cabecera de siempre y en ZIP en los atributos externos y el
+//! extra de fecha extendida.
+//!
+//! Si algo falla a medias, el stream termina con un error en vez de cerrarse
+//! limpio: el cliente ve una descarga fallida y no un archivo truncado que
+//! parece correcto.
+
+use super::paths::PathResolver;
+use super::walk::{unix_mode, TreeWalker};
+use super::zip_plan::utc_datetime;
+use async_compression::{
+    tokio::write::{GzipEncoder, ZstdEncoder},
+    Level,
+};
 use async_zip::tokio::write::ZipFileWriter;
-use async_zip::{Compression, ZipEntryBuilder};
-use futures::stream::Stream;
+use async_zip::{Compression, DeflateOption, ZipDateTime, ZipDateTimeBuilder, ZipEntryBuilder};
+use futures::stream::{self, Stream, StreamExt};
+use std::fs::Metadata;
 use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use tokio::io::{AsyncWrite, AsyncWriteExt, DuplexStream};
 use tokio_util::io::ReaderStream;
-use tokio_util::compat::FuturesAsyncWriteCompatExt; 
+use tokio_util::compat::FuturesAsyncWriteCompatExt;
 
 const BUFFER_SIZE: usize = 64 * 1024;
 
-pub fn archive_directory_stream(
-    root_path: PathBuf,
-    dir_name: String,
+/// Formato de descarga de una carpeta (`?format=`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    /// ZIP sin compresión, con tamaño conocido y reanudable (`zip_plan`)
+    Store,
+    /// ZIP Deflate
+    Zip,
+    Tar,
+    TarGz,
+    TarZst,
+}
+
+impl ArchiveFormat {
+    /// `zip` sin `level` (o con `level=0`) es el ZIP stored de siempre; con un
+    /// nivel pasa a Deflate. `deflate` se mantiene por compatibilidad.
+    pub fn parse(format: Option<&str>, level: Option<i32>) -> Option<Self> {
+        let compressed = level.is_some_and(|level| level > 0);
+        match format.unwrap_or("zip").to_ascii_lowercase().as_str() {
+            "zip" if compressed => Some(Self::Zip),
+            "zip" | "store" => Some(Self::Store),
+            "deflate" => Some(Self::Zip),
+            "tar" => Some(Self::Tar),
+            "tgz" | "tar.gz" | "gz" => Some(Self::TarGz),
+            "zst" | "tar.zst" | "zstd" => Some(Self::TarZst),
+            _ => None,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Store | Self::Zip => "zip",
+            Self::Tar => "tar",
+            Self::TarGz => "tar.gz",
+            Self::TarZst => "tar.zst",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Store | Self::Zip => "application/zip",
+            Self::Tar => "application/x-tar",
+            Self::TarGz => "application/gzip",
+            Self::TarZst => "application/zstd",
+        }
+    }
+}
+
+/// Algo que meter en el archivo: un archivo o una carpeta entera (recursiva),
+/// con el nombre que tendrá dentro (`fotos`, `informes/q3.pdf`).
+pub struct ArchiveItem {
+    pub source: PathBuf,
+    pub name: String,
+}
+
+pub fn archive_stream(
+    items: Vec<ArchiveItem>,
+    resolver: PathResolver,
+    format: ArchiveFormat,
+    level: Option<i32>,
 ) -> impl Stream<Item = std::io::Result<bytes::Bytes>> {
-    
+
     let (w, r) = tokio::io::duplex(BUFFER_SIZE);
 
-    tokio::spawn(async move {
-        if let Err(e) = zip_directory(w, &root_path, &dir_name).await {
-            tracing::error!("Error comprimiendo el directorio: {:?}", e);
-        }
-    });
+    let task = tokio::spawn(async move { write_archive(w, &items, &resolver, format, level).await });
+
+    // Al acabar los datos, el resultado de la tarea decide si el cuerpo
+    // termina bien o con error
+    let outcome = stream::once(async move {
+        let error = match task.await {
+            Ok(Ok(())) => return None,
+            Ok(Err(e)) => e,
+            Err(e) => anyhow::Error::new(e),
+        };
+        tracing::error!("Error comprimiendo el directorio: {:?}", error);
+        Some(Err(std::io::Error::other(error)))
+    })
+    .filter_map(futures::future::ready);
 
-    ReaderStream::new(r)
+    ReaderStream::new(r).chain(outcome)
 }
 
-async fn zip_directory<W>(writer: W, root_path: &Path, _dir_prefix: &str) -> anyhow::Result<()>
+async fn write_archive(
+    w: DuplexStream,
+    items: &[ArchiveItem],
+    resolver: &PathResolver,
+    format: ArchiveFormat,
+    level: Option<i32>,
+) -> anyhow::Result<()> {
+    let level = level.map_or(Level::Default, Level::Precise);
+    match format {
+        ArchiveFormat::Store | ArchiveFormat::Zip => {
+            let compression = if format == ArchiveFormat::Store { Compression::Stored } else { Compression::Deflate };
+            let mut sink = ZipSink { writer: ZipFileWriter::with_tokio(w), compression, level };
+            walk(&mut sink, items, resolver).await?;
+            sink.writer.close().await?;
+        }
+        ArchiveFormat::Tar => write_tar(w, items, resolver).await?,
+        ArchiveFormat::TarGz => write_tar(GzipEncoder::with_quality(w, level), items, resolver).await?,
+        ArchiveFormat::TarZst => write_tar(ZstdEncoder::with_quality(w, level), items, resolver).await?,
+    }
+    Ok(())
+}
+
+async fn write_tar<W>(w: W, items: &[ArchiveItem], resolver: &PathResolver) -> anyhow::Result<()>
 where
-    W: tokio::io::AsyncWrite + Unpin + Send + 'static,
+    W: AsyncWrite + Unpin + Send + 'static,
 {
-    let mut writer = ZipFileWriter::with_tokio(writer);
-    let mut stack = vec![root_path.to_path_buf()];
-    let parent_dir = root_path.parent().unwrap_or(Path::new("/"));
-
-    while let Some(path) = stack.pop() {
-        let mut entries = tokio::fs::read_dir(&path).await?;
-
-        while let Some(entry) = entries.next_entry().await? {
-            let entry_path = entry.path();
-            let metadata = entry.metadata().await?;
-
-            if metadata.is_dir() {
-                stack.push(entry_path);
-            } else {
-                let relative_path = entry_path.strip_prefix(parent_dir)?;
-                let entry_name = relative_path.to_string_lossy().to_string();
-
-                let builder = ZipEntryBuilder::new(entry_name.into(), Compression::Deflate);
-                let mut file = tokio::fs::File::open(&entry_path).await?;
-
-                // --- SOLUCIÓN AL ERROR E0382 ---
-                
-                // 1. Obtenemos el escritor original (Futures)
-                let entry_writer = writer.write_entry_stream(builder).await?;
-                
-                // 2. Lo "envolvemos" para que funcione con Tokio
-                let mut compat_writer = entry_writer.compat_write();
-                
-                // 3. Copiamos los datos usando Tokio
-                tokio::io::copy(&mut file, &mut compat_writer).await?;
-                
-                // 4. IMPORTANTE: Recuperamos el escritor original "desenvolviendo" el wrapper
-                let entry_writer = compat_writer.into_inner();
-                
-                // 5. Ahora sí podemos cerrarlo porque recuperamos la propiedad (ownership)
-                entry_writer.close().await?;
+    let mut sink = TarSink { builder: tokio_tar::Builder::new(w) };
+    walk(&mut sink, items, resolver).await?;
+    // El compresor escribe su cola al cerrar
+    sink.builder.into_inner().await?.shutdown().await?;
+    Ok(())
+}
+
+/// Destino de lo que encuentra el recorrido.
+trait ArchiveSink {
+    async fn add_dir(&mut self, name: &str, path: &Path, metadata: &Metadata) -> anyhow::Result<()>;
+    async fn add_file(&mut self, name: &str, path: &Path, metadata: &Metadata) -> anyhow::Result<()>;
+}
+
+struct ZipSink {
+    writer: ZipFileWriter<DuplexStream>,
+    compression: Compression,
+    level: Level,
+}
+
+impl ArchiveSink for ZipSink {
+    async fn add_dir(&mut self, name: &str, _path: &Path, metadata: &Metadata) -> anyhow::Result<()> {
+        let builder = ZipEntryBuilder::new(format!("{}/", name).into(), Compression::Stored)
+            .last_modification_date(zip_datetime(metadata))
+            .unix_permissions(unix_mode(metadata) as u16);
+        self.writer.write_entry_whole(builder, &[]).await?;
+        Ok(())
+    }
+
+    async fn add_file(&mut self, name: &str, path: &Path, metadata: &Metadata) -> anyhow::Result<()> {
+        let option = match self.level {
+            Level::Precise(level) => DeflateOption::Other(level),
+            _ => DeflateOption::Normal,
+        };
+        let builder = ZipEntryBuilder::new(name.to_string().into(), self.compression)
+            .deflate_option(option)
+            .last_modification_date(zip_datetime(metadata))
+            .unix_permissions(unix_mode(metadata) as u16);
+        let mut file = tokio::fs::File::open(path).await?;
+
+        // --- SOLUCIÓN AL ERROR E0382 ---
+
+        // 1. Obtenemos el escritor original (Futures)
+        let entry_writer = self.writer.write_entry_stream(builder).await?;
+
+        // 2. Lo "envolvemos" para que funcione con Tokio
+        let mut compat_writer = entry_writer.compat_write();
+
+        // 3. Copiamos los datos usando Tokio
+        tokio::io::copy(&mut file, &mut compat_writer).await?;
+
+        // 4. IMPORTANTE: Recuperamos el escritor original "desenvolviendo" el wrapper
+        let entry_writer = compat_writer.into_inner();
+
+        // 5. Ahora sí podemos cerrarlo porque recuperamos la propiedad (ownership)
+        entry_writer.close().await?;
+        Ok(())
+    }
+}
+
+/// Fecha de modificación en UTC, dentro del rango que admite el formato MS-DOS.
+fn zip_datetime(metadata: &Metadata) -> ZipDateTime {
+    let (year, month, day, hour, minute, second) = utc_datetime(metadata.modified().unwrap_or(UNIX_EPOCH));
+    let (year, month, day, hour, minute, second) = match year {
+        ..1980 => (1980, 1, 1, 0, 0, 0),
+        2108.. => (2107, 12, 31, 23, 59, 58),
+        _ => (year, month, day, hour, minute, second),
+    };
+    ZipDateTimeBuilder::new()
+        .year(year as i32)
+        .month(month)
+        .day(day)
+        .hour(hour)
+        .minute(minute)
+        .second(second)
+        .build()
+}
+
+struct TarSink<W: AsyncWrite + Unpin + Send + 'static> {
+    builder: tokio_tar::Builder<W>,
+}
+
+impl<W: AsyncWrite + Unpin + Send + 'static> ArchiveSink for TarSink<W> {
+    // `append_dir`/`append_file` ya copian mtime y modo (HeaderMode::Complete)
+    async fn add_dir(&mut self, name: &str, path: &Path, _metadata: &Metadata) -> anyhow::Result<()> {
+        self.builder.append_dir(name, path).await?;
+        Ok(())
+    }
+
+    async fn add_file(&mut self, name: &str, path: &Path, _metadata: &Metadata) -> anyhow::Result<()> {
+        let mut file = tokio::fs::File::open(path).await?;
+        self.builder.append_file(name, &mut file).await?;
+        Ok(())
+    }
+}
+
+/// Pasa cada elemento de `items` al `sink`; las carpetas se recorren y su
+/// contenido (también las subcarpetas vacías) queda bajo el nombre de la
+/// carpeta (`fotos/2024/a.jpg`).
+async fn walk<S: ArchiveSink>(sink: &mut S, items: &[ArchiveItem], resolver: &PathResolver) -> anyhow::Result<()> {
+    for item in items {
+        let metadata = tokio::fs::metadata(&item.source).await?;
+        if !metadata.is_dir() {
+            sink.add_file(&item.name, &item.source, &metadata).await?;
+            continue;
+        }
+
+        sink.add_dir(&item.name, &item.source, &metadata).await?;
+        let mut walker = TreeWalker::new(&item.source, &item.name, resolver).await?;
+        while let Some(entry) = walker.next().await? {
+            if entry.metadata.is_dir() {
+                sink.add_dir(&entry.name, &entry.path, &entry.metadata).await?;
+            } else if entry.metadata.is_file() {
+                sink.add_file(&entry.name, &entry.path, &entry.metadata).await?;
             }
+            // FIFOs, sockets, dispositivos: abrirlos podría bloquear la descarga
         }
     }
-
-    writer.close().await?;
     Ok(())
-}
\ No newline at end of file
+}
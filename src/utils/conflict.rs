@@ -0,0 +1,218 @@
+// src/utils/conflict.rs
+
+//! Qué hacer cuando un archivo subido se llama igual que uno que ya existe.
+//!
+//! El destino se reserva con `create_new` (atómico en el sistema de archivos)
+//! antes de renombrar encima el temporal ya completo, así dos subidas
+//! simultáneas con el mismo nombre nunca se pisan entre sí.
+
+use crate::{error::AppError, trash::Trash, utils::paths};
+use serde::{Deserialize, Serialize};
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+/// Límite de "(n)" que se prueban antes de rendirse con un 409.
+const MAX_RENAMES: u32 = 10_000;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictPolicy {
+    /// Guardar como `nombre(1).ext`, `nombre(2).ext`...
+    #[default]
+    Rename,
+    /// Reemplazar el existente (que va a la papelera si está activa)
+    Overwrite,
+    /// Descartar la subida y conservar el existente
+    Skip,
+    /// Rechazar la subida con 409 Conflict
+    Fail,
+}
+
+impl ConflictPolicy {
+    /// Valor de `?on_conflict=` o de los metadatos de tus.
+    pub fn parse(value: &str) -> Option<Self> {
+        <Self as clap::ValueEnum>::from_str(value, true).ok()
+    }
+}
+
+/// Cómo acabó cada archivo.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Created,
+    Renamed,
+    Overwritten,
+    Skipped,
+}
+
+impl Outcome {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Created => "created",
+            Self::Renamed => "renamed",
+            Self::Overwritten => "overwritten",
+            Self::Skipped => "skipped",
+        }
+    }
+}
+
+pub struct Placed {
+    /// Ruta final (o la del archivo que se conservó, si se omitió la subida)
+    pub path: PathBuf,
+    /// La misma, relativa a la carpeta compartida y con `/` inicial
+    pub relative: String,
+    pub outcome: Outcome,
+}
+
+/// Contexto para publicar subidas: la política y, para `overwrite`, a dónde
+/// mandar la versión anterior.
+pub struct Placement<'a> {
+    pub policy: ConflictPolicy,
+    pub trash: Option<&'a Trash>,
+    pub base_path: &'a Path,
+    pub user: Option<&'a str>,
+}
+
+impl Placement<'_> {
+    /// Mueve `source` (un temporal completo en la misma partición) a `dir/name`.
+    /// Con `Skipped` el temporal queda intacto y lo limpia quien llama.
+    pub async fn place(&self, source: &Path, dir: &Path, name: &str) -> Result<Placed, AppError> {
+        let (path, outcome) = self.place_as(source, dir, name).await?;
+        Ok(Placed { relative: self.relative(&path), path, outcome })
+    }
+
+    async fn place_as(&self, source: &Path, dir: &Path, name: &str) -> Result<(PathBuf, Outcome), AppError> {
+        let dest = dir.join(name);
+        match self.policy {
+            ConflictPolicy::Rename => {
+                let mut candidate = dest;
+                let mut counter = 0;
+                while !reserve(&candidate).await? {
+                    counter += 1;
+                    if counter > MAX_RENAMES {
+                        return Err(AppError::Conflict);
+                    }
+                    candidate = dir.join(numbered(name, counter));
+                }
+                fill(source, &candidate).await?;
+                let outcome = if counter == 0 { Outcome::Created } else { Outcome::Renamed };
+                Ok((candidate, outcome))
+            }
+            ConflictPolicy::Skip | ConflictPolicy::Fail => {
+                if !reserve(&dest).await? {
+                    if self.policy == ConflictPolicy::Fail {
+                        return Err(AppError::Conflict);
+                    }
+                    return Ok((dest, Outcome::Skipped));
+                }
+                fill(source, &dest).await?;
+                Ok((dest, Outcome::Created))
+            }
+            ConflictPolicy::Overwrite => {
+                let outcome = match tokio::fs::symlink_metadata(&dest).await {
+                    Ok(metadata) if metadata.is_dir() => return Err(AppError::Conflict),
+                    Ok(_) => {
+                        self.retire(&dest).await?;
+                        Outcome::Overwritten
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => Outcome::Created,
+                    Err(e) => return Err(e.into()),
+                };
+                // rename reemplaza de forma atómica lo que haya en ese momento
+                tokio::fs::rename(source, &dest).await?;
+                Ok((dest, outcome))
+            }
+        }
+    }
+
+    /// Manda a la papelera la versión que se va a reemplazar.
+    async fn retire(&self, dest: &Path) -> Result<(), AppError> {
+        let Some(trash) = self.trash else {
+            return Ok(());
+        };
+        let original = self.relative(dest);
+        match trash.put(dest, original, self.user.map(str::to_string)).await {
+            // Otro proceso se adelantó y ya no está: nada que guardar
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            result => result.map(|_| ()).map_err(AppError::from),
+        }
+    }
+
+    /// Ruta relativa a la carpeta compartida, con `/` inicial.
+    fn relative(&self, path: &Path) -> String {
+        let relative = path.strip_prefix(self.base_path).unwrap_or(path);
+        format!("/{}", paths::display_path(relative))
+    }
+}
+
+/// Crea el destino vacío si no existe; `false` si ya estaba ocupado.
+async fn reserve(path: &Path) -> Result<bool, AppError> {
+    match tokio::fs::OpenOptions::new().write(true).create_new(true).open(path).await {
+        Ok(_) => Ok(true),
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Renombra `source` encima del destino reservado. Si falla (otra partición,
+/// permisos, disco lleno...), borra la reserva para no dejar un archivo vacío
+/// con el nombre del usuario.
+async fn fill(source: &Path, reserved: &Path) -> Result<(), AppError> {
+    if let Err(e) = tokio::fs::rename(source, reserved).await {
+        let _ = tokio::fs::remove_file(reserved).await;
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+/// `informe.pdf` -> `informe(2).pdf`
+fn numbered(file_name: &str, counter: u32) -> String {
+    let path = Path::new(file_name);
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or(file_name);
+    match path.extension().and_then(|e| e.to_str()) {
+        Some(extension) => format!("{}({}).{}", stem, counter, extension),
+        None => format!("{}({})", stem, counter),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn placement(base_path: &Path, policy: ConflictPolicy) -> Placement<'_> {
+        Placement { policy, trash: None, base_path, user: None }
+    }
+
+    #[tokio::test]
+    async fn renames_next_to_existing_file() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::write(tmp.path().join("a.txt"), "old").unwrap();
+        let source = tmp.path().join("upload");
+        std::fs::write(&source, "new").unwrap();
+
+        let placed = placement(tmp.path(), ConflictPolicy::Rename).place(&source, tmp.path(), "a.txt").await.unwrap();
+        assert_eq!(placed.outcome, Outcome::Renamed);
+        assert_eq!(placed.relative, "/a(1).txt");
+        assert_eq!(std::fs::read_to_string(tmp.path().join("a(1).txt")).unwrap(), "new");
+        assert_eq!(std::fs::read_to_string(tmp.path().join("a.txt")).unwrap(), "old");
+    }
+
+    #[tokio::test]
+    async fn failed_rename_removes_reservation() {
+        let tmp = tempfile::tempdir().unwrap();
+        std::fs::write(tmp.path().join("a.txt"), "old").unwrap();
+        // El temporal no existe: el rename falla después de reservar
+        let source = tmp.path().join("missing");
+
+        for policy in [ConflictPolicy::Rename, ConflictPolicy::Skip, ConflictPolicy::Fail] {
+            let placement = placement(tmp.path(), policy);
+            assert!(placement.place(&source, tmp.path(), "b.txt").await.is_err());
+            assert!(!tmp.path().join("b.txt").exists(), "{:?}", policy);
+        }
+        assert!(placement(tmp.path(), ConflictPolicy::Rename).place(&source, tmp.path(), "a.txt").await.is_err());
+        assert!(!tmp.path().join("a(1).txt").exists());
+        assert_eq!(std::fs::read_to_string(tmp.path().join("a.txt")).unwrap(), "old");
+    }
+}
@@ -1,51 +1,197 @@
+use crate::config::Features;
+use super::listing::{FileEntry, Page, Sort, SortKey};
+use super::zip_plan::utc_datetime;
 use humansize::{format_size, DECIMAL};
+use std::time::{Duration, UNIX_EPOCH};
 
 pub fn generate_file_list_html(
-    entries: Vec<(String, bool, u64)>, // (name, is_dir, size)
+    page: &Page,
+    limit: usize,
     current_path: &str,
     max_upload_size: u64,
+    features: &Features,
 ) -> String {
     let parent_link = if current_path.is_empty() {
         String::new()
     } else {
-        format!(r#"<a href="../" class="back">⬅ Subir un nivel</a>"#)
+        r#"<a href="../" class="back">⬅ Subir un nivel</a>"#.to_string()
     };
 
     let mut list_items = String::new();
     let current_url_path = if current_path.is_empty() { 
         String::new() 
     } else { 
-        format!("{}/", current_path.trim_end_matches('/')) 
+        format!("{}/", encode_path(current_path.trim_end_matches('/'))) 
     };
 
-    for (name, is_dir, size) in entries {
-        if is_dir {
-            let browse_link = format!("/list/{}{}", current_url_path, name);
-            let zip_link = format!("/download/{}{}", current_url_path, name);
+    // Los nombres vienen del disco: escapados en el HTML y codificados en los enlaces
+    for entry in &page.entries {
+        let name = escape_html(&entry.name);
+        let url_name = encode_path(&entry.name);
+        let symlink = if entry.is_symlink { " ↪" } else { "" };
+        if entry.is_dir {
+            let browse_link = format!("/list/{}{}", current_url_path, url_name);
+            let zip_link = if features.zip {
+                format!(r#"<a href="/download/{}{}" class="action-link">⬇ ZIP</a>"#, current_url_path, url_name)
+            } else {
+                String::new()
+            };
+            let children = entry.children.map(|n| format!("{} elementos", n)).unwrap_or_default();
             list_items.push_str(&format!(
                 r#"<li>
                     <div class="file-row">
-                        <a href="{}" class="file-link">📁 {}/</a>
-                        <a href="{}" class="action-link">⬇ ZIP</a>
+                        <a href="{}" class="file-link">📁 {}/{}</a>
+                        <span class="meta">{}</span>
+                        <span class="meta">{}</span>
+                        {}
                     </div>
                    </li>"#, 
-                browse_link, name, zip_link
+                browse_link, name, symlink, children, format_date(entry), zip_link
             ));
         } else {
-            let link = format!("/download/{}{}", current_url_path, name);
-            let size_str = format_size(size, DECIMAL);
+            let link = format!("/download/{}{}", current_url_path, url_name);
+            let size_str = format_size(entry.size, DECIMAL);
             list_items.push_str(&format!(
                 r#"<li>
                     <div class="file-row">
-                        <a href="{}" class="file-link">📄 {}</a>
+                        <a href="{}" class="file-link" title="{}">📄 {}{}</a>
+                        <span class="meta">{}</span>
                         <span class="meta">{}</span>
                     </div>
                    </li>"#, 
-                link, name, size_str
+                link, escape_html(entry.mime.as_deref().unwrap_or("")), name, symlink, size_str, format_date(entry)
             ));
         }
     }
 
+    // Orden: el criterio activo cambia de sentido al pulsarlo otra vez
+    let sort_links = [(SortKey::Name, "Nombre"), (SortKey::Size, "Tamaño"), (SortKey::Mtime, "Fecha")]
+        .into_iter()
+        .map(|(key, label)| {
+            let active = page.sort.key == key;
+            let next = Sort { key, descending: active && !page.sort.descending };
+            let arrow = match (active, page.sort.descending) {
+                (false, _) => "",
+                (true, false) => " ▲",
+                (true, true) => " ▼",
+            };
+            format!(r#"<a href="{}" class="action-link">{}{}</a>"#, legacy_url(next, 0, limit), label, arrow)
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    // Paginación por desplazamiento (enlaces que se pueden guardar)
+    let end = page.offset + page.entries.len();
+    let mut pager = String::new();
+    if page.offset > 0 {
+        let previous = page.offset.saturating_sub(limit);
+        pager.push_str(&format!(r#"<a href="{}" class="action-link">« Anteriores</a> "#, legacy_url(page.sort, previous, limit)));
+    }
+    if page.total > 0 {
+        pager.push_str(&format!(r#"<span class="meta">{}–{} de {}</span>"#, page.offset + 1, end, page.total));
+    }
+    if end < page.total {
+        pager.push_str(&format!(r#" <a href="{}" class="action-link">Siguientes »</a>"#, legacy_url(page.sort, end, limit)));
+    }
+
+    // Zona de subida (sólo si el servidor admite subidas)
+    let (upload_zone, upload_script) = if features.upload {
+        let zone = format!(r#"
+            <div id="drop-zone">
+                <p>Arrastra archivos o carpetas aquí o haz clic para subir</p>
+                <p style="font-size: 0.8em; margin-top: 0.5rem;">Máximo: {}</p>
+                <input type="file" id="file-input" multiple style="display: none">
+                <div id="progress">
+                    <div class="bar"><div class="fill" id="progress-fill"></div></div>
+                    <p id="status-text" style="font-size: 0.8em; margin-top: 0.5rem;">Subiendo...</p>
+                </div>
+            </div>
+        "#, format_size(max_upload_size, DECIMAL));
+
+        let script = format!(r#"
+            <script src="/assets/js/upload.js"></script>
+            <script>
+                const dropZone = document.getElementById('drop-zone');
+                const fileInput = document.getElementById('file-input');
+                const progress = document.getElementById('progress');
+                const progressFill = document.getElementById('progress-fill');
+                const statusText = document.getElementById('status-text');
+                const currentPath = {};
+
+                // Click to upload
+                dropZone.addEventListener('click', () => fileInput.click());
+                fileInput.addEventListener('change', (e) => handleFiles(e.target.files));
+
+                // Drag & Drop
+                dropZone.addEventListener('dragover', (e) => {{
+                    e.preventDefault();
+                    dropZone.classList.add('dragover');
+                }});
+                dropZone.addEventListener('dragleave', () => dropZone.classList.remove('dragover'));
+                dropZone.addEventListener('drop', async (e) => {{
+                    e.preventDefault();
+                    dropZone.classList.remove('dragover');
+                    // Las carpetas arrastradas se suben con su estructura
+                    handleFiles(await filesFromDataTransfer(e.dataTransfer));
+                }});
+
+                async function handleFiles(files) {{
+                    if (files.length === 0) return;
+
+                    progress.style.display = 'block';
+                    let uploaded = 0;
+                    let total = files.length;
+
+                    for (let file of files) {{
+                        statusText.textContent = `Subiendo ${{uploadName(file)}}...`;
+
+                        try {{
+                            // Archivos grandes: subida reanudable (tus), ver /assets/js/upload.js
+                            await uploadFile(file, currentPath || '/', (sent, size) => {{
+                                progressFill.style.width = `${{((uploaded + sent / size) / total) * 100}}%`;
+                            }});
+                            uploaded++;
+                            progressFill.style.width = `${{(uploaded / total) * 100}}%`;
+                        }} catch (err) {{
+                            console.error(err);
+                            alert(`Error subiendo ${{file.name}}`);
+                        }}
+                    }}
+
+                    statusText.textContent = '¡Completado! Recargando...';
+                    setTimeout(() => window.location.reload(), 500);
+                }}
+            </script>
+        "#, script_json(&current_path));
+
+        (zone, script)
+    } else {
+        (String::new(), String::new())
+    };
+
+    // Recarga la página cuando cambia la carpeta (`/api/events`), salvo en plena subida
+    let live_script = if features.live_updates {
+        format!(r#"
+            <script>
+                (() => {{
+                    const events = new EventSource('/api/events?path=' + encodeURIComponent({}));
+                    let timer = null;
+                    const reload = () => {{
+                        clearTimeout(timer);
+                        timer = setTimeout(() => {{
+                            const progress = document.getElementById('progress');
+                            if (!progress || progress.style.display !== 'block') window.location.reload();
+                        }}, 300);
+                    }};
+                    events.addEventListener('change', reload);
+                    events.addEventListener('resync', reload);
+                }})();
+            </script>
+        "#, script_json(&format!("/{}", current_path.trim_matches('/'))))
+    } else {
+        String::new()
+    };
+
     format!(r#"
         <!DOCTYPE html>
         <html>
@@ -76,6 +222,7 @@ pub fn generate_file_list_html(
                 .meta {{ color: var(--text-muted); font-size: 0.85em; margin-left: 1rem; }}
                 .back {{ display: inline-block; margin-bottom: 1rem; text-decoration: none; color: var(--text-muted); font-weight: 500; }}
                 .back:hover {{ color: var(--primary); }}
+                .toolbar {{ display: flex; align-items: center; gap: 0.5rem; color: var(--text-muted); font-size: 0.9em; }}
 
                 /* Upload Zone */
                 #drop-zone {{
@@ -101,79 +248,63 @@ pub fn generate_file_list_html(
         <body>
             <h1>Local Share</h1>
             
-            <div id="drop-zone">
-                <p>Arrastra archivos aquí o haz clic para subir</p>
-                <p style="font-size: 0.8em; margin-top: 0.5rem;">Máximo: {}</p>
-                <input type="file" id="file-input" multiple style="display: none">
-                <div id="progress">
-                    <div class="bar"><div class="fill" id="progress-fill"></div></div>
-                    <p id="status-text" style="font-size: 0.8em; margin-top: 0.5rem;">Subiendo...</p>
-                </div>
-            </div>
-
             {}
+            {}
+            <p class="toolbar">Ordenar: {}</p>
             <ul>
                 {}
             </ul>
+            <p class="toolbar">{}</p>
 
-            <script>
-                const dropZone = document.getElementById('drop-zone');
-                const fileInput = document.getElementById('file-input');
-                const progress = document.getElementById('progress');
-                const progressFill = document.getElementById('progress-fill');
-                const statusText = document.getElementById('status-text');
-                const currentPath = "{}";
-
-                // Click to upload
-                dropZone.addEventListener('click', () => fileInput.click());
-                fileInput.addEventListener('change', (e) => handleFiles(e.target.files));
-
-                // Drag & Drop
-                dropZone.addEventListener('dragover', (e) => {{
-                    e.preventDefault();
-                    dropZone.classList.add('dragover');
-                }});
-                dropZone.addEventListener('dragleave', () => dropZone.classList.remove('dragover'));
-                dropZone.addEventListener('drop', (e) => {{
-                    e.preventDefault();
-                    dropZone.classList.remove('dragover');
-                    handleFiles(e.dataTransfer.files);
-                }});
-
-                async function handleFiles(files) {{
-                    if (files.length === 0) return;
+{}{}        </body>
+        </html>
+    "#, upload_zone, parent_link, sort_links, list_items, pager, upload_script, live_script)
+}
 
-                    progress.style.display = 'block';
-                    let uploaded = 0;
-                    let total = files.length;
+/// Enlace relativo a esta misma carpeta en modo legacy.
+fn legacy_url(sort: Sort, offset: usize, limit: usize) -> String {
+    format!("?mode=legacy&sort={}&order={}&offset={}&limit={}", sort.key.as_str(), sort.order(), offset, limit)
+}
 
-                    for (let file of files) {{
-                        statusText.textContent = `Subiendo ${{file.name}}...`;
-                        const formData = new FormData();
-                        formData.append('file', file);
+/// Fecha de modificación en UTC (`2024-03-05 17:20`).
+fn format_date(entry: &FileEntry) -> String {
+    let Some(modified) = entry.modified else {
+        return String::new();
+    };
+    let (year, month, day, hour, minute, _) = utc_datetime(UNIX_EPOCH + Duration::from_secs(modified));
+    format!("{:04}-{:02}-{:02} {:02}:{:02} UTC", year, month, day, hour, minute)
+}
 
-                        try {{
-                            // Usamos encodeURIComponent para manejar espacios y caracteres especiales en la ruta
-                            const pathParam = currentPath ? `?path=${{encodeURIComponent(currentPath)}}` : '?path=/';
-                            const response = await fetch('/upload' + pathParam, {{
-                                method: 'POST',
-                                body: formData
-                            }});
+/// Texto seguro dentro del HTML y de atributos entre comillas.
+fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
 
-                            if (!response.ok) throw new Error('Error en subida');
-                            uploaded++;
-                            progressFill.style.width = `${{(uploaded / total) * 100}}%`;
-                        }} catch (err) {{
-                            console.error(err);
-                            alert(`Error subiendo ${{file.name}}`);
-                        }}
-                    }}
+/// Ruta con cada componente codificado para un `href` (`a b#1` → `a%20b%231`).
+fn encode_path(path: &str) -> String {
+    let mut out = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => out.push(byte as char),
+            byte => out.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    out
+}
 
-                    statusText.textContent = '¡Completado! Recargando...';
-                    setTimeout(() => window.location.reload(), 500);
-                }}
-            </script>
-        </body>
-        </html>
-    "#, format_size(max_upload_size, DECIMAL), parent_link, list_items, current_path)
+/// JSON para incrustar en un `<script>`: sin `<` literales, así ningún valor
+/// puede cerrar la etiqueta (`</script>`) ni abrir un comentario.
+pub fn script_json<T: serde::Serialize + ?Sized>(value: &T) -> String {
+    serde_json::to_string(value).unwrap_or_default().replace('<', "\\u003c")
 }
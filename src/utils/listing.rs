@@ -0,0 +1,289 @@
+// src/utils/listing.rs
+
+//! Listado de una carpeta: metadatos de cada entrada, orden natural
+//! ("foto2" antes que "foto10") y paginación.
+//!
+//! Las páginas se piden por desplazamiento (`offset`) o con un cursor opaco
+//! que guarda la clave de orden de la última entrada devuelta. Con el cursor,
+//! la página siguiente empieza justo después de esa entrada aunque entretanto
+//! se hayan creado o borrado archivos antes de ella.
+
+use super::paths::PathResolver;
+use base64::{engine::general_purpose, Engine as _};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::io;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Criterio de orden (`?sort=`). Las carpetas van siempre primero.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortKey {
+    #[default]
+    Name,
+    Size,
+    Mtime,
+}
+
+impl SortKey {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "name" => Some(Self::Name),
+            "size" => Some(Self::Size),
+            "mtime" | "modified" | "date" => Some(Self::Mtime),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Name => "name",
+            Self::Size => "size",
+            Self::Mtime => "mtime",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Sort {
+    pub key: SortKey,
+    pub descending: bool,
+}
+
+impl Sort {
+    /// `order` admite `asc` y `desc`; sin valor, ascendente.
+    pub fn parse(sort: Option<&str>, order: Option<&str>) -> Option<Self> {
+        let key = match sort {
+            Some(sort) => SortKey::parse(sort)?,
+            None => SortKey::default(),
+        };
+        let descending = match order.map(str::to_ascii_lowercase).as_deref() {
+            None | Some("asc") => false,
+            Some("desc") => true,
+            _ => return None,
+        };
+        Some(Self { key, descending })
+    }
+
+    pub fn order(self) -> &'static str {
+        if self.descending { "desc" } else { "asc" }
+    }
+
+    fn compare(self, a: &SortFields, b: &SortFields) -> Ordering {
+        let by_key = match self.key {
+            SortKey::Name => Ordering::Equal,
+            SortKey::Size => a.size.cmp(&b.size),
+            SortKey::Mtime => a.modified.cmp(&b.modified),
+        };
+        let by_key = by_key.then_with(|| natural_cmp(a.name, b.name));
+        let by_key = if self.descending { by_key.reverse() } else { by_key };
+        b.is_dir.cmp(&a.is_dir).then(by_key)
+    }
+}
+
+#[derive(Serialize)]
+pub struct FileEntry {
+    pub name: String,
+    /// Del destino, si la entrada es un enlace que se puede seguir
+    pub is_dir: bool,
+    pub size: u64,
+    /// Segundos Unix
+    pub modified: Option<u64>,
+    pub created: Option<u64>,
+    /// Deducido de la extensión; sólo archivos
+    pub mime: Option<String>,
+    pub extension: Option<String>,
+    pub is_symlink: bool,
+    /// Entradas visibles dentro de la carpeta; sólo en las de la página pedida
+    pub children: Option<u64>,
+}
+
+impl FileEntry {
+    fn fields(&self) -> SortFields<'_> {
+        SortFields { is_dir: self.is_dir, name: &self.name, size: self.size, modified: self.modified.unwrap_or(0) }
+    }
+}
+
+/// Lo que decide la posición de una entrada en el listado.
+struct SortFields<'a> {
+    is_dir: bool,
+    name: &'a str,
+    size: u64,
+    modified: u64,
+}
+
+/// Contenido del cursor antes de codificarlo: el orden y la última entrada vista.
+#[derive(Serialize, Deserialize)]
+struct CursorData {
+    sort: Sort,
+    is_dir: bool,
+    name: String,
+    size: u64,
+    modified: u64,
+}
+
+/// Una página del listado ya ordenado.
+pub struct Page {
+    pub entries: Vec<FileEntry>,
+    pub sort: Sort,
+    /// Entradas visibles en la carpeta
+    pub total: usize,
+    /// Posición de la primera entrada de la página
+    pub offset: usize,
+    /// Para pedir la página siguiente (`?cursor=`), si la hay
+    pub next_cursor: Option<String>,
+}
+
+/// Dónde empieza la página.
+pub enum Start<'a> {
+    Offset(usize),
+    /// Cursor devuelto en una página anterior; su orden manda sobre `?sort=`
+    Cursor(&'a str),
+}
+
+/// Lee las entradas visibles de `dir` (sin ocultas ni carpetas internas).
+pub async fn read_entries(dir: &Path, resolver: &PathResolver) -> io::Result<Vec<FileEntry>> {
+    let mut entries = tokio::fs::read_dir(dir).await?;
+    let mut items = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        // Ignorar archivos ocultos unix
+        if name.starts_with('.') || resolver.is_internal(&entry.path()) {
+            continue;
+        }
+
+        let mut metadata = entry.metadata().await?;
+        let is_symlink = metadata.file_type().is_symlink();
+        if is_symlink && resolver.symlink_allowed(&entry.path()).await {
+            // Los enlaces rotos se listan con los datos del propio enlace
+            if let Ok(target) = tokio::fs::metadata(entry.path()).await {
+                metadata = target;
+            }
+        }
+
+        let is_dir = metadata.is_dir();
+        let extension = if is_dir {
+            None
+        } else {
+            Path::new(&name).extension().map(|ext| ext.to_string_lossy().to_lowercase())
+        };
+        let mime = if is_dir { None } else { mime_guess::from_path(&name).first().map(|mime| mime.to_string()) };
+
+        items.push(FileEntry {
+            is_dir,
+            size: if is_dir { 0 } else { metadata.len() },
+            modified: metadata.modified().ok().and_then(unix_seconds),
+            created: metadata.created().ok().and_then(unix_seconds),
+            mime,
+            extension,
+            is_symlink,
+            children: None,
+            name,
+        });
+    }
+    Ok(items)
+}
+
+/// Ordena `entries` y devuelve la página pedida. Sin `limit`, hasta el final.
+pub fn paginate(mut entries: Vec<FileEntry>, sort: Sort, start: Start, limit: Option<usize>) -> Option<Page> {
+    let cursor = match start {
+        Start::Offset(_) => None,
+        Start::Cursor(cursor) => Some(decode_cursor(cursor)?),
+    };
+    let sort = cursor.as_ref().map_or(sort, |cursor| cursor.sort);
+    entries.sort_by(|a, b| sort.compare(&a.fields(), &b.fields()));
+
+    let total = entries.len();
+    let offset = match (cursor, start) {
+        (Some(cursor), _) => {
+            let last = SortFields { is_dir: cursor.is_dir, name: &cursor.name, size: cursor.size, modified: cursor.modified };
+            entries.partition_point(|entry| sort.compare(&entry.fields(), &last) != Ordering::Greater)
+        }
+        (None, Start::Offset(offset)) => offset.min(total),
+        (None, Start::Cursor(_)) => 0,
+    };
+    let end = limit.map_or(total, |limit| offset.saturating_add(limit.max(1)).min(total));
+
+    let next_cursor = (end < total).then(|| encode_cursor(sort, &entries[end - 1]));
+    let entries = entries.drain(offset..end).collect();
+    Some(Page { entries, sort, total, offset, next_cursor })
+}
+
+/// Rellena `children` en las carpetas (sólo las de la página: contar es leer
+/// la carpeta entera).
+pub async fn count_children(dir: &Path, entries: &mut [FileEntry]) {
+    for entry in entries.iter_mut().filter(|entry| entry.is_dir) {
+        let Ok(mut children) = tokio::fs::read_dir(dir.join(&entry.name)).await else {
+            continue;
+        };
+        let mut count = 0;
+        while let Ok(Some(child)) = children.next_entry().await {
+            if !child.file_name().to_string_lossy().starts_with('.') {
+                count += 1;
+            }
+        }
+        entry.children = Some(count);
+    }
+}
+
+fn encode_cursor(sort: Sort, last: &FileEntry) -> String {
+    let data = CursorData {
+        sort,
+        is_dir: last.is_dir,
+        name: last.name.clone(),
+        size: last.size,
+        modified: last.modified.unwrap_or(0),
+    };
+    general_purpose::URL_SAFE_NO_PAD.encode(serde_json::to_vec(&data).unwrap_or_default())
+}
+
+fn decode_cursor(cursor: &str) -> Option<CursorData> {
+    let bytes = general_purpose::URL_SAFE_NO_PAD.decode(cursor).ok()?;
+    serde_json::from_slice(&bytes).ok()
+}
+
+pub fn unix_seconds(time: SystemTime) -> Option<u64> {
+    time.duration_since(UNIX_EPOCH).ok().map(|d| d.as_secs())
+}
+
+/// Comparación "natural": las series de dígitos se comparan por su valor y el
+/// resto sin distinguir mayúsculas. Con empate, decide el orden de bytes.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut x, mut y) = (a.chars().peekable(), b.chars().peekable());
+    loop {
+        match (x.peek().copied(), y.peek().copied()) {
+            (None, None) => return a.cmp(b),
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(c), Some(d)) if c.is_ascii_digit() && d.is_ascii_digit() => {
+                let m = take_number(&mut x);
+                let n = take_number(&mut y);
+                // Sin ceros a la izquierda, más dígitos es un número mayor
+                let ordering = m.len().cmp(&n.len()).then_with(|| m.cmp(&n));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(c), Some(d)) => {
+                let ordering = c.to_lowercase().cmp(d.to_lowercase());
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+                x.next();
+                y.next();
+            }
+        }
+    }
+}
+
+/// Consume una serie de dígitos y la devuelve sin ceros a la izquierda.
+fn take_number(chars: &mut std::iter::Peekable<std::str::Chars>) -> String {
+    let mut digits = String::new();
+    while let Some(c) = chars.next_if(char::is_ascii_digit) {
+        if !(digits.is_empty() && c == '0') {
+            digits.push(c);
+        }
+    }
+    digits
+}
@@ -18,7 +18,6 @@ pub fn register_service(
     tls: bool,
 ) -> MdnsGuard {
     // Clone values to move into the async block
-    let port = port;
     let name = instance_name.to_string();
     
     // Create the daemon
@@ -1,5 +1,10 @@
 pub mod archiver;
+pub mod conflict;
 pub mod html;
+pub mod listing;
+pub mod paths;
+pub mod walk;
+pub mod zip_plan;
 
 pub mod net;
 pub mod qr;
@@ -0,0 +1,360 @@
+// src/utils/paths.rs
+
+//! Resolución segura de rutas pedidas por el cliente.
+//!
+//! Todas las rutas llegan ya percent-decoded (las decodifica el extractor de
+//! axum). Aquí se normalizan componente a componente, se resuelven bajo la
+//! carpeta compartida y se aplica la política de enlaces simbólicos, de modo
+//! que ninguna ruta resuelta pueda salir de `root` salvo con `--symlinks follow`.
+
+use crate::error::AppError;
+use std::path::{Component, Path, PathBuf};
+
+/// Carpeta oculta donde esperan las subidas reanudables a medias.
+pub const STAGING_DIR: &str = ".local-share-uploads";
+
+/// Carpeta oculta de la papelera.
+pub const TRASH_DIR: &str = ".local-share-trash";
+
+/// Carpetas internas del servidor que nunca se exponen por HTTP.
+const RESERVED_DIRS: &[&str] = &[STAGING_DIR, TRASH_DIR];
+
+/// Qué hacer cuando una ruta atraviesa un enlace simbólico.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SymlinkPolicy {
+    /// Seguir cualquier enlace, aunque apunte fuera de la carpeta compartida
+    Follow,
+    /// Seguir enlaces sólo si su destino sigue dentro de la carpeta compartida
+    #[default]
+    WithinRoot,
+    /// No seguir ningún enlace
+    Deny,
+}
+
+#[derive(Clone, Debug)]
+pub struct PathResolver {
+    root: PathBuf,
+    policy: SymlinkPolicy,
+}
+
+impl PathResolver {
+    /// `root` debe estar ya canonicalizada (lo hace `start_server`).
+    pub fn new(root: PathBuf, policy: SymlinkPolicy) -> Self {
+        Self { root, policy }
+    }
+
+    /// Resuelve una ruta existente dentro de la carpeta compartida.
+    pub async fn resolve(&self, request_path: &str) -> Result<PathBuf, AppError> {
+        let relative = normalize(request_path)?;
+
+        let mut current = self.root.clone();
+        for component in relative.components() {
+            current.push(component);
+
+            let metadata = tokio::fs::symlink_metadata(&current).await?;
+            if metadata.file_type().is_symlink() && !self.symlink_allowed(&current).await {
+                tracing::warn!("Acceso denegado a través de enlace simbólico: {:?}", current);
+                return Err(AppError::PermissionDenied);
+            }
+        }
+
+        // Defensa en profundidad: la ruta final (enlaces resueltos) debe seguir bajo root
+        if self.policy != SymlinkPolicy::Follow {
+            let canonical = tokio::fs::canonicalize(&current).await?;
+            if !canonical.starts_with(&self.root) {
+                return Err(AppError::PermissionDenied);
+            }
+        }
+
+        Ok(current)
+    }
+
+    /// Como [`resolve`](Self::resolve), pero la ruta debe ser una carpeta.
+    pub async fn resolve_dir(&self, request_path: &str) -> Result<PathBuf, AppError> {
+        let path = self.resolve(request_path).await?;
+        if !tokio::fs::metadata(&path).await?.is_dir() {
+            return Err(AppError::NotFound);
+        }
+        Ok(path)
+    }
+
+    /// Destino de una operación que crea algo nuevo (mkdir, mover, copiar): la
+    /// carpeta padre debe existir y resolverse con las mismas reglas, y el
+    /// nombre final no puede estar ocupado.
+    pub async fn resolve_new(&self, request_path: &str) -> Result<PathBuf, AppError> {
+        let relative = normalize(request_path)?;
+        let name = relative.file_name().ok_or(AppError::InvalidPath)?;
+        let parent = relative.parent().map(display_path).unwrap_or_default();
+
+        let target = self.resolve_dir(&parent).await?.join(name);
+        if tokio::fs::symlink_metadata(&target).await.is_ok() {
+            return Err(AppError::Conflict);
+        }
+        Ok(target)
+    }
+
+    /// Como [`resolve_dir`](Self::resolve_dir), pero crea las carpetas que falten
+    /// por el camino (restaurar desde la papelera a una carpeta ya borrada).
+    pub async fn create_dir_all(&self, request_path: &str) -> Result<PathBuf, AppError> {
+        let relative = normalize(request_path)?;
+
+        let mut current = self.root.clone();
+        let mut walked = PathBuf::new();
+        for component in relative.components() {
+            walked.push(component);
+            match tokio::fs::symlink_metadata(current.join(component)).await {
+                // Lo que ya existe se resuelve con las reglas de siempre
+                Ok(_) => current = self.resolve_dir(&display_path(&walked)).await?,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    current.push(component);
+                    match tokio::fs::create_dir(&current).await {
+                        Err(e) if e.kind() != std::io::ErrorKind::AlreadyExists => return Err(e.into()),
+                        _ => {}
+                    }
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(current)
+    }
+
+    /// Carpetas internas del servidor en la raíz compartida, que los recorridos
+    /// (ZIP, copias...) deben saltarse.
+    pub fn is_internal(&self, path: &Path) -> bool {
+        path.parent() == Some(self.root.as_path())
+            && path.file_name().and_then(|n| n.to_str()).is_some_and(|n| RESERVED_DIRS.contains(&n))
+    }
+
+    /// Indica si un enlace simbólico encontrado al recorrer el árbol puede seguirse.
+    pub async fn symlink_allowed(&self, link: &Path) -> bool {
+        match self.policy {
+            SymlinkPolicy::Follow => true,
+            SymlinkPolicy::Deny => false,
+            SymlinkPolicy::WithinRoot => match tokio::fs::canonicalize(link).await {
+                Ok(target) => target.starts_with(&self.root),
+                Err(_) => false,
+            },
+        }
+    }
+}
+
+/// Convierte la ruta del cliente en una ruta relativa limpia: sin raíz, sin `.`,
+/// con los `..` aplicados y sin poder subir por encima de la carpeta compartida.
+/// Los nombres con puntos seguidos (`notes..txt`) son válidos.
+pub fn normalize(request_path: &str) -> Result<PathBuf, AppError> {
+    if request_path.contains('\0') {
+        return Err(AppError::InvalidPath);
+    }
+
+    let mut parts: Vec<&str> = Vec::new();
+    for part in request_path.split(['/', '\\']) {
+        match part {
+            "" | "." => {}
+            ".." => {
+                if parts.pop().is_none() {
+                    return Err(AppError::InvalidPath);
+                }
+            }
+            name => parts.push(name),
+        }
+    }
+
+    if parts.first().is_some_and(|first| RESERVED_DIRS.contains(first)) {
+        return Err(AppError::NotFound);
+    }
+
+    let relative: PathBuf = parts.iter().collect();
+
+    // Prefijos de unidad (`C:`) u otros componentes raros en Windows
+    if !relative.components().all(|c| matches!(c, Component::Normal(_))) {
+        return Err(AppError::InvalidPath);
+    }
+
+    Ok(relative)
+}
+
+/// Destino de un archivo subido con su ruta relativa (`fotos/2024/a.jpg`, de
+/// `webkitRelativePath`) dentro de la carpeta `target`: devuelve la carpeta
+/// (relativa a la raíz compartida) y el nombre final. La ruta no puede salir
+/// de `target`.
+pub fn upload_destination(target: &str, uploaded_name: &str) -> Result<(String, String), AppError> {
+    let target = normalize(target)?;
+    let relative = normalize(&format!("{}/{}", display_path(&target), uploaded_name))?;
+    if !relative.starts_with(&target) {
+        return Err(AppError::InvalidPath);
+    }
+    // Sin nombre utilizable (campo vacío, sólo `.`): el nombre genérico de siempre
+    if relative == target {
+        return Ok((display_path(&target), "uploaded_file".to_string()));
+    }
+
+    let file_name = relative
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or(AppError::InvalidPath)?
+        .to_string();
+    let dir = relative.parent().map(display_path).unwrap_or_default();
+    Ok((dir, file_name))
+}
+
+/// Ruta relativa normalizada con separadores `/`, tal como se muestra en la UI.
+pub fn display_path(relative: &Path) -> String {
+    relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invalid(path: &str) -> bool {
+        matches!(normalize(path), Err(AppError::InvalidPath))
+    }
+
+    #[test]
+    fn dot_dot_cannot_leave_the_root() {
+        assert!(invalid(".."));
+        assert!(invalid("../etc/passwd"));
+        assert!(invalid("docs/../../etc/passwd"));
+        assert!(invalid("docs/./../.."));
+        // Dentro de la carpeta compartida sí se aplican
+        assert_eq!(normalize("docs/../fotos/./a.jpg").unwrap(), PathBuf::from("fotos/a.jpg"));
+        assert_eq!(normalize("docs/..").unwrap(), PathBuf::new());
+    }
+
+    #[test]
+    fn encoded_dots_are_plain_names() {
+        // axum ya decodifica: un `%2e%2e` que llega aquí es un nombre literal
+        assert_eq!(normalize("%2e%2e/%2e%2e/etc").unwrap(), PathBuf::from("%2e%2e/%2e%2e/etc"));
+        assert_eq!(normalize("notes..txt").unwrap(), PathBuf::from("notes..txt"));
+        assert_eq!(normalize("...").unwrap(), PathBuf::from("..."));
+    }
+
+    #[test]
+    fn absolute_paths_stay_under_the_root() {
+        assert_eq!(normalize("/etc/passwd").unwrap(), PathBuf::from("etc/passwd"));
+        assert_eq!(normalize("//etc//passwd/").unwrap(), PathBuf::from("etc/passwd"));
+        assert!(invalid("/../etc/passwd"));
+    }
+
+    #[test]
+    fn backslashes_are_separators() {
+        assert!(invalid("..\\..\\windows\\win.ini"));
+        assert!(invalid("docs\\..\\..\\x"));
+        assert_eq!(normalize("docs\\a.txt").unwrap(), PathBuf::from("docs/a.txt"));
+    }
+
+    #[test]
+    fn nul_bytes_are_rejected() {
+        assert!(invalid("a.txt\0.jpg"));
+        assert!(invalid("\0"));
+    }
+
+    #[test]
+    fn reserved_dirs_are_hidden_at_the_root() {
+        assert!(matches!(normalize(".local-share-trash"), Err(AppError::NotFound)));
+        assert!(matches!(normalize("/.local-share-uploads/x.part"), Err(AppError::NotFound)));
+        assert!(matches!(normalize("docs/../.local-share-trash/x"), Err(AppError::NotFound)));
+        // Más abajo sólo son carpetas con un nombre curioso
+        assert!(normalize("docs/.local-share-trash").is_ok());
+
+        let resolver = PathResolver::new(PathBuf::from("/srv/share"), SymlinkPolicy::WithinRoot);
+        assert!(resolver.is_internal(Path::new("/srv/share/.local-share-trash")));
+        assert!(resolver.is_internal(Path::new("/srv/share/.local-share-uploads")));
+        assert!(!resolver.is_internal(Path::new("/srv/share/docs/.local-share-trash")));
+        assert!(!resolver.is_internal(Path::new("/srv/share/.local-share-other")));
+    }
+
+    #[test]
+    fn upload_destination_stays_in_target() {
+        assert_eq!(upload_destination("docs", "fotos/2024/a.jpg").unwrap(), ("docs/fotos/2024".into(), "a.jpg".into()));
+        assert_eq!(upload_destination("", "a.jpg").unwrap(), (String::new(), "a.jpg".into()));
+        assert_eq!(upload_destination("docs", ".").unwrap(), ("docs".into(), "uploaded_file".into()));
+        assert!(matches!(upload_destination("docs", "../otra/a.jpg"), Err(AppError::InvalidPath)));
+        assert!(matches!(upload_destination("docs", "../../etc/passwd"), Err(AppError::InvalidPath)));
+        assert!(matches!(upload_destination("docs", "..\\..\\a.jpg"), Err(AppError::InvalidPath)));
+        assert!(matches!(upload_destination("", "a\0.jpg"), Err(AppError::InvalidPath)));
+        assert!(matches!(upload_destination("", ".local-share-trash/a.jpg"), Err(AppError::NotFound)));
+    }
+
+    /// `<tmp>/root` con `sub/a.txt`, un enlace interno (`inside` → `sub`), uno
+    /// que sale (`outside` → `<tmp>/secret`) y uno roto (`dangling`).
+    #[cfg(unix)]
+    fn symlink_tree() -> (tempfile::TempDir, PathBuf) {
+        use std::os::unix::fs::symlink;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let base = tmp.path().canonicalize().unwrap();
+        let root = base.join("root");
+        std::fs::create_dir_all(root.join("sub")).unwrap();
+        std::fs::write(root.join("sub/a.txt"), "a").unwrap();
+        std::fs::create_dir(base.join("secret")).unwrap();
+        std::fs::write(base.join("secret/key.txt"), "secreto").unwrap();
+        symlink(root.join("sub"), root.join("inside")).unwrap();
+        symlink(base.join("secret"), root.join("outside")).unwrap();
+        symlink(base.join("secret/key.txt"), root.join("key.txt")).unwrap();
+        symlink(root.join("missing"), root.join("dangling")).unwrap();
+        (tmp, root)
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn escaping_symlinks_follow_the_policy() {
+        let (_tmp, root) = symlink_tree();
+        let resolver = |policy| PathResolver::new(root.clone(), policy);
+
+        let follow = resolver(SymlinkPolicy::Follow);
+        assert_eq!(follow.resolve("outside/key.txt").await.unwrap(), root.join("outside/key.txt"));
+        assert!(follow.resolve("key.txt").await.is_ok());
+        assert!(follow.resolve("inside/a.txt").await.is_ok());
+        assert!(follow.symlink_allowed(&root.join("outside")).await);
+
+        let within = resolver(SymlinkPolicy::WithinRoot);
+        assert!(matches!(within.resolve("outside/key.txt").await, Err(AppError::PermissionDenied)));
+        assert!(matches!(within.resolve("outside").await, Err(AppError::PermissionDenied)));
+        assert!(matches!(within.resolve("key.txt").await, Err(AppError::PermissionDenied)));
+        assert!(matches!(within.resolve_dir("outside").await, Err(AppError::PermissionDenied)));
+        assert!(within.resolve("inside/a.txt").await.is_ok());
+        assert!(!within.symlink_allowed(&root.join("outside")).await);
+        assert!(within.symlink_allowed(&root.join("inside")).await);
+
+        let deny = resolver(SymlinkPolicy::Deny);
+        assert!(matches!(deny.resolve("outside/key.txt").await, Err(AppError::PermissionDenied)));
+        assert!(matches!(deny.resolve("inside/a.txt").await, Err(AppError::PermissionDenied)));
+        assert!(deny.resolve("sub/a.txt").await.is_ok());
+        assert!(!deny.symlink_allowed(&root.join("inside")).await);
+
+        // `..` tras un enlace no sirve para salir: se normaliza antes de tocar el disco
+        for policy in [SymlinkPolicy::Follow, SymlinkPolicy::WithinRoot, SymlinkPolicy::Deny] {
+            assert!(matches!(resolver(policy).resolve("outside/../../secret/key.txt").await, Err(AppError::InvalidPath)));
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn resolve_new_rejects_escaping_and_dangling_parents() {
+        let (_tmp, root) = symlink_tree();
+
+        for policy in [SymlinkPolicy::Follow, SymlinkPolicy::WithinRoot, SymlinkPolicy::Deny] {
+            let resolver = PathResolver::new(root.clone(), policy);
+            // Padre roto: no hay dónde crear nada
+            assert!(resolver.resolve_new("dangling/new.txt").await.is_err());
+            assert!(!root.join("missing").exists());
+            // El enlace roto ocupa su nombre
+            assert!(matches!(resolver.resolve_new("dangling").await, Err(AppError::Conflict)));
+            assert!(matches!(resolver.resolve_new("../new.txt").await, Err(AppError::InvalidPath)));
+            assert!(matches!(resolver.resolve_new("").await, Err(AppError::InvalidPath)));
+        }
+
+        let within = PathResolver::new(root.clone(), SymlinkPolicy::WithinRoot);
+        assert!(matches!(within.resolve_new("outside/new.txt").await, Err(AppError::PermissionDenied)));
+        assert_eq!(within.resolve_new("inside/new.txt").await.unwrap(), root.join("inside/new.txt"));
+
+        let follow = PathResolver::new(root.clone(), SymlinkPolicy::Follow);
+        assert_eq!(follow.resolve_new("outside/new.txt").await.unwrap(), root.join("outside/new.txt"));
+    }
+}
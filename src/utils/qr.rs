@@ -27,7 +27,7 @@ pub fn generate_qr_image(content: &str) -> Result<(u32, u32, Vec<u8>), String> {
     let width = (logical_width + 2 * quiet_zone) * scale;
     let height = width; // QR codes are square
     
-    let mut buffer = vec![255u8; (width * height * 3) as usize]; // White background (255)
+    let mut buffer = vec![255u8; width * height * 3]; // White background (255)
     
     // Function to set a pixel (x, y) to color (r, g, b)
     let set_pixel = |buf: &mut Vec<u8>, x: usize, y: usize, r: u8, g: u8, b: u8| {
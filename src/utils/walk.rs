@@ -0,0 +1,138 @@
+// src/utils/walk.rs
+
+//! Recorrido de un árbol de carpetas para generar ZIPs y tars (y para buscar).
+//!
+//! Se saltan las carpetas internas del servidor y los enlaces que no permite
+//! la política. Los enlaces a carpetas permitidos se siguen, pero sólo si su
+//! destino no es la propia carpeta ni una antecesora en el recorrido (ciclos
+//! como `a/b/enlace -> a`). Los enlaces rotos se omiten.
+
+use super::paths::PathResolver;
+use std::fs::Metadata;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// `st_mode` completo (tipo y permisos) para guardarlo en el archivo. Fuera
+/// de Unix se deduce del tipo y del atributo de sólo lectura.
+pub fn unix_mode(metadata: &Metadata) -> u32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        metadata.mode()
+    }
+    #[cfg(not(unix))]
+    {
+        if metadata.is_dir() {
+            0o40755
+        } else if metadata.permissions().readonly() {
+            0o100444
+        } else {
+            0o100644
+        }
+    }
+}
+
+pub struct WalkEntry {
+    pub path: PathBuf,
+    /// Ruta dentro del archivo, bajo el nombre de la raíz (`fotos/2024/a.jpg`)
+    pub name: String,
+    /// Del destino, si la entrada es un enlace
+    pub metadata: Metadata,
+}
+
+struct PendingDir {
+    path: PathBuf,
+    name: String,
+    /// Niveles por debajo de la raíz (0: la raíz)
+    depth: usize,
+    /// Rutas reales de esta carpeta y de sus antecesoras en el recorrido
+    chain: Vec<PathBuf>,
+}
+
+/// Recorrido en profundidad; cada carpeta sale antes que su contenido.
+pub struct TreeWalker<'a> {
+    resolver: &'a PathResolver,
+    stack: Vec<PendingDir>,
+    current: Option<(tokio::fs::ReadDir, PendingDir)>,
+    skip_hidden: bool,
+    max_depth: Option<usize>,
+}
+
+impl<'a> TreeWalker<'a> {
+    /// `root` es una carpeta ya resuelta; ella misma no se devuelve.
+    pub async fn new(root: &Path, name: &str, resolver: &'a PathResolver) -> io::Result<Self> {
+        let real = tokio::fs::canonicalize(root).await?;
+        let root = PendingDir { path: root.to_path_buf(), name: name.to_string(), depth: 0, chain: vec![real] };
+        Ok(Self { resolver, stack: vec![root], current: None, skip_hidden: false, max_depth: None })
+    }
+
+    /// Sin entradas ocultas (`.algo`) ni su contenido, como el listado.
+    pub fn skip_hidden(mut self) -> Self {
+        self.skip_hidden = true;
+        self
+    }
+
+    /// Hasta `depth` niveles por debajo de la raíz (1: sólo su contenido).
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    pub async fn next(&mut self) -> io::Result<Option<WalkEntry>> {
+        loop {
+            let Some((entries, dir)) = self.current.as_mut() else {
+                let Some(dir) = self.stack.pop() else {
+                    return Ok(None);
+                };
+                self.current = Some((tokio::fs::read_dir(&dir.path).await?, dir));
+                continue;
+            };
+            let Some(entry) = entries.next_entry().await? else {
+                self.current = None;
+                continue;
+            };
+
+            let path = entry.path();
+            if self.resolver.is_internal(&path) {
+                continue; // subidas a medias, papelera...
+            }
+            let file_name = entry.file_name();
+            if self.skip_hidden && file_name.to_string_lossy().starts_with('.') {
+                continue;
+            }
+            let name = format!("{}/{}", dir.name, file_name.to_string_lossy());
+
+            let mut metadata = entry.metadata().await?;
+            let mut real = None;
+            if metadata.file_type().is_symlink() {
+                if !self.resolver.symlink_allowed(&path).await {
+                    tracing::debug!("Omitiendo enlace simbólico no permitido: {:?}", path);
+                    continue;
+                }
+                metadata = match tokio::fs::metadata(&path).await {
+                    Ok(metadata) => metadata,
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => continue, // enlace roto
+                    Err(e) => return Err(e),
+                };
+                if metadata.is_dir() {
+                    let target = tokio::fs::canonicalize(&path).await?;
+                    if dir.chain.iter().any(|ancestor| ancestor.starts_with(&target)) {
+                        tracing::warn!("Omitiendo enlace simbólico que forma un ciclo: {:?}", path);
+                        continue;
+                    }
+                    real = Some(target);
+                }
+            }
+
+            let depth = dir.depth + 1;
+            if metadata.is_dir() && self.max_depth.is_none_or(|max| depth < max) {
+                let mut chain = dir.chain.clone();
+                let parent_real = chain.last().cloned().unwrap_or_default();
+                chain.push(real.unwrap_or_else(|| parent_real.join(&file_name)));
+                self.stack.push(PendingDir { path: path.clone(), name: name.clone(), depth, chain });
+            }
+
+            return Ok(Some(WalkEntry { path, name, metadata }));
+        }
+    }
+}
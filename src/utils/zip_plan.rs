@@ -0,0 +1,624 @@
+// src/utils/zip_plan.rs
+
+//! ZIP "virtual" en modo stored (sin compresión) cuyo tamaño se conoce antes de
+//! leer un solo byte de los archivos. Como cada byte del archivo final tiene una
+//! posición determinista, podemos anunciar `Content-Length`, `ETag` y servir
+//! rangos arbitrarios (`Range: bytes=...`) para reanudar descargas de carpetas.
+//!
+//! Los local headers usan el bit 3 (data descriptor), así que el CRC32 de cada
+//! archivo sólo hace falta en el descriptor y en el directorio central. Los CRC
+//! se calculan al vuelo y se guardan en un [`CrcCache`] para que las reanudaciones
+//! no tengan que releer lo que ya se descargó.
+//!
+//! Cada entrada lleva su modo Unix en los atributos externos y la fecha de
+//! modificación tanto en formato MS-DOS como en el extra "extended timestamp"
+//! (0x5455), que los descompresores usan para restaurarla al segundo y en UTC.
+
+use super::paths::PathResolver;
+use super::walk::{unix_mode, TreeWalker, WalkEntry};
+use futures::stream::{self, Stream, StreamExt};
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWrite, AsyncWriteExt};
+use tokio_util::io::ReaderStream;
+
+const BUFFER_SIZE: usize = 64 * 1024;
+const ZIP64_LIMIT: u64 = 0xFFFF_FFFF;
+
+const LOCAL_HEADER_SIG: u32 = 0x0403_4b50;
+const DATA_DESCRIPTOR_SIG: u32 = 0x0807_4b50;
+const CENTRAL_HEADER_SIG: u32 = 0x0201_4b50;
+const ZIP64_EOCD_SIG: u32 = 0x0606_4b50;
+const ZIP64_LOCATOR_SIG: u32 = 0x0706_4b50;
+const EOCD_SIG: u32 = 0x0605_4b50;
+
+// Extra "extended timestamp": cabecera (4) + flags (1) + mtime (4)
+const EXTENDED_TIMESTAMP_ID: u16 = 0x5455;
+const EXTENDED_TIMESTAMP_LEN: u16 = 9;
+
+// Bit 3: sizes/CRC en el data descriptor. Bit 11: nombres en UTF-8.
+const FLAG_DATA_DESCRIPTOR: u16 = 0x0008;
+const FLAG_UTF8: u16 = 0x0800;
+// Creado en Unix (3), especificación 4.5 (ZIP64)
+const VERSION_MADE_BY: u16 = (3 << 8) | 45;
+
+/// Entradas que guarda la [`CrcCache`]; al pasarse se descartan las menos usadas.
+const CRC_CACHE_CAPACITY: usize = 50_000;
+
+/// Cache de CRC32 por archivo, invalidada por tamaño y fecha de modificación.
+/// Está acotada: al llenarse se descarta la décima parte usada hace más tiempo.
+#[derive(Default)]
+pub struct CrcCache {
+    entries: Mutex<CrcEntries>,
+}
+
+#[derive(Default)]
+struct CrcEntries {
+    /// Ruta → (tamaño, mtime, CRC, último uso)
+    map: HashMap<PathBuf, (u64, SystemTime, u32, u64)>,
+    /// Reloj lógico para el último uso
+    tick: u64,
+}
+
+impl CrcCache {
+    fn get(&self, path: &Path, size: u64, mtime: SystemTime) -> Option<u32> {
+        let mut entries = self.entries.lock().expect("Lock poisoned");
+        entries.tick += 1;
+        let tick = entries.tick;
+        match entries.map.get_mut(path) {
+            Some((s, m, crc, used)) if *s == size && *m == mtime => {
+                *used = tick;
+                Some(*crc)
+            }
+            // El archivo ha cambiado: el CRC guardado ya no sirve
+            Some(_) => {
+                entries.map.remove(path);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn insert(&self, path: PathBuf, size: u64, mtime: SystemTime, crc: u32) {
+        let mut entries = self.entries.lock().expect("Lock poisoned");
+        entries.tick += 1;
+        let tick = entries.tick;
+        entries.map.insert(path, (size, mtime, crc, tick));
+        if entries.map.len() > CRC_CACHE_CAPACITY {
+            entries.evict(CRC_CACHE_CAPACITY / 10);
+        }
+    }
+}
+
+impl CrcEntries {
+    /// Descarta las `count` entradas usadas hace más tiempo.
+    fn evict(&mut self, count: usize) {
+        let mut used: Vec<u64> = self.map.values().map(|&(_, _, _, used)| used).collect();
+        let count = count.min(used.len());
+        if count == 0 {
+            return;
+        }
+        let (_, &mut threshold, _) = used.select_nth_unstable(count - 1);
+        self.map.retain(|_, &mut (_, _, _, used)| used > threshold);
+    }
+}
+
+struct PlannedEntry {
+    source: PathBuf,
+    name: String,
+    is_dir: bool,
+    size: u64,
+    mtime: SystemTime,
+    /// `st_mode` completo (tipo y permisos)
+    mode: u32,
+    offset: u64,
+}
+
+impl PlannedEntry {
+    /// El local header lleva el extra ZIP64 y el descriptor usa campos de 8 bytes.
+    fn needs_zip64_data(&self) -> bool {
+        self.size >= ZIP64_LIMIT
+    }
+
+    fn version_needed(&self) -> u16 {
+        if self.needs_zip64_data() || self.offset >= ZIP64_LIMIT {
+            45
+        } else {
+            20
+        }
+    }
+
+    fn flags(&self) -> u16 {
+        if self.is_dir {
+            FLAG_UTF8
+        } else {
+            FLAG_UTF8 | FLAG_DATA_DESCRIPTOR
+        }
+    }
+
+    fn external_attributes(&self) -> u32 {
+        // Bit 4: atributo MS-DOS de carpeta
+        (self.mode << 16) | if self.is_dir { 0x10 } else { 0 }
+    }
+
+    fn descriptor_len(&self) -> u64 {
+        if self.needs_zip64_data() {
+            24
+        } else {
+            16
+        }
+    }
+}
+
+enum Segment {
+    Static(Vec<u8>),
+    Data(usize),
+    Descriptor(usize),
+    CentralDirectory,
+}
+
+struct Span {
+    start: u64,
+    len: u64,
+    segment: Segment,
+}
+
+/// Disposición completa de un ZIP stored generado a partir de un directorio.
+pub struct ZipPlan {
+    entries: Vec<PlannedEntry>,
+    spans: Vec<Span>,
+    total_size: u64,
+    etag: String,
+}
+
+impl ZipPlan {
+    /// Recorre `root_path` y calcula la posición de cada byte del ZIP resultante.
+    /// Los nombres internos quedan bajo `dir_name/` igual que en el ZIP comprimido.
+    pub async fn build(root_path: &Path, dir_name: &str, resolver: &PathResolver) -> io::Result<Self> {
+        let root_meta = tokio::fs::metadata(root_path).await?;
+        let mut entries = vec![PlannedEntry {
+            source: root_path.to_path_buf(),
+            name: format!("{}/", dir_name),
+            is_dir: true,
+            size: 0,
+            mtime: root_meta.modified().unwrap_or(UNIX_EPOCH),
+            mode: unix_mode(&root_meta),
+            offset: 0,
+        }];
+
+        let mut walker = TreeWalker::new(root_path, dir_name, resolver).await?;
+        while let Some(WalkEntry { path, name, metadata }) = walker.next().await? {
+            let mtime = metadata.modified().unwrap_or(UNIX_EPOCH);
+            let mode = unix_mode(&metadata);
+            if metadata.is_dir() {
+                entries.push(PlannedEntry {
+                    source: path,
+                    name: format!("{}/", name),
+                    is_dir: true,
+                    size: 0,
+                    mtime,
+                    mode,
+                    offset: 0,
+                });
+            } else if metadata.is_file() {
+                entries.push(PlannedEntry {
+                    source: path,
+                    name,
+                    is_dir: false,
+                    size: metadata.len(),
+                    mtime,
+                    mode,
+                    offset: 0,
+                });
+            }
+        }
+
+        // El orden del recorrido depende del sistema de archivos; ordenamos para
+        // que dos planes del mismo árbol produzcan exactamente los mismos bytes.
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(Self::layout(entries))
+    }
+
+    fn layout(mut entries: Vec<PlannedEntry>) -> Self {
+        let mut spans = Vec::with_capacity(entries.len() * 3 + 2);
+        let mut offset = 0u64;
+        let mut hasher = crc32fast::Hasher::new();
+
+        for (idx, entry) in entries.iter_mut().enumerate() {
+            entry.offset = offset;
+
+            hasher.update(entry.name.as_bytes());
+            hasher.update(&entry.size.to_le_bytes());
+            hasher.update(&entry.mode.to_le_bytes());
+            if let Ok(d) = entry.mtime.duration_since(UNIX_EPOCH) {
+                hasher.update(&d.as_nanos().to_le_bytes());
+            }
+
+            let header = local_header(entry);
+            offset = push_span(&mut spans, offset, header.len() as u64, Segment::Static(header));
+            if !entry.is_dir {
+                offset = push_span(&mut spans, offset, entry.size, Segment::Data(idx));
+                offset = push_span(&mut spans, offset, entry.descriptor_len(), Segment::Descriptor(idx));
+            }
+        }
+
+        let central_directory_offset = offset;
+        let central_directory_len: u64 = entries.iter().map(central_header_len).sum();
+        offset = push_span(&mut spans, offset, central_directory_len, Segment::CentralDirectory);
+
+        let end = end_records(entries.len() as u64, central_directory_offset, central_directory_len);
+        offset = push_span(&mut spans, offset, end.len() as u64, Segment::Static(end));
+
+        let etag = format!("\"zip-{:08x}-{:x}\"", hasher.finalize(), offset);
+
+        Self {
+            entries,
+            spans,
+            total_size: offset,
+            etag,
+        }
+    }
+
+    pub fn total_size(&self) -> u64 {
+        self.total_size
+    }
+
+    pub fn etag(&self) -> &str {
+        &self.etag
+    }
+
+    /// Devuelve los bytes `start..=end` del ZIP como un stream. El trabajo no
+    /// empieza hasta que alguien consume el stream (por ejemplo, no en `HEAD`).
+    pub fn stream_range(
+        self: Arc<Self>,
+        cache: Arc<CrcCache>,
+        start: u64,
+        end: u64,
+    ) -> impl Stream<Item = io::Result<bytes::Bytes>> {
+        stream::once(async move {
+            let (w, r) = tokio::io::duplex(BUFFER_SIZE);
+
+            let task = tokio::spawn(async move { self.write_range(&cache, w, start, end).await });
+
+            // Un fallo a medias corta el cuerpo con error: el cliente no debe
+            // quedarse con un ZIP truncado que parece completo
+            let outcome = stream::once(async move {
+                let error = match task.await {
+                    Ok(Ok(())) => return None,
+                    Ok(Err(e)) => e,
+                    Err(e) => io::Error::other(e),
+                };
+                tracing::error!("Error generando el ZIP: {:?}", error);
+                Some(Err(error))
+            })
+            .filter_map(futures::future::ready);
+
+            ReaderStream::new(r).chain(outcome)
+        })
+        .flatten()
+    }
+
+    async fn write_range<W>(&self, cache: &CrcCache, mut w: W, start: u64, end: u64) -> io::Result<()>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let first = self.spans.partition_point(|s| s.start + s.len <= start);
+
+        for span in &self.spans[first..] {
+            if span.start > end {
+                break;
+            }
+            let from = start.saturating_sub(span.start);
+            let to = span.len.min(end + 1 - span.start);
+            if from >= to {
+                continue;
+            }
+
+            match &span.segment {
+                Segment::Static(bytes) => {
+                    w.write_all(&bytes[from as usize..to as usize]).await?;
+                }
+                Segment::Data(idx) => {
+                    self.write_data(cache, &mut w, &self.entries[*idx], from, to).await?;
+                }
+                Segment::Descriptor(idx) => {
+                    let entry = &self.entries[*idx];
+                    let crc = file_crc(cache, entry).await?;
+                    let bytes = data_descriptor(entry, crc);
+                    w.write_all(&bytes[from as usize..to as usize]).await?;
+                }
+                Segment::CentralDirectory => {
+                    let bytes = self.central_directory(cache).await?;
+                    debug_assert_eq!(bytes.len() as u64, span.len);
+                    w.write_all(&bytes[from as usize..to as usize]).await?;
+                }
+            }
+        }
+
+        w.shutdown().await
+    }
+
+    async fn write_data<W>(
+        &self,
+        cache: &CrcCache,
+        w: &mut W,
+        entry: &PlannedEntry,
+        from: u64,
+        to: u64,
+    ) -> io::Result<()>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let mut file = tokio::fs::File::open(&entry.source).await?;
+        if from > 0 {
+            file.seek(io::SeekFrom::Start(from)).await?;
+        }
+
+        // Si enviamos el archivo completo aprovechamos para calcular su CRC
+        let mut hasher = (from == 0 && to == entry.size).then(crc32fast::Hasher::new);
+        let mut remaining = to - from;
+        let mut buf = vec![0u8; BUFFER_SIZE];
+
+        while remaining > 0 {
+            let want = remaining.min(BUFFER_SIZE as u64) as usize;
+            let n = file.read(&mut buf[..want]).await?;
+            if n == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!("El archivo cambió durante la descarga: {:?}", entry.source),
+                ));
+            }
+            if let Some(h) = hasher.as_mut() {
+                h.update(&buf[..n]);
+            }
+            w.write_all(&buf[..n]).await?;
+            remaining -= n as u64;
+        }
+
+        if let Some(h) = hasher {
+            cache.insert(entry.source.clone(), entry.size, entry.mtime, h.finalize());
+        }
+        Ok(())
+    }
+
+    async fn central_directory(&self, cache: &CrcCache) -> io::Result<Vec<u8>> {
+        let mut out = Vec::new();
+        for entry in &self.entries {
+            let crc = if entry.is_dir { 0 } else { file_crc(cache, entry).await? };
+            central_header(&mut out, entry, crc);
+        }
+        Ok(out)
+    }
+}
+
+fn push_span(spans: &mut Vec<Span>, start: u64, len: u64, segment: Segment) -> u64 {
+    spans.push(Span { start, len, segment });
+    start + len
+}
+
+/// CRC32 de un archivo, desde la cache o leyéndolo completo.
+async fn file_crc(cache: &CrcCache, entry: &PlannedEntry) -> io::Result<u32> {
+    if let Some(crc) = cache.get(&entry.source, entry.size, entry.mtime) {
+        return Ok(crc);
+    }
+
+    let mut file = tokio::fs::File::open(&entry.source).await?;
+    let mut hasher = crc32fast::Hasher::new();
+    let mut buf = vec![0u8; BUFFER_SIZE];
+    let mut total = 0u64;
+    loop {
+        let n = file.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+        total += n as u64;
+    }
+
+    if total != entry.size {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            format!("El archivo cambió durante la descarga: {:?}", entry.source),
+        ));
+    }
+
+    let crc = hasher.finalize();
+    cache.insert(entry.source.clone(), entry.size, entry.mtime, crc);
+    Ok(crc)
+}
+
+fn local_header(entry: &PlannedEntry) -> Vec<u8> {
+    let (time, date) = dos_datetime(entry.mtime);
+    let zip64 = entry.needs_zip64_data();
+    let mut out = Vec::with_capacity(30 + entry.name.len() + 20 + EXTENDED_TIMESTAMP_LEN as usize);
+
+    put_u32(&mut out, LOCAL_HEADER_SIG);
+    put_u16(&mut out, entry.version_needed());
+    put_u16(&mut out, entry.flags());
+    put_u16(&mut out, 0); // stored
+    put_u16(&mut out, time);
+    put_u16(&mut out, date);
+    put_u32(&mut out, 0); // CRC en el data descriptor
+    let placeholder = if zip64 { u32::MAX } else { 0 };
+    put_u32(&mut out, placeholder);
+    put_u32(&mut out, placeholder);
+    put_u16(&mut out, entry.name.len() as u16);
+    put_u16(&mut out, EXTENDED_TIMESTAMP_LEN + if zip64 { 20 } else { 0 });
+    out.extend_from_slice(entry.name.as_bytes());
+    if zip64 {
+        put_u16(&mut out, 0x0001);
+        put_u16(&mut out, 16);
+        put_u64(&mut out, 0);
+        put_u64(&mut out, 0);
+    }
+    extended_timestamp(&mut out, entry.mtime);
+    out
+}
+
+fn data_descriptor(entry: &PlannedEntry, crc: u32) -> Vec<u8> {
+    let mut out = Vec::with_capacity(24);
+    put_u32(&mut out, DATA_DESCRIPTOR_SIG);
+    put_u32(&mut out, crc);
+    if entry.needs_zip64_data() {
+        put_u64(&mut out, entry.size);
+        put_u64(&mut out, entry.size);
+    } else {
+        put_u32(&mut out, entry.size as u32);
+        put_u32(&mut out, entry.size as u32);
+    }
+    out
+}
+
+/// Campos que no caben en 32 bits y van en el extra ZIP64 del directorio central.
+fn central_zip64_fields(entry: &PlannedEntry) -> Vec<u64> {
+    let mut fields = Vec::new();
+    if entry.size >= ZIP64_LIMIT {
+        fields.push(entry.size);
+        fields.push(entry.size);
+    }
+    if entry.offset >= ZIP64_LIMIT {
+        fields.push(entry.offset);
+    }
+    fields
+}
+
+fn central_header_len(entry: &PlannedEntry) -> u64 {
+    let fields = central_zip64_fields(entry);
+    let extra = if fields.is_empty() { 0 } else { 4 + 8 * fields.len() };
+    (46 + entry.name.len() + extra + EXTENDED_TIMESTAMP_LEN as usize) as u64
+}
+
+fn central_header(out: &mut Vec<u8>, entry: &PlannedEntry, crc: u32) {
+    let (time, date) = dos_datetime(entry.mtime);
+    let fields = central_zip64_fields(entry);
+    let size32 = if entry.size >= ZIP64_LIMIT { u32::MAX } else { entry.size as u32 };
+    let offset32 = if entry.offset >= ZIP64_LIMIT { u32::MAX } else { entry.offset as u32 };
+
+    put_u32(out, CENTRAL_HEADER_SIG);
+    put_u16(out, VERSION_MADE_BY);
+    put_u16(out, entry.version_needed());
+    put_u16(out, entry.flags());
+    put_u16(out, 0);
+    put_u16(out, time);
+    put_u16(out, date);
+    put_u32(out, crc);
+    put_u32(out, size32);
+    put_u32(out, size32);
+    put_u16(out, entry.name.len() as u16);
+    let zip64_len = if fields.is_empty() { 0 } else { (4 + 8 * fields.len()) as u16 };
+    put_u16(out, zip64_len + EXTENDED_TIMESTAMP_LEN);
+    put_u16(out, 0); // comentario
+    put_u16(out, 0); // disco
+    put_u16(out, 0); // atributos internos
+    put_u32(out, entry.external_attributes());
+    put_u32(out, offset32);
+    out.extend_from_slice(entry.name.as_bytes());
+    if !fields.is_empty() {
+        put_u16(out, 0x0001);
+        put_u16(out, (8 * fields.len()) as u16);
+        for field in fields {
+            put_u64(out, field);
+        }
+    }
+    extended_timestamp(out, entry.mtime);
+}
+
+/// Extra 0x5455 sólo con la fecha de modificación (segundos Unix, UTC).
+fn extended_timestamp(out: &mut Vec<u8>, mtime: SystemTime) {
+    let secs = mtime
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+        .min(i32::MAX as u64);
+    put_u16(out, EXTENDED_TIMESTAMP_ID);
+    put_u16(out, EXTENDED_TIMESTAMP_LEN - 4);
+    out.push(0x01); // sólo mtime
+    put_u32(out, secs as u32);
+}
+
+fn end_records(count: u64, cd_offset: u64, cd_len: u64) -> Vec<u8> {
+    let mut out = Vec::new();
+    let zip64 = count >= 0xFFFF || cd_offset >= ZIP64_LIMIT || cd_len >= ZIP64_LIMIT;
+
+    if zip64 {
+        let record_offset = cd_offset + cd_len;
+        put_u32(&mut out, ZIP64_EOCD_SIG);
+        put_u64(&mut out, 44);
+        put_u16(&mut out, VERSION_MADE_BY);
+        put_u16(&mut out, 45);
+        put_u32(&mut out, 0);
+        put_u32(&mut out, 0);
+        put_u64(&mut out, count);
+        put_u64(&mut out, count);
+        put_u64(&mut out, cd_len);
+        put_u64(&mut out, cd_offset);
+
+        put_u32(&mut out, ZIP64_LOCATOR_SIG);
+        put_u32(&mut out, 0);
+        put_u64(&mut out, record_offset);
+        put_u32(&mut out, 1);
+    }
+
+    put_u32(&mut out, EOCD_SIG);
+    put_u16(&mut out, 0);
+    put_u16(&mut out, 0);
+    put_u16(&mut out, count.min(0xFFFF) as u16);
+    put_u16(&mut out, count.min(0xFFFF) as u16);
+    put_u32(&mut out, cd_len.min(ZIP64_LIMIT) as u32);
+    put_u32(&mut out, cd_offset.min(ZIP64_LIMIT) as u32);
+    put_u16(&mut out, 0);
+    out
+}
+
+/// Convierte una fecha a formato MS-DOS (resolución de 2 segundos, desde 1980).
+fn dos_datetime(time: SystemTime) -> (u16, u16) {
+    let (year, month, day, hour, minute, second) = utc_datetime(time);
+    if year < 1980 {
+        return (0, (1 << 5) | 1);
+    }
+    let year = year.min(2107);
+
+    let time = (hour << 11) | (minute << 5) | (second / 2);
+    let date = ((year - 1980) << 9) | (month << 5) | day;
+    (time as u16, date as u16)
+}
+
+/// Año, mes, día, hora, minuto y segundo en UTC. Las fechas anteriores a 1970
+/// se tratan como 1970.
+pub fn utc_datetime(time: SystemTime) -> (u32, u32, u32, u32, u32, u32) {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+
+    // Algoritmo "days from civil" inverso de Howard Hinnant
+    let days = (secs / 86_400) as i64;
+    let rem = (secs % 86_400) as u32;
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    (year as u32, month as u32, day as u32, rem / 3600, (rem % 3600) / 60, rem % 60)
+}
+
+fn put_u16(out: &mut Vec<u8>, v: u16) {
+    out.extend_from_slice(&v.to_le_bytes());
+}
+
+fn put_u32(out: &mut Vec<u8>, v: u32) {
+    out.extend_from_slice(&v.to_le_bytes());
+}
+
+fn put_u64(out: &mut Vec<u8>, v: u64) {
+    out.extend_from_slice(&v.to_le_bytes());
+}
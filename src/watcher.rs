@@ -0,0 +1,144 @@
+// Cambios en la carpeta compartida en tiempo real.
+//
+// Un único watcher (notify, recursivo) sobre `base_path` traduce los eventos
+// del sistema a `FsEvent` con rutas relativas (`/docs/a.txt`) y los reparte
+// por un canal broadcast. Cada cliente de `/api/events` se suscribe y se queda
+// sólo con los de la carpeta que está viendo.
+//
+// Las entradas ocultas (y con ellas las carpetas internas del servidor) no se
+// notifican, igual que no se listan. Un renombrado puede llegar como `renamed`
+// y además como `deleted` + `created`, según el sistema.
+
+use notify::{
+    event::{ModifyKind, RenameMode},
+    EventKind, RecommendedWatcher, RecursiveMode, Watcher,
+};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use tokio::sync::broadcast;
+
+/// Eventos en cola por cliente antes de que se le pida recargar (`resync`)
+const CHANNEL_CAPACITY: usize = 1024;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FsEventKind {
+    Created,
+    Modified,
+    Deleted,
+    Renamed,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct FsEvent {
+    pub kind: FsEventKind,
+    /// Ruta relativa a la carpeta compartida (`/docs/a.txt`)
+    pub path: String,
+    /// Carpeta que la contiene (`/docs`, `/` en la raíz)
+    pub dir: String,
+    /// Nueva ruta, en los renombrados
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to: Option<String>,
+}
+
+impl FsEvent {
+    /// El evento cambia el listado de `dir` (o afecta a la propia carpeta).
+    pub fn concerns(&self, dir: &str) -> bool {
+        self.dir == dir || self.path == dir || self.to.as_deref().is_some_and(|to| parent_of(to) == dir || to == dir)
+    }
+}
+
+pub struct DirWatcher {
+    events: broadcast::Sender<FsEvent>,
+    // Vigila mientras viva
+    _watcher: RecommendedWatcher,
+}
+
+impl DirWatcher {
+    /// `base_path` debe estar ya canonicalizada.
+    pub fn start(base_path: PathBuf) -> notify::Result<Self> {
+        let (events, _) = broadcast::channel(CHANNEL_CAPACITY);
+        let sender = events.clone();
+        let root = base_path.clone();
+
+        let mut watcher = notify::recommended_watcher(move |result: notify::Result<notify::Event>| match result {
+            Ok(event) => {
+                for event in translate(&root, event) {
+                    // Sin suscriptores no es un error: nadie está mirando
+                    let _ = sender.send(event);
+                }
+            }
+            Err(e) => tracing::warn!("Error vigilando la carpeta compartida: {}", e),
+        })?;
+        watcher.watch(&base_path, RecursiveMode::Recursive)?;
+
+        Ok(Self { events, _watcher: watcher })
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<FsEvent> {
+        self.events.subscribe()
+    }
+}
+
+fn translate(root: &Path, event: notify::Event) -> Vec<FsEvent> {
+    let kind = match event.kind {
+        EventKind::Create(_) => FsEventKind::Created,
+        EventKind::Remove(_) => FsEventKind::Deleted,
+        EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+            let (Some(from), Some(to)) = (event.paths.first(), event.paths.get(1)) else {
+                return Vec::new();
+            };
+            return match (relative(root, from), relative(root, to)) {
+                (Some(from), Some(to)) => vec![FsEvent { kind: FsEventKind::Renamed, dir: parent_of(&from), path: from, to: Some(to) }],
+                // Entra o sale de lo visible (p. ej. desde una carpeta oculta)
+                (None, Some(to)) => vec![new_event(FsEventKind::Created, to)],
+                (Some(from), None) => vec![new_event(FsEventKind::Deleted, from)],
+                (None, None) => Vec::new(),
+            };
+        }
+        EventKind::Modify(ModifyKind::Name(RenameMode::From)) => FsEventKind::Deleted,
+        EventKind::Modify(ModifyKind::Name(RenameMode::To)) => FsEventKind::Created,
+        // Sin saber el sentido del renombrado, decide si la ruta sigue existiendo
+        EventKind::Modify(ModifyKind::Name(_)) => {
+            return event
+                .paths
+                .iter()
+                .filter_map(|path| {
+                    let kind = if path.exists() { FsEventKind::Created } else { FsEventKind::Deleted };
+                    relative(root, path).map(|relative| new_event(kind, relative))
+                })
+                .collect();
+        }
+        EventKind::Modify(_) => FsEventKind::Modified,
+        // Lecturas y demás: no cambian ningún listado
+        _ => return Vec::new(),
+    };
+    event.paths.iter().filter_map(|path| relative(root, path)).map(|path| new_event(kind, path)).collect()
+}
+
+fn new_event(kind: FsEventKind, path: String) -> FsEvent {
+    FsEvent { kind, dir: parent_of(&path), path, to: None }
+}
+
+/// `/docs/a.txt` para `<root>/docs/a.txt`; `None` fuera de la raíz o si algún
+/// componente está oculto.
+fn relative(root: &Path, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(root).ok()?;
+    let mut out = String::new();
+    for component in relative.components() {
+        let name = component.as_os_str().to_string_lossy();
+        if name.starts_with('.') {
+            return None;
+        }
+        out.push('/');
+        out.push_str(&name);
+    }
+    (!out.is_empty()).then_some(out)
+}
+
+fn parent_of(path: &str) -> String {
+    match path.rfind('/') {
+        Some(0) | None => "/".to_string(),
+        Some(i) => path[..i].to_string(),
+    }
+}
@@ -0,0 +1,284 @@
+// Descarga una carpeta en cada formato contra el binario real, la descomprime
+// y compara el árbol resultante con el original.
+
+mod common;
+
+use common::Server;
+use std::collections::BTreeMap;
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Carpetas (terminadas en `/`) y archivos con su contenido.
+type Tree = BTreeMap<String, Option<Vec<u8>>>;
+
+/// `share/` con archivos, una carpeta vacía y subcarpetas anidadas.
+fn fixture(root: &Path) -> PathBuf {
+    let share = root.join("share");
+    std::fs::create_dir_all(share.join("nested/deep")).unwrap();
+    std::fs::create_dir_all(share.join("empty")).unwrap();
+    std::fs::write(share.join("a.txt"), "hola\n").unwrap();
+    std::fs::write(share.join("nested/deep/b.bin"), (0..200_000u32).map(|i| (i % 251) as u8).collect::<Vec<_>>()).unwrap();
+    std::fs::write(share.join("nested/vacío.txt"), "").unwrap();
+    share
+}
+
+fn disk_tree(dir: &Path, name: &str, tree: &mut Tree) {
+    tree.insert(format!("{}/", name), None);
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let entry = entry.unwrap();
+        let child = format!("{}/{}", name, entry.file_name().to_string_lossy());
+        let metadata = std::fs::symlink_metadata(entry.path()).unwrap();
+        if metadata.is_dir() {
+            disk_tree(&entry.path(), &child, tree);
+        } else if metadata.is_file() {
+            tree.insert(child, Some(std::fs::read(entry.path()).unwrap()));
+        }
+    }
+}
+
+fn zip_tree(data: &[u8]) -> Tree {
+    let mut archive = zip::ZipArchive::new(Cursor::new(data)).unwrap();
+    let mut tree = Tree::new();
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i).unwrap();
+        let name = file.name().to_string();
+        if file.is_dir() {
+            tree.insert(name, None);
+        } else {
+            let mut content = Vec::new();
+            file.read_to_end(&mut content).unwrap();
+            tree.insert(name, Some(content));
+        }
+    }
+    tree
+}
+
+fn tar_tree(reader: impl Read) -> Tree {
+    let mut archive = tar::Archive::new(reader);
+    let mut tree = Tree::new();
+    for entry in archive.entries().unwrap() {
+        let mut entry = entry.unwrap();
+        let name = entry.path().unwrap().to_string_lossy().into_owned();
+        if entry.header().entry_type().is_dir() {
+            tree.insert(format!("{}/", name.trim_end_matches('/')), None);
+        } else {
+            let mut content = Vec::new();
+            entry.read_to_end(&mut content).unwrap();
+            tree.insert(name, Some(content));
+        }
+    }
+    tree
+}
+
+#[test]
+fn folder_downloads_round_trip_in_every_format() {
+    let server = Server::start();
+    let share = fixture(server.root.path());
+    let mut expected = Tree::new();
+    disk_tree(&share, "share", &mut expected);
+
+    assert_eq!(zip_tree(&server.download("/download/share")), expected, "zip stored");
+    assert_eq!(zip_tree(&server.download("/download/share?format=zip&level=9")), expected, "zip deflate");
+    assert_eq!(tar_tree(Cursor::new(server.download("/download/share?format=tar"))), expected, "tar");
+    let tgz = server.download("/download/share?format=tgz");
+    assert_eq!(tar_tree(flate2::read::GzDecoder::new(Cursor::new(tgz))), expected, "tar.gz");
+}
+
+#[cfg(unix)]
+#[test]
+fn symlink_loops_are_not_followed() {
+    let server = Server::start();
+    let share = fixture(server.root.path());
+    std::os::unix::fs::symlink(&share, share.join("nested/deep/loop")).unwrap();
+    std::os::unix::fs::symlink("../a.txt", share.join("nested/link.txt")).unwrap();
+
+    let mut expected = Tree::new();
+    disk_tree(&share, "share", &mut expected);
+    // El enlace al archivo se sigue; el que vuelve a la raíz no
+    expected.insert("share/nested/link.txt".to_string(), Some(b"hola\n".to_vec()));
+
+    assert_eq!(zip_tree(&server.download("/download/share")), expected, "zip stored");
+    assert_eq!(tar_tree(Cursor::new(server.download("/download/share?format=tar"))), expected, "tar");
+}
+
+#[test]
+fn zip64_for_more_than_65535_entries() {
+    let server = Server::start();
+    let share = server.root.path().join("many");
+    std::fs::create_dir(&share).unwrap();
+    for i in 0..66_000 {
+        std::fs::write(share.join(format!("{:05}", i)), "").unwrap();
+    }
+
+    let stored = zip::ZipArchive::new(Cursor::new(server.download("/download/many"))).unwrap();
+    assert_eq!(stored.len(), 66_001);
+    let deflated = zip::ZipArchive::new(Cursor::new(server.download("/download/many?format=deflate"))).unwrap();
+    assert_eq!(deflated.len(), 66_001);
+}
+
+#[cfg(unix)]
+#[test]
+fn timestamps_and_permissions_are_preserved() {
+    use std::os::unix::fs::PermissionsExt;
+    use std::time::UNIX_EPOCH;
+
+    const MTIME: u64 = 1_000_000_000; // 2001-09-09 01:46:40 UTC
+
+    let server = Server::start();
+    let share = fixture(server.root.path());
+    std::fs::write(share.join("run.sh"), "#!/bin/sh\n").unwrap();
+    std::fs::set_permissions(share.join("run.sh"), std::fs::Permissions::from_mode(0o755)).unwrap();
+    std::fs::set_permissions(share.join("empty"), std::fs::Permissions::from_mode(0o750)).unwrap();
+    let file = std::fs::File::options().write(true).open(share.join("a.txt")).unwrap();
+    file.set_modified(UNIX_EPOCH + Duration::from_secs(MTIME)).unwrap();
+
+    for path in ["/download/share", "/download/share?format=zip&level=9"] {
+        let mut archive = zip::ZipArchive::new(Cursor::new(server.download(path))).unwrap();
+        assert_eq!(archive.by_name("share/run.sh").unwrap().unix_mode(), Some(0o100755), "{}", path);
+        assert_eq!(archive.by_name("share/empty/").unwrap().unix_mode().map(|m| m & 0o777), Some(0o750), "{}", path);
+        let entry = archive.by_name("share/a.txt").unwrap();
+        assert_eq!(entry.unix_mode(), Some(0o100644), "{}", path);
+        let modified = entry.last_modified().unwrap();
+        assert_eq!(
+            (modified.year(), modified.month(), modified.day(), modified.hour(), modified.minute(), modified.second()),
+            (2001, 9, 9, 1, 46, 40),
+            "{}",
+            path
+        );
+    }
+
+    // El ZIP reanudable lleva además la fecha exacta en el extra 0x5455
+    let mut stored = zip::ZipArchive::new(Cursor::new(server.download("/download/share"))).unwrap();
+    let entry = stored.by_name("share/a.txt").unwrap();
+    let exact = entry.extra_data_fields().find_map(|field| match field {
+        zip::extra_fields::ExtraField::ExtendedTimestamp(ts) => ts.mod_time(),
+        _ => None,
+    });
+    assert_eq!(exact, Some(MTIME as u32));
+
+    let mut archive = tar::Archive::new(Cursor::new(server.download("/download/share?format=tar")));
+    let mut seen = 0;
+    for entry in archive.entries().unwrap() {
+        let entry = entry.unwrap();
+        let name = entry.path().unwrap().to_string_lossy().into_owned();
+        let header = entry.header();
+        match name.trim_end_matches('/') {
+            "share/a.txt" => assert_eq!(header.mtime().unwrap(), MTIME),
+            "share/run.sh" => assert_eq!(header.mode().unwrap() & 0o777, 0o755),
+            "share/empty" => assert_eq!(header.mode().unwrap() & 0o777, 0o750),
+            _ => continue,
+        }
+        seen += 1;
+    }
+    assert_eq!(seen, 3);
+}
+
+/// Lee el ZIP por rangos HTTP, como un cliente que reanuda o salta al final.
+struct RangeReader {
+    url: String,
+    len: u64,
+    pos: u64,
+}
+
+impl RangeReader {
+    fn new(url: String) -> Self {
+        let response = ureq::head(&url).call().unwrap();
+        let len = response.header("Content-Length").unwrap().parse().unwrap();
+        Self { url, len, pos: 0 }
+    }
+
+    fn range(&self, start: u64, end: u64) -> Vec<u8> {
+        let response = ureq::get(&self.url).set("Range", &format!("bytes={}-{}", start, end)).call().unwrap();
+        assert_eq!(response.status(), 206);
+        let mut body = Vec::new();
+        response.into_reader().read_to_end(&mut body).unwrap();
+        body
+    }
+}
+
+impl Read for RangeReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if buf.is_empty() || self.pos >= self.len {
+            return Ok(0);
+        }
+        let end = (self.pos + buf.len() as u64).min(self.len) - 1;
+        let body = self.range(self.pos, end);
+        buf[..body.len()].copy_from_slice(&body);
+        self.pos += body.len() as u64;
+        Ok(body.len())
+    }
+}
+
+impl std::io::Seek for RangeReader {
+    fn seek(&mut self, from: std::io::SeekFrom) -> std::io::Result<u64> {
+        self.pos = match from {
+            std::io::SeekFrom::Start(n) => n,
+            std::io::SeekFrom::End(n) => self.len.checked_add_signed(n).unwrap(),
+            std::io::SeekFrom::Current(n) => self.pos.checked_add_signed(n).unwrap(),
+        };
+        Ok(self.pos)
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn zip64_for_entries_and_offsets_beyond_4_gib() {
+    const BIG: u64 = (4 << 30) + 4096;
+
+    let server = Server::start();
+    let share = server.root.path().join("big");
+    std::fs::create_dir(&share).unwrap();
+    std::fs::write(share.join("a.txt"), "antes\n").unwrap();
+    // Disperso: ocupa un bloque en disco aunque mida más de 4 GiB
+    let big = std::fs::File::create(share.join("b.bin")).unwrap();
+    big.set_len(BIG).unwrap();
+    std::os::unix::fs::FileExt::write_all_at(&big, b"fin", BIG - 3).unwrap();
+    drop(big);
+    std::fs::write(share.join("c.txt"), "después\n").unwrap();
+
+    // Sólo se descargan el directorio central y lo que se pide de cada entrada
+    let reader = std::io::BufReader::with_capacity(64 * 1024, RangeReader::new(server.url("/download/big")));
+    let mut archive = zip::ZipArchive::new(reader).unwrap();
+    assert_eq!(archive.len(), 4);
+
+    let (big_start, big_size) = {
+        let entry = archive.by_name("big/b.bin").unwrap();
+        assert!(entry.header_start() < u32::MAX as u64);
+        (entry.data_start(), entry.size())
+    };
+    assert_eq!(big_size, BIG, "tamaño ZIP64");
+
+    let entry = archive.by_name("big/c.txt").unwrap();
+    assert!(entry.header_start() > u32::MAX as u64, "desplazamiento ZIP64");
+    drop(entry);
+    let mut content = String::new();
+    archive.by_name("big/c.txt").unwrap().read_to_string(&mut content).unwrap();
+    assert_eq!(content, "después\n");
+
+    // El final del archivo grande está donde dice el directorio central
+    let tail = archive.into_inner().into_inner();
+    assert_eq!(tail.range(big_start + BIG - 3, big_start + BIG - 1), b"fin");
+}
+
+#[cfg(unix)]
+#[test]
+fn folder_names_are_escaped_in_content_disposition() {
+    let server = Server::start();
+    // Las barras invertidas ya se rechazan en las rutas
+    let name = "a\"b\nc ñ";
+    std::fs::create_dir(server.root.path().join(name)).unwrap();
+    std::fs::write(server.root.path().join(name).join("x.txt"), "x").unwrap();
+    let encoded: String = name.bytes().map(|b| format!("%{:02X}", b)).collect();
+
+    for (query, extension) in [("", "zip"), ("?format=tar", "tar")] {
+        let response = ureq::get(&server.url(&format!("/download/{}{}", encoded, query))).call().unwrap();
+        assert_eq!(
+            response.header("Content-Disposition"),
+            Some(format!("attachment; filename=\"a_b_c _.{ext}\"; filename*=UTF-8''a%22b%0Ac%20%C3%B1.{ext}", ext = extension).as_str())
+        );
+        let mut body = Vec::new();
+        response.into_reader().read_to_end(&mut body).unwrap();
+        assert!(!body.is_empty());
+    }
+}
@@ -0,0 +1,40 @@
+// El WebSocket del portapapeles sólo acepta conexiones de la propia web.
+
+mod common;
+
+use common::Server;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+
+/// Código de estado de la respuesta al handshake con el `Origin` dado.
+fn handshake(server: &Server, origin: Option<&str>) -> u16 {
+    let host = server.url("").trim_start_matches("http://").to_string();
+    let mut stream = TcpStream::connect(&host).unwrap();
+    let mut request = format!(
+        "GET /api/clipboard/ws HTTP/1.1\r\nHost: {}\r\nConnection: Upgrade\r\nUpgrade: websocket\r\n\
+         Sec-WebSocket-Version: 13\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n",
+        host
+    );
+    if let Some(origin) = origin {
+        request.push_str(&format!("Origin: {}\r\n", origin));
+    }
+    request.push_str("\r\n");
+    stream.write_all(request.as_bytes()).unwrap();
+
+    let mut status = String::new();
+    BufReader::new(stream).read_line(&mut status).unwrap();
+    status.split_whitespace().nth(1).unwrap().parse().unwrap()
+}
+
+#[test]
+fn websocket_rejects_cross_site_origins() {
+    let server = Server::start();
+    let own = server.url("");
+
+    assert_eq!(handshake(&server, Some(&own)), 101);
+    assert_eq!(handshake(&server, None), 101, "clientes que no son navegadores");
+    assert_eq!(handshake(&server, Some("http://evil.example")), 403);
+    assert_eq!(handshake(&server, Some("null")), 403);
+    // Mismo host, otro puerto: otra web
+    assert_eq!(handshake(&server, Some(&format!("{}1", own))), 403);
+}
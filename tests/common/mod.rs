@@ -0,0 +1,68 @@
+// Servidor real (el binario compilado) sobre una carpeta temporal, para las
+// pruebas de integración.
+
+// Cada archivo de pruebas compila su copia y no todos usan todo
+#![allow(dead_code)]
+
+use std::io::Read;
+use std::net::TcpListener;
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+pub struct Server {
+    child: Child,
+    port: u16,
+    pub root: tempfile::TempDir,
+    _home: tempfile::TempDir,
+}
+
+impl Server {
+    pub fn start() -> Self {
+        let root = tempfile::tempdir().unwrap();
+        let home = tempfile::tempdir().unwrap();
+        let config = home.path().join("config.toml");
+        std::fs::write(&config, "[profiles.default.features]\nmdns = false\nqr = false\n").unwrap();
+
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let child = Command::new(env!("CARGO_BIN_EXE_local-share"))
+            .arg("--config")
+            .arg(&config)
+            .arg("--path")
+            .arg(root.path())
+            .arg("--port")
+            .arg(port.to_string())
+            .env("HOME", home.path())
+            .env("XDG_CONFIG_HOME", home.path())
+            .env("XDG_DATA_HOME", home.path())
+            .env("RUST_LOG", "warn")
+            .stdout(Stdio::null())
+            .spawn()
+            .unwrap();
+
+        let server = Self { child, port, root, _home: home };
+        let deadline = Instant::now() + Duration::from_secs(20);
+        while ureq::get(&server.url("/health")).call().is_err() {
+            assert!(Instant::now() < deadline, "el servidor no arrancó");
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        server
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("http://127.0.0.1:{}{}", self.port, path)
+    }
+
+    pub fn download(&self, path: &str) -> Vec<u8> {
+        let response = ureq::get(&self.url(path)).call().unwrap();
+        let mut body = Vec::new();
+        response.into_reader().read_to_end(&mut body).unwrap();
+        body
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
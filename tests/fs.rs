@@ -0,0 +1,49 @@
+// API de gestión de archivos (`/api/fs`) contra el binario real.
+
+mod common;
+
+use common::Server;
+
+#[test]
+fn deletes_top_level_entries_named_like_fs_routes() {
+    let server = Server::start();
+    std::fs::write(server.root.path().join("copy"), "archivo").unwrap();
+    std::fs::create_dir(server.root.path().join("mkdir")).unwrap();
+
+    for name in ["copy", "mkdir"] {
+        let response = ureq::delete(&server.url(&format!("/api/fs/delete/{}", name))).call().unwrap();
+        assert_eq!(response.status(), 204, "{}", name);
+        assert!(!server.root.path().join(name).exists(), "{}", name);
+    }
+}
+
+/// Estado de un `POST` con cuerpo JSON, también si es un error.
+fn post_json(server: &Server, path: &str, body: &str) -> u16 {
+    match ureq::post(&server.url(path)).set("Content-Type", "application/json").send_string(body) {
+        Ok(response) => response.status(),
+        Err(ureq::Error::Status(status, _)) => status,
+        Err(e) => panic!("{}", e),
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn copy_and_move_into_themselves_through_symlinks_are_rejected() {
+    let server = Server::start();
+    let root = server.root.path();
+    std::fs::create_dir(root.join("sub")).unwrap();
+    std::fs::write(root.join("sub/a.txt"), "hola").unwrap();
+    std::os::unix::fs::symlink("sub", root.join("inside")).unwrap();
+
+    let body = r#"{"from": "/sub", "to": "/inside/sub2"}"#;
+    assert_eq!(post_json(&server, "/api/fs/copy", body), 400);
+    assert_eq!(post_json(&server, "/api/fs/move", body), 400);
+    assert!(!root.join("sub/sub2").exists());
+    assert!(root.join("sub/a.txt").exists());
+
+    // Copiar el enlace a su lado sí vale: copia la carpeta a la que apunta
+    assert_eq!(post_json(&server, "/api/fs/copy", r#"{"from": "/inside", "to": "/copia"}"#), 201);
+    assert_eq!(std::fs::read_to_string(root.join("copia/a.txt")).unwrap(), "hola");
+    // Y mover el propio enlace dentro de su destino no crea ningún bucle de copias
+    assert_eq!(post_json(&server, "/api/fs/move", r#"{"from": "/inside", "to": "/sub/link"}"#), 200);
+}
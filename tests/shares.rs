@@ -0,0 +1,99 @@
+// Límite de descargas y contraseñas de los enlaces compartidos contra el
+// binario real.
+
+mod common;
+
+use common::Server;
+
+fn status(request: ureq::Request) -> u16 {
+    match request.call() {
+        Ok(response) => response.status(),
+        Err(ureq::Error::Status(code, _)) => code,
+        Err(e) => panic!("{}", e),
+    }
+}
+
+/// Crea un enlace y devuelve su ruta (`/s/<token>`).
+fn create_share(server: &Server, path: &str, max_downloads: u32) -> String {
+    create_share_with(server, serde_json::json!({ "path": path, "max_downloads": max_downloads }))
+}
+
+fn create_share_with(server: &Server, body: serde_json::Value) -> String {
+    let body = body.to_string();
+    let response = ureq::post(&server.url("/api/shares"))
+        .set("Content-Type", "application/json")
+        .send_string(&body)
+        .unwrap();
+    let share: serde_json::Value = serde_json::from_reader(response.into_reader()).unwrap();
+    share["url"].as_str().unwrap().to_string()
+}
+
+#[test]
+fn exhausted_share_rejects_range_requests() {
+    let server = Server::start();
+    std::fs::write(server.root.path().join("a.txt"), "contenido del enlace\n").unwrap();
+
+    let url = create_share(&server, "a.txt", 1);
+
+    // Los HEAD no gastan usos
+    assert_eq!(status(ureq::head(&server.url(&url))), 200);
+    assert_eq!(server.download(&url), b"contenido del enlace\n");
+
+    // Agotado: ningún rango lo reabre
+    for range in ["bytes=1-", "bytes=0-0", "bytes=-5", "bytes=0-"] {
+        assert_eq!(status(ureq::get(&server.url(&url)).set("Range", range)), 410, "{}", range);
+    }
+    assert_eq!(status(ureq::get(&server.url(&url))), 410);
+}
+
+#[test]
+fn range_requests_count_as_downloads() {
+    let server = Server::start();
+    std::fs::write(server.root.path().join("b.txt"), "0123456789").unwrap();
+
+    let url = create_share(&server, "b.txt", 2);
+
+    assert_eq!(status(ureq::get(&server.url(&url)).set("Range", "bytes=1-")), 206);
+    assert_eq!(status(ureq::get(&server.url(&url)).set("Range", "bytes=-3")), 206);
+    assert_eq!(status(ureq::get(&server.url(&url)).set("Range", "bytes=5-")), 410);
+}
+
+#[test]
+fn wrong_share_passwords_back_off() {
+    let server = Server::start();
+    std::fs::write(server.root.path().join("c.txt"), "secreto").unwrap();
+    let url = create_share_with(&server, serde_json::json!({ "path": "c.txt", "password": "buena" }));
+
+    // Sin seguir la redirección, para ver a dónde manda cada intento
+    let agent = ureq::AgentBuilder::new().redirects(0).build();
+    let unlock = |password: &str| {
+        let response = agent.post(&server.url(&url)).send_form(&[("password", password)]).unwrap();
+        response.header("Location").unwrap_or_default().to_string()
+    };
+
+    for _ in 0..5 {
+        assert!(unlock("mala").ends_with("?error=1"));
+    }
+    assert!(unlock("mala").ends_with("?error=1"));
+    // Bloqueado: ni la contraseña buena se comprueba hasta que pase la espera
+    assert!(unlock("buena").ends_with("?error=wait"));
+
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+    assert_eq!(unlock("buena"), "");
+}
+
+#[test]
+fn failed_requests_do_not_use_up_downloads() {
+    let server = Server::start();
+    std::fs::write(server.root.path().join("d.txt"), "0123456789").unwrap();
+
+    let url = create_share(&server, "d.txt", 1);
+
+    assert_eq!(status(ureq::get(&server.url(&url)).set("Range", "bytes=50-")), 416);
+    std::fs::rename(server.root.path().join("d.txt"), server.root.path().join("e.txt")).unwrap();
+    assert_eq!(status(ureq::get(&server.url(&url))), 404);
+    std::fs::rename(server.root.path().join("e.txt"), server.root.path().join("d.txt")).unwrap();
+
+    assert_eq!(server.download(&url), b"0123456789");
+    assert_eq!(status(ureq::get(&server.url(&url))), 410);
+}